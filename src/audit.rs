@@ -0,0 +1,451 @@
+//! An append-only log of every change to the records, kept in `audit.log`
+//! in the data dir when `audit` is on. Each line is the HMAC-SHA256 of its
+//! entry followed by the entry as JSON, which holds the hash of the line
+//! before, so that changing, removing or reordering lines breaks the chain.
+//! The HMAC is keyed with a random key kept in the keyring as `audit`, so
+//! that the chain cannot be rebuilt by whoever can only write the data dir.
+//! Each entry also holds the digest of the day's records after the change,
+//! so that days changed without going through azk stand out, see [`verify`].
+//! Entries only hold keyed hashes of records, so that the log does not give
+//! away days that are encrypted. How many lines the log has and the hash of
+//! the last are kept in the config dir as well, where cutting lines off the
+//! end of the log shows.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, NaiveDate};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::model::{Record, RecordError};
+use crate::store::{DayError, Lock, Store};
+use crate::sync::hex;
+use crate::{config, secret};
+
+/// The log, in the data dir.
+pub const FILE_NAME: &str = "audit.log";
+
+/// The keyring entry holding the key the log is chained with.
+pub const SECRET: &str = "audit";
+
+/// Where the key is taken from without a keyring.
+const KEY_VAR: &str = "AZK_AUDIT_KEY";
+
+/// The file in the config dir with the [`Head`] of each log.
+const HEADS_FILE: &str = "audit-heads.json";
+
+/// What the first entry follows.
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Deserialize, Serialize)]
+struct Entry {
+    /// When the change was made, in RFC 3339.
+    time: String,
+    /// `baseline` for the days recorded when the log was started, `add` for
+    /// a record added, `write` for a day rewritten, `delete` for one
+    /// removed, and `pull` or `restore` for days changed by `azk sync` or
+    /// `azk backup restore`.
+    action: String,
+    date: NaiveDate,
+    /// The hash of the record added, or else of each of the day's records
+    /// after the change, see [`seal`].
+    records: Vec<String>,
+    /// The digest of the day's records after the change, see [`digest`].
+    digest: String,
+    /// The hash of the line before, or [`GENESIS`].
+    prev: String,
+}
+
+/// How many lines a log had when it was last appended to, and the hash of
+/// the last one.
+#[derive(Deserialize, Serialize)]
+struct Head {
+    lines: usize,
+    hash: String,
+}
+
+/// The key the lines are chained with, from the keyring or `$AZK_AUDIT_KEY`.
+/// To `create` a log, a random one is put in the keyring if there is none.
+fn key(create: bool) -> Result<String, Box<dyn Error>> {
+    if let Some(key) = secret::get(SECRET).ok().flatten() {
+        return Ok(key);
+    }
+    if let Some(key) = std::env::var(KEY_VAR).ok().filter(|key| !key.is_empty()) {
+        return Ok(key);
+    }
+    if !create {
+        Err(format!(
+            "the key of the audit log is neither in the keyring as '{SECRET}' nor in ${KEY_VAR}"
+        ))?
+    }
+    let mut bytes = [0; 32];
+    getrandom::fill(&mut bytes).map_err(|err| format!("no randomness for a key: {err}"))?;
+    let key = hex(&bytes);
+    secret::set(SECRET, &key)
+        .map_err(|err| format!("cannot keep the key of the audit log, {err}, or set ${KEY_VAR}"))?;
+    Ok(key)
+}
+
+/// The HMAC-SHA256 of `data`, for the hash of a line holding it as an entry
+/// or of a record.
+fn seal(key: &str, data: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(data);
+    hex(&mac.finalize().into_bytes())
+}
+
+/// The digest of a day's `records`, whichever store they are kept in.
+fn digest(key: &str, records: &[Record]) -> Result<String, Box<dyn Error>> {
+    Ok(seal(key, &serde_json::to_vec(records)?))
+}
+
+/// The hash and the entry of each line of the log at `path`, none if it is
+/// missing.
+fn read(path: &Path) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => Err(format!("{}: {err}", path.display()))?,
+    };
+    Ok(contents
+        .lines()
+        .map(|line| {
+            let (hash, entry) = line.split_once(' ').unwrap_or((line, ""));
+            (hash.to_owned(), entry.to_owned())
+        })
+        .collect())
+}
+
+/// The log of the records in a data dir.
+struct Log {
+    dir: PathBuf,
+    path: PathBuf,
+    lock: PathBuf,
+}
+
+impl Log {
+    fn new(dir: &Path) -> Log {
+        Log {
+            dir: dir.to_owned(),
+            path: dir.join(FILE_NAME),
+            lock: dir.join("audit.lock"),
+        }
+    }
+
+    /// The hash of the last line, or `None` if the log was not started.
+    fn last_hash(&self) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(read(&self.path)?.pop().map(|(hash, _)| hash))
+    }
+
+    /// The heads of the logs in all data dirs, and the file they are kept in.
+    fn heads() -> Result<(BTreeMap<String, Head>, PathBuf), Box<dyn Error>> {
+        let path = config::dir()?.join(HEADS_FILE);
+        let heads = match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .map_err(|err| format!("{}: {err}", path.display()))?,
+            Err(err) if err.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => Err(format!("{}: {err}", path.display()))?,
+        };
+        Ok((heads, path))
+    }
+
+    /// What the head of this log is filed under.
+    fn head_name(&self) -> String {
+        let dir = self.dir.canonicalize().unwrap_or_else(|_| self.dir.clone());
+        dir.to_string_lossy().into_owned()
+    }
+
+    /// Keeps `head` as the one of this log.
+    fn set_head(&self, head: Head) -> Result<(), Box<dyn Error>> {
+        let (mut heads, path) = Log::heads()?;
+        heads.insert(self.head_name(), head);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&temp, serde_json::to_vec_pretty(&heads)?)?;
+        fs::rename(&temp, &path)?;
+        Ok(())
+    }
+
+    /// The digest of each day as last logged, and the line it was logged in.
+    fn digests(&self) -> Result<BTreeMap<NaiveDate, (String, usize)>, Box<dyn Error>> {
+        let mut digests = BTreeMap::new();
+        for (index, (_, entry)) in read(&self.path)?.into_iter().enumerate() {
+            let entry: Entry = serde_json::from_str(&entry)
+                .map_err(|err| format!("{}:{}: {err}", self.path.display(), index + 1))?;
+            digests.insert(entry.date, (entry.digest, index + 1));
+        }
+        Ok(digests)
+    }
+
+    /// Appends entries for `changes` of `action`, each a day with the
+    /// records logged for it and its records after the change, chained with
+    /// `key`.
+    fn append(
+        &self,
+        key: &str,
+        action: &str,
+        changes: &[(NaiveDate, Vec<Record>, Vec<Record>)],
+    ) -> Result<(), Box<dyn Error>> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let logged = read(&self.path)?;
+        let mut prev = logged
+            .last()
+            .map_or_else(|| GENESIS.to_owned(), |(hash, _)| hash.clone());
+        let mut lines = String::new();
+        for (date, records, day) in changes {
+            let records = records
+                .iter()
+                .map(|record| Ok(seal(key, &serde_json::to_vec(record)?)))
+                .collect::<Result<_, serde_json::Error>>()?;
+            let entry = serde_json::to_string(&Entry {
+                time: Local::now().to_rfc3339(),
+                action: action.to_owned(),
+                date: *date,
+                records,
+                digest: digest(key, day)?,
+                prev,
+            })?;
+            prev = seal(key, entry.as_bytes());
+            lines += &format!("{prev} {entry}\n");
+        }
+        let mut file = File::options().create(true).append(true).open(&self.path)?;
+        file.write_all(lines.as_bytes())?;
+        file.sync_all()?;
+        self.set_head(Head {
+            lines: logged.len() + changes.len(),
+            hash: prev,
+        })
+    }
+
+    /// Takes the lock of the log, starting it with the days of `store` as
+    /// they are if it was not yet. Returns the lock and the key.
+    fn start(&self, store: &dyn Store) -> Result<(Lock, String), Box<dyn Error>> {
+        let lock = Lock::acquire(&self.lock)?;
+        if self.last_hash()?.is_some() {
+            return Ok((lock, key(false)?));
+        }
+        let key = key(true)?;
+        let mut days = Vec::new();
+        for date in store.list_days()? {
+            let records = store.read_day(date)?.unwrap_or_default();
+            days.push((date, records.clone(), records));
+        }
+        self.append(&key, "baseline", &days)?;
+        Ok((lock, key))
+    }
+}
+
+/// A store logging every change made through it, see the module docs.
+pub struct Audited {
+    inner: Box<dyn Store>,
+    log: Log,
+}
+
+impl Audited {
+    /// Logs the changes to `inner`, whose days are in `dir`.
+    pub fn new(inner: Box<dyn Store>, dir: &Path) -> Audited {
+        Audited {
+            inner,
+            log: Log::new(dir),
+        }
+    }
+}
+
+impl Store for Audited {
+    fn location(&self, date: NaiveDate) -> String {
+        self.inner.location(date)
+    }
+
+    fn file(&self, date: NaiveDate) -> PathBuf {
+        self.inner.file(date)
+    }
+
+    fn locate(&self, date: NaiveDate, index: usize) -> String {
+        self.inner.locate(date, index)
+    }
+
+    fn read_day(&self, date: NaiveDate) -> Result<Option<Vec<Record>>, DayError> {
+        self.inner.read_day(date)
+    }
+
+    fn visit_day(
+        &self,
+        date: NaiveDate,
+        visit: &mut dyn FnMut(&Record) -> Result<(), RecordError>,
+    ) -> Result<bool, DayError> {
+        self.inner.visit_day(date, visit)
+    }
+
+    fn append_record(&self, date: NaiveDate, record: Record) -> Result<(), Box<dyn Error>> {
+        let (_lock, key) = self.log.start(self.inner.as_ref())?;
+        self.inner.append_record(date, record.clone())?;
+        let day = self.inner.read_day(date)?.unwrap_or_default();
+        self.log.append(&key, "add", &[(date, vec![record], day)])
+    }
+
+    fn write_day(&self, date: NaiveDate, records: &[Record]) -> Result<(), Box<dyn Error>> {
+        let (_lock, key) = self.log.start(self.inner.as_ref())?;
+        self.inner.write_day(date, records)?;
+        let action = if records.is_empty() {
+            "delete"
+        } else {
+            "write"
+        };
+        // The digest is of the day as it reads back, which the store may
+        // have normalised.
+        let day = self.inner.read_day(date)?.unwrap_or_default();
+        self.log
+            .append(&key, action, &[(date, records.to_vec(), day)])
+    }
+
+    fn schema(&self, date: NaiveDate) -> Result<Option<u32>, DayError> {
+        self.inner.schema(date)
+    }
+
+    fn list_days(&self) -> Result<Vec<NaiveDate>, Box<dyn Error>> {
+        self.inner.list_days()
+    }
+
+    fn modified(&self, date: NaiveDate) -> Result<u64, Box<dyn Error>> {
+        self.inner.modified(date)
+    }
+
+    fn lock(&self) -> Result<Lock, Box<dyn Error>> {
+        self.inner.lock()
+    }
+}
+
+/// Logs the days of `store` in `dir` that differ from how they were last
+/// logged, as changed by `action`: those kept in the `files` relative to
+/// `dir`, or all of them. Returns how many were logged.
+pub fn note(
+    store: &dyn Store,
+    dir: &Path,
+    action: &str,
+    files: Option<&[String]>,
+) -> Result<usize, Box<dyn Error>> {
+    let log = Log::new(dir);
+    let (_lock, key) = log.start(store)?;
+    let digests = log.digests()?;
+    let mut dates: Vec<NaiveDate> = digests.keys().copied().collect();
+    dates.extend(store.list_days()?);
+    dates.sort();
+    dates.dedup();
+    let mut changes = Vec::new();
+    for date in dates {
+        if let Some(files) = files {
+            let file = store.file(date);
+            let Ok(relative) = file.strip_prefix(dir) else {
+                continue;
+            };
+            if !files.contains(&relative.to_string_lossy().replace('\\', "/")) {
+                continue;
+            }
+        }
+        let records = store.read_day(date)?.unwrap_or_default();
+        if digests.get(&date).map(|(digest, _)| digest) != Some(&digest(&key, &records)?) {
+            changes.push((date, records.clone(), records));
+        }
+    }
+    log.append(&key, action, &changes)?;
+    Ok(changes.len())
+}
+
+/// What [`verify`] checked.
+pub struct Verified {
+    pub entries: usize,
+    pub days: usize,
+    /// What was found changed since it was logged, one per line or day.
+    pub problems: Vec<String>,
+}
+
+/// Checks that no line of the log in `dir` was changed, removed or
+/// reordered, that it still ends in the line it was last appended, and that
+/// the records of `store` are as last logged.
+pub fn verify(store: &dyn Store, dir: &Path) -> Result<Verified, Box<dyn Error>> {
+    let log = Log::new(dir);
+    let _lock = Lock::acquire(&log.lock)?;
+    let lines = read(&log.path)?;
+    if lines.is_empty() {
+        Err(format!(
+            "{} was not started yet, set 'audit' to true and change a record",
+            log.path.display()
+        ))?
+    }
+    let key = key(false)?;
+    let mut problems = Vec::new();
+    let mut prev = GENESIS.to_owned();
+    let mut digests = BTreeMap::new();
+    for (index, (hash, entry)) in lines.iter().enumerate() {
+        let line = index + 1;
+        if seal(&key, entry.as_bytes()) != *hash {
+            problems.push(format!("line {line} was changed after it was logged"));
+        }
+        match serde_json::from_str::<Entry>(entry) {
+            Ok(entry) => {
+                if entry.prev != prev {
+                    problems.push(format!(
+                        "line {line} does not follow the line before, lines were removed or reordered"
+                    ));
+                }
+                digests.insert(entry.date, (entry.digest, line));
+            }
+            Err(err) => problems.push(format!("line {line} is not an entry: {err}")),
+        }
+        prev = hash.clone();
+    }
+    let (heads, heads_path) = Log::heads()?;
+    match heads.get(&log.head_name()) {
+        None => problems.push(format!(
+            "the end of the log is not in {}, lines may have been cut off",
+            heads_path.display()
+        )),
+        Some(head) if lines.len() < head.lines => problems.push(format!(
+            "the log ends at line {}, but was appended to up to line {}, lines were cut off",
+            lines.len(),
+            head.lines
+        )),
+        Some(head)
+            if lines.get(head.lines.wrapping_sub(1)).map(|(hash, _)| hash) != Some(&head.hash) =>
+        {
+            problems.push(format!(
+                "line {} is not the one last appended, the log was rewritten",
+                head.lines
+            ))
+        }
+        Some(_) => {}
+    }
+
+    let days = store.list_days()?;
+    for date in &days {
+        if !digests.contains_key(date) {
+            problems.push(format!(
+                "{} was recorded without being logged",
+                store.location(*date)
+            ));
+        }
+    }
+    for (date, (logged, line)) in &digests {
+        let records = store.read_day(*date)?.unwrap_or_default();
+        if digest(&key, &records)? != *logged {
+            problems.push(format!(
+                "{date} was changed since it was logged on line {line}"
+            ));
+        }
+    }
+    Ok(Verified {
+        entries: lines.len(),
+        days: days.len(),
+        problems,
+    })
+}
@@ -0,0 +1,261 @@
+//! Backups of the records and the config as a zstd-compressed tar archive,
+//! and restoring them. An archive holds `data/` and `config/` of the profile
+//! in use and a manifest saying what it is.
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::store::{self, read_work_time, Store};
+use crate::{audit, config, sync};
+
+const MANIFEST: &str = "azk-backup.json";
+
+/// The version of the archives written by this version of azk.
+const VERSION: u32 = 1;
+
+#[derive(Deserialize, Serialize)]
+pub struct Manifest {
+    pub version: u32,
+    /// When the backup was made, in RFC 3339.
+    pub created: String,
+    /// The profile backed up, or `None` for the main one.
+    pub profile: Option<String>,
+    /// How many days have records.
+    pub days: usize,
+}
+
+/// What was restored, and where the records replaced by it were saved.
+pub struct Restored {
+    pub manifest: Manifest,
+    pub saved: Option<PathBuf>,
+}
+
+/// Whether a restore leaves the file at `relative` alone: the locks, which
+/// may be held, the other profiles, which are backed up on their own, what
+/// was last synced, against which the restored records are changes, and the
+/// audit log, which only ever grows.
+fn kept(relative: &Path) -> bool {
+    relative.starts_with("profiles")
+        || relative == Path::new(sync::STATE)
+        || relative.starts_with(sync::BASE)
+        || relative == Path::new(audit::FILE_NAME)
+        || relative.extension().is_some_and(|ext| ext == "lock")
+}
+
+/// Whether the file at `relative` is left out of backups, as kept or as
+/// rebuilt when needed, like caches and temporary files.
+pub fn skipped(relative: &Path) -> bool {
+    let name = relative.file_name().unwrap_or_default().to_string_lossy();
+    kept(relative) || name.ends_with(".tmp") || name.ends_with("-cache.csv")
+}
+
+/// The files under `dir` relative to it, in order, or none if it is missing.
+pub fn files(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    fn walk(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+        let mut entries = fs::read_dir(dir.join(relative))?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let path = relative.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                walk(dir, &path, files)?;
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+    let mut found = Vec::new();
+    if dir.is_dir() {
+        walk(dir, Path::new(""), &mut found)?;
+    }
+    Ok(found)
+}
+
+/// Backs up the records of `store` and the config to an archive in `dest`,
+/// named after `now`, or to the file `dest` if it is not a directory.
+pub fn create(
+    store: &dyn Store,
+    dest: &Path,
+    now: DateTime<Local>,
+) -> Result<(PathBuf, Manifest), Box<dyn Error>> {
+    let profile = config::profile()?;
+    let path = if dest.is_dir() {
+        let name = match &profile {
+            Some(profile) => format!("azk-{profile}-{}.tar.zst", now.format("%Y%m%d-%H%M%S")),
+            None => format!("azk-{}.tar.zst", now.format("%Y%m%d-%H%M%S")),
+        };
+        dest.join(name)
+    } else {
+        dest.to_owned()
+    };
+    let data_dir = store::data_dir()?;
+    let config_dir = config::dir()?;
+
+    let _lock = store.lock()?;
+    let manifest = Manifest {
+        version: VERSION,
+        created: now.to_rfc3339(),
+        profile,
+        days: store.list_days()?.len(),
+    };
+    let file = File::create_new(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let mut archive = tar::Builder::new(zstd::Encoder::new(file, 0)?);
+    let contents = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(now.timestamp() as u64);
+    header.set_cksum();
+    archive.append_data(&mut header, MANIFEST, contents.as_slice())?;
+    for (dir, prefix) in [(&data_dir, "data"), (&config_dir, "config")] {
+        for relative in files(dir)? {
+            if !skipped(&relative) {
+                archive.append_path_with_name(
+                    dir.join(&relative),
+                    Path::new(prefix).join(&relative),
+                )?;
+            }
+        }
+    }
+    archive.into_inner()?.finish()?.sync_all()?;
+    Ok((path, manifest))
+}
+
+/// Unpacks the backup at `path` into `staging`, refusing anything but the
+/// manifest and files under `data/` and `config/`.
+fn unpack(path: &Path, staging: &Path) -> Result<Manifest, Box<dyn Error>> {
+    let file = File::open(path)?;
+    let mut archive = tar::Archive::new(zstd::Decoder::new(file)?);
+    let mut manifest = None;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.into_owned();
+        if name == Path::new(MANIFEST) {
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents)?;
+            manifest = Some(serde_json::from_str::<Manifest>(&contents)?);
+            continue;
+        }
+        let regular = matches!(
+            entry.header().entry_type(),
+            tar::EntryType::Regular | tar::EntryType::Directory
+        );
+        let inside = (name.starts_with("data") || name.starts_with("config"))
+            && name
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if !regular || !inside {
+            Err(format!(
+                "unexpected entry '{}', not restoring it",
+                name.display()
+            ))?
+        }
+        entry.unpack_in(staging)?;
+    }
+    let manifest = manifest.ok_or("not a backup of azk")?;
+    if manifest.version > VERSION {
+        Err("written by a newer version of azk")?
+    }
+    Ok(manifest)
+}
+
+/// Replaces the files of `dir` by those in `from`, except for those kept.
+fn replace(dir: &Path, from: &Path) -> Result<(), Box<dyn Error>> {
+    for relative in files(dir)? {
+        if !kept(&relative) {
+            fs::remove_file(dir.join(relative))?;
+        }
+    }
+    for relative in files(from)? {
+        let target = dir.join(&relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(from.join(&relative), target)?;
+    }
+    Ok(())
+}
+
+/// Restores the backup at `path` over the records of `store` and the config.
+/// Every day in it must read back. Records of days it does not have are only
+/// replaced if `force`. The records replaced are backed up next to `path`
+/// first.
+pub fn restore(
+    store: &dyn Store,
+    path: &Path,
+    force: bool,
+    now: DateTime<Local>,
+) -> Result<Restored, Box<dyn Error>> {
+    let data_dir = store::data_dir()?;
+    let mut staging = data_dir.clone().into_os_string();
+    staging.push(".restore");
+    let staging = PathBuf::from(staging);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+    let result = restore_from(store, path, &staging, force, now);
+    fs::remove_dir_all(&staging)?;
+    result
+}
+
+fn restore_from(
+    store: &dyn Store,
+    path: &Path,
+    staging: &Path,
+    force: bool,
+    now: DateTime<Local>,
+) -> Result<Restored, Box<dyn Error>> {
+    let manifest = unpack(path, staging).map_err(|err| format!("{}: {err}", path.display()))?;
+    let backend = config::load_from(&staging.join("config").join("config.toml"))?.store;
+    let restored = store::open_backend_in(&backend, staging.join("data"))?;
+    let days = restored.list_days()?;
+    for date in &days {
+        read_work_time(restored.as_ref(), *date, None)?;
+    }
+    if days.len() != manifest.days {
+        Err(format!(
+            "{} holds {} days, but was made with {}",
+            path.display(),
+            days.len(),
+            manifest.days
+        ))?
+    }
+
+    let current = store.list_days()?;
+    let days: BTreeSet<_> = days.into_iter().collect();
+    let missing: Vec<_> = current.iter().filter(|date| !days.contains(date)).collect();
+    if let (Some(last), false) = (missing.last(), force) {
+        Err(format!(
+            "{} recorded days are not in the backup, the last on {last}, restore with --force to replace them anyway",
+            missing.len()
+        ))?
+    }
+    let saved = if current.is_empty() {
+        None
+    } else {
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let name = format!("azk-replaced-{}.tar.zst", now.format("%Y%m%d-%H%M%S"));
+        Some(create(store, &dir.join(name), now)?.0)
+    };
+
+    let _lock = store.lock()?;
+    replace(&store::data_dir()?, &staging.join("data"))?;
+    replace(&config::dir()?, &staging.join("config"))?;
+    // The restored config may use another store.
+    let config = config::load()?;
+    if config.audit {
+        let restored = store::open_backend(&config.store)?;
+        audit::note(restored.as_ref(), &store::data_dir()?, "restore", None)?;
+    }
+    Ok(Restored { manifest, saved })
+}
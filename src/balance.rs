@@ -0,0 +1,188 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::purge;
+use crate::report::BreakRule;
+use crate::schedule::Schedule;
+use crate::store::{data_dir, Store};
+use crate::totals::{self, Totals};
+
+const CACHE_FILE: &str = "balance-cache.csv";
+
+/// A month's balance as computed before. It stays valid as long as the number
+/// of days, their newest modification time, the schedule, the break rules and
+/// the days off are unchanged.
+#[derive(Deserialize, Serialize)]
+struct CacheEntry {
+    month: String,
+    days: usize,
+    modified: u64,
+    schedule: String,
+    break_rules: String,
+    off: String,
+    balance: isize,
+}
+
+pub struct MonthBalance {
+    pub month: String,
+    pub days: usize,
+    pub balance: isize,
+}
+
+/// The balance running up to each of `months`, from the first one or, with
+/// `reset_monthly`, from the start of each.
+pub fn running(months: &[MonthBalance], reset_monthly: bool) -> Vec<isize> {
+    let mut running = 0;
+    months
+        .iter()
+        .map(|month| {
+            running = if reset_monthly {
+                month.balance
+            } else {
+                running + month.balance
+            };
+            running
+        })
+        .collect()
+}
+
+fn read_cache() -> HashMap<String, CacheEntry> {
+    if !totals::caching() {
+        return HashMap::new();
+    }
+    let Ok(mut rdr) = data_dir().and_then(|dir| Ok(csv::Reader::from_path(dir.join(CACHE_FILE))?))
+    else {
+        return HashMap::new();
+    };
+    rdr.deserialize::<CacheEntry>()
+        .filter_map(Result::ok)
+        .map(|entry| (entry.month.clone(), entry))
+        .collect()
+}
+
+/// Sums the difference between worked time, less deductions for `rules`, and
+/// the target of the `schedule` of every recorded day, per month. Days `off`
+/// are credited with their target, so only work on them counts. The days
+/// purged count with the totals kept of them. Past months are cached; the
+/// current one, which may have a running session, is always recomputed.
+pub fn monthly(
+    store: &dyn Store,
+    today: NaiveDate,
+    now: isize,
+    schedule: &Schedule,
+    rules: &[BreakRule],
+    off: &BTreeMap<NaiveDate, String>,
+) -> Result<Vec<MonthBalance>, Box<dyn Error>> {
+    let schedule_key = schedule.to_string();
+    let break_rules = rules
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut months: BTreeMap<String, Vec<NaiveDate>> = BTreeMap::new();
+    for date in store.list_days()? {
+        months
+            .entry(format!("{}", date.format("%Y-%m")))
+            .or_default()
+            .push(date);
+    }
+
+    let current_month = format!("{}", today.format("%Y-%m"));
+    let recent = totals::recent();
+    let cache = read_cache();
+    let mut new_cache = Vec::new();
+    let mut balances = Vec::new();
+
+    // The months whose balance is cached, and the days of the others, which
+    // are then read all at once.
+    let mut stale = Vec::new();
+    let mut checked = Vec::new();
+    for (month, dates) in months {
+        let mut modified = 0;
+        for date in &dates {
+            modified = modified.max(store.modified(*date)?);
+        }
+
+        let off_key = dates
+            .iter()
+            .filter(|date| off.contains_key(date))
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let cached = cache
+            .get(&month)
+            .filter(|entry| {
+                month != current_month
+                    && entry.days == dates.len()
+                    && entry.modified == modified
+                    && entry.schedule == schedule_key
+                    && entry.break_rules == break_rules
+                    && entry.off == off_key
+            })
+            .map(|entry| entry.balance);
+        if cached.is_none() {
+            stale.extend(&dates);
+        }
+        checked.push((month, dates, modified, off_key, cached));
+    }
+    let mut totals = Totals::load(rules);
+    let mut read = stale
+        .iter()
+        .zip(totals.of(store, &stale, today, now)?)
+        .map(|(date, total)| {
+            let target = if off.contains_key(date) {
+                0
+            } else {
+                schedule.target(*date)
+            };
+            total.net - target
+        });
+
+    for (month, dates, modified, off_key, cached) in checked {
+        let balance = match cached {
+            Some(balance) => balance,
+            None => read.by_ref().take(dates.len()).sum(),
+        };
+
+        // Like the totals, a month changed too recently is not cached.
+        if month != current_month && modified < recent {
+            new_cache.push(CacheEntry {
+                month: month.clone(),
+                days: dates.len(),
+                modified,
+                schedule: schedule_key.clone(),
+                break_rules: break_rules.clone(),
+                off: off_key,
+                balance,
+            });
+        }
+        balances.push(MonthBalance {
+            month,
+            days: dates.len(),
+            balance,
+        });
+    }
+
+    for total in purge::read_totals()?.into_values() {
+        let balance = total.seconds - total.target;
+        match balances.iter_mut().find(|month| month.month == total.month) {
+            Some(month) => {
+                month.days += total.days;
+                month.balance += balance;
+            }
+            None => balances.push(MonthBalance {
+                month: total.month,
+                days: total.days,
+                balance,
+            }),
+        }
+    }
+    balances.sort_by(|a, b| a.month.cmp(&b.month));
+
+    totals::write_cache(CACHE_FILE, &new_cache)?;
+    totals.save()?;
+    Ok(balances)
+}
@@ -0,0 +1,148 @@
+use std::error::Error;
+use std::fmt;
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate};
+
+use crate::model::DayInfo;
+use crate::report::{required_break, BreakRule};
+use crate::script::Script;
+use crate::store::{read_work_time, Store};
+use crate::time::s_to_hhmm;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Taken care of by reports, e.g. by deducting missing breaks.
+    Warning,
+    /// Breaks a limit that reports can't make up for.
+    Violation,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Violation => write!(f, "violation"),
+        }
+    }
+}
+
+pub struct Finding {
+    pub date: NaiveDate,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// The limits days are checked against, in seconds.
+pub struct Limits {
+    pub max_daily: isize,
+    pub min_rest: isize,
+    pub break_rules: Vec<BreakRule>,
+}
+
+/// Checks the days from `from` to `to` (inclusive) for more work than
+/// `max_daily`, for less rest than `min_rest` since the day before, which is
+/// read as well, for breaks that fall short of the break rules, and for
+/// what the user script finds, see `script`. Only `today` counts an
+/// unfinished session up to `now`.
+pub fn check(
+    store: &dyn Store,
+    from: NaiveDate,
+    to: NaiveDate,
+    today: NaiveDate,
+    now: isize,
+    limits: &Limits,
+) -> Result<Vec<Finding>, Box<dyn Error>> {
+    let mut findings = Vec::new();
+    let mut finding = |date, severity, message| {
+        findings.push(Finding {
+            date,
+            severity,
+            message,
+        })
+    };
+    let mut last_stop = last_stop(store, from - Duration::days(1))?;
+    let script = Script::load()?;
+
+    for date in from.iter_days().take_while(|date| *date <= to) {
+        let Some(info) = read_work_time(store, date, (date == today).then_some(now))? else {
+            last_stop = None;
+            continue;
+        };
+        let records = store.read_day(date)?.unwrap_or_default();
+
+        if let (Some(stop), Some(first)) = (last_stop, records.first()) {
+            let rest = (first.timestamp - stop).num_seconds() as isize;
+            if rest < limits.min_rest {
+                finding(
+                    date,
+                    Severity::Violation,
+                    format!(
+                        "only {} of rest before starting at {}, {} required",
+                        s_to_hhmm(rest),
+                        &first.time()[..5],
+                        s_to_hhmm(limits.min_rest)
+                    ),
+                );
+            }
+        }
+        if info.duration() > limits.max_daily {
+            finding(
+                date,
+                Severity::Violation,
+                format!(
+                    "worked {}, more than {}",
+                    s_to_hhmm(info.duration()),
+                    s_to_hhmm(limits.max_daily)
+                ),
+            );
+        }
+        let required = required_break(&info, &limits.break_rules);
+        if info.break_duration() < required {
+            finding(
+                date,
+                Severity::Warning,
+                format!(
+                    "{} of breaks after {} of work, {} required",
+                    s_to_hhmm(info.break_duration()),
+                    s_to_hhmm(info.duration()),
+                    s_to_hhmm(required)
+                ),
+            );
+        }
+
+        if let Some(script) = &script {
+            for (severity, message) in script.check_day(date, &info)? {
+                finding(date, severity, message);
+            }
+        }
+
+        last_stop = stopped_at(&info, records.last().map(|record| record.timestamp));
+    }
+    Ok(findings)
+}
+
+/// When work on `date` last stopped, if it did.
+fn last_stop(
+    store: &dyn Store,
+    date: NaiveDate,
+) -> Result<Option<DateTime<FixedOffset>>, Box<dyn Error>> {
+    let Some(info) = read_work_time(store, date, None)? else {
+        return Ok(None);
+    };
+    let records = store.read_day(date)?.unwrap_or_default();
+    Ok(stopped_at(
+        &info,
+        records.last().map(|record| record.timestamp),
+    ))
+}
+
+fn stopped_at(
+    info: &DayInfo,
+    last: Option<DateTime<FixedOffset>>,
+) -> Option<DateTime<FixedOffset>> {
+    if info.ongoing() {
+        None
+    } else {
+        last
+    }
+}
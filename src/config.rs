@@ -0,0 +1,519 @@
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, Offset};
+use serde::{Deserialize, Serialize};
+
+use crate::compliance::Limits;
+use crate::earnings::Rates;
+use crate::model::{DayInfo, Record};
+use crate::rounding::Rounding;
+use crate::schedule::Schedule;
+use crate::time::hhmm_to_s;
+use crate::{format, holidays, hooks, purge, report, store, sync, timesheet};
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// Target work duration per weekday, in HH:MM.
+    pub daily_target: String,
+    /// Target work duration per week, in HH:MM, unless there is a `schedule`.
+    pub weekly_target: String,
+    /// Targets per day of the week as in `mon-thu=08:00, fri=04:00`,
+    /// overriding `daily_target` on those days and nothing on weekends.
+    pub schedule: String,
+    /// Template for the output of `get` and `status`, see `--format`.
+    pub format: Option<String>,
+    /// Where the records are kept, or empty for the platform's data dir.
+    /// `$AZK_DATA_DIR` and `--data-dir` take precedence.
+    pub data_dir: String,
+    /// How records are kept, one of `store::BACKENDS`.
+    pub store: String,
+    /// Whether every change to the records is logged, see `audit`.
+    pub audit: bool,
+    /// How long days are kept before `azk purge` removes them, as in `2y`,
+    /// or empty to keep them all.
+    pub retention: String,
+    /// Whether `azk daemon` also purges as `retention` says, once a day.
+    /// Needs `retention_archive`, so that nothing is lost unattended.
+    pub auto_purge: bool,
+    /// The directory purged days are archived to, or empty to only delete
+    /// them.
+    pub retention_archive: String,
+    /// Whether the monthly totals of purged days are kept, see
+    /// `purge::TOTALS_FILE`.
+    pub retention_totals: bool,
+    /// Where `azk sync` keeps the records, one of `sync::BACKENDS`.
+    pub sync_backend: String,
+    /// The remote of `sync_backend`, or empty for none: a git remote as in
+    /// `git@example.com:me/azk-data.git`, a directory for rsync as in
+    /// `me@example.com:azk`, the URL of a WebDAV folder, or that of an S3
+    /// bucket and prefix as in `https://s3.example.com/bucket/azk`.
+    pub sync_remote: String,
+    /// The branch of `sync_remote` the records are on, with git.
+    pub sync_branch: String,
+    /// The username for WebDAV, or the access key for S3. The password or
+    /// secret key is in the keyring, see `sync::SECRET`.
+    pub sync_username: String,
+    /// The region of the S3 bucket.
+    pub sync_region: String,
+    /// The profile used without `--profile`, or empty for none. Only read
+    /// from the main config file, see [`profile`].
+    pub profile: String,
+    /// Seconds after a stamp in which `stamp` asks before stamping again.
+    pub min_gap: u32,
+    /// The region whose public holidays are days off, one of
+    /// `holidays::REGIONS`, or empty for none.
+    pub holidays: String,
+    /// Vacation days per year, shown against those taken with `azk off`.
+    pub vacation_days: u32,
+    /// Breaks required by law, as in `06:00=00:30, 09:00=00:45`. Reports take
+    /// time off days whose breaks fall short, see `report::deduction`.
+    pub break_rules: String,
+    /// The most work allowed per day, in HH:MM, see `compliance::check`.
+    pub max_daily: String,
+    /// The least rest required between two days of work, in HH:MM.
+    pub min_rest: String,
+    /// The GitLab that `azk export gitlab --push` posts to.
+    pub gitlab_url: String,
+    /// The project of issue references without one, like `#12`, as in
+    /// `group/project`.
+    pub gitlab_project: String,
+    /// URLs posted to after every start and stop, with the bearer token
+    /// from the keyring if there is one, see `hooks::SECRET`.
+    pub webhooks: Vec<String>,
+    /// The JSON posted to `webhooks`, with the placeholders of
+    /// `hooks::stamp_vars`, or empty for `hooks::DEFAULT_PAYLOAD`.
+    pub webhook_payload: String,
+    /// Minutes without input after which `azk daemon` stops work.
+    pub idle_minutes: u32,
+    /// Whether `azk daemon` notifies when the daily target is reached.
+    pub notify_target: bool,
+    /// Whether `azk daemon` notifies when `max_daily` is exceeded.
+    pub notify_max: bool,
+    /// Whether to notify when a stop takes a project past 80% or all of its
+    /// budget, see `azk project add --budget`.
+    pub notify_budget: bool,
+    /// How long to be idle while working, in HH:MM, before `azk daemon`
+    /// notifies of a break that was not stamped, or empty for never.
+    pub notify_break: String,
+    /// How long to work without a break, in HH:MM, before `azk watch` and
+    /// `azk daemon` remind of one, or empty for never.
+    pub break_reminder: String,
+    /// How long snoozing the break reminder puts it off, in HH:MM.
+    pub break_snooze: String,
+    /// The Slack status while working, as in `:hammer: working`, or empty to
+    /// leave the status alone. The token is read from the keyring, see
+    /// `slack::SECRET`.
+    pub slack_working: String,
+    /// The Slack status on a break stamped with `azk break`. Other stops
+    /// clear the status.
+    pub slack_break: String,
+    /// The MQTT broker that the state is published to on stamps, as in
+    /// `localhost:1883`, or empty for none.
+    pub mqtt_broker: String,
+    /// The topic under which `state` and `today` are published.
+    pub mqtt_topic: String,
+    /// The user to log in to the broker as, with the password from the
+    /// keyring, see `mqtt::SECRET`. Empty to connect anonymously.
+    pub mqtt_username: String,
+    /// The write endpoint of InfluxDB for `azk export influx --push`, with
+    /// a precision of seconds, as in
+    /// `http://localhost:8086/api/v2/write?org=me&bucket=azk&precision=s`.
+    /// The token is taken from the keyring, see `export::INFLUX_SECRET`.
+    pub influx_url: String,
+    /// Hourly rates per project as in `acme=95, globex=120.50, *=80`, where
+    /// `*` stands for the other projects, see `report --earnings`.
+    pub rates: String,
+    /// The currency of the `rates`, as in `EUR`.
+    pub currency: String,
+    /// How `report` and `export` round durations, as in `nearest:15`,
+    /// `up:6` or `down:5` in minutes, or empty for exact durations.
+    pub rounding: String,
+    /// Whether `rounding` applies to each interval or to the total of each
+    /// day, one of `rounding::SCOPES`.
+    pub rounding_per: String,
+    /// Whether durations are printed in decimal hours, as with `--decimal`.
+    pub decimal_hours: bool,
+    /// The heading of `azk timesheet`, with the placeholders `{month}`,
+    /// `{month_name}` and `{name}`.
+    pub timesheet_title: String,
+    /// The name of whoever the timesheet is for, printed under the heading.
+    pub timesheet_name: String,
+    /// Who signs the timesheet, as in `Employee, Supervisor`, with a line each.
+    pub timesheet_signatures: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            daily_target: "08:00".to_owned(),
+            weekly_target: "40:00".to_owned(),
+            schedule: String::new(),
+            format: None,
+            data_dir: String::new(),
+            store: "csv".to_owned(),
+            audit: false,
+            retention: String::new(),
+            auto_purge: false,
+            retention_archive: String::new(),
+            retention_totals: true,
+            sync_backend: "git".to_owned(),
+            sync_remote: String::new(),
+            sync_branch: "main".to_owned(),
+            sync_username: String::new(),
+            sync_region: "us-east-1".to_owned(),
+            profile: String::new(),
+            min_gap: 60,
+            holidays: String::new(),
+            vacation_days: 0,
+            break_rules: String::new(),
+            max_daily: "10:00".to_owned(),
+            min_rest: "11:00".to_owned(),
+            gitlab_url: "https://gitlab.com".to_owned(),
+            gitlab_project: String::new(),
+            webhooks: Vec::new(),
+            webhook_payload: String::new(),
+            idle_minutes: 10,
+            notify_target: false,
+            notify_max: false,
+            notify_budget: false,
+            notify_break: String::new(),
+            break_reminder: String::new(),
+            break_snooze: "00:10".to_owned(),
+            slack_working: String::new(),
+            slack_break: ":coffee: break".to_owned(),
+            mqtt_broker: String::new(),
+            mqtt_topic: "azk".to_owned(),
+            mqtt_username: String::new(),
+            influx_url: String::new(),
+            rates: String::new(),
+            currency: String::new(),
+            rounding: String::new(),
+            rounding_per: "interval".to_owned(),
+            decimal_hours: false,
+            timesheet_title: "Timesheet {month_name}".to_owned(),
+            timesheet_name: String::new(),
+            timesheet_signatures: "Employee, Supervisor".to_owned(),
+        }
+    }
+}
+
+/// The environment variable naming the profile to use, which `--profile`
+/// sets too.
+pub const PROFILE_VAR: &str = "AZK_PROFILE";
+
+const FILE_NAME: &str = "config.toml";
+
+/// The config dir of no profile, which holds the main config file.
+fn main_dir() -> Result<PathBuf, Box<dyn Error>> {
+    if let Some(proj_dirs) = directories::ProjectDirs::from("com", "hylo", "azk") {
+        return Ok(proj_dirs.config_dir().to_owned());
+    }
+    Err("path error")?
+}
+
+/// Checks that `name` can name a profile, and so a directory.
+pub fn check_profile(name: &str) -> Result<(), Box<dyn Error>> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Err(format!(
+            "invalid profile '{name}', expected letters, digits, - and _"
+        ))?
+    }
+    Ok(())
+}
+
+/// The profile in use, from `$AZK_PROFILE` or else the `profile` key of the
+/// main config file, or `None` to use the main config and data. Each profile
+/// has a config dir and a data dir of its own, under `profiles`.
+pub fn profile() -> Result<Option<String>, Box<dyn Error>> {
+    let profile = match std::env::var(PROFILE_VAR) {
+        Ok(profile) if !profile.is_empty() => profile,
+        _ => match read_table(&main_dir()?.join(FILE_NAME))?.remove("profile") {
+            Some(toml::Value::String(profile)) if !profile.is_empty() => profile,
+            _ => return Ok(None),
+        },
+    };
+    check_profile(&profile)?;
+    Ok(Some(profile))
+}
+
+/// The config dir of the profile in use, which holds `config.toml` and the
+/// `hooks` scripts.
+pub fn dir() -> Result<PathBuf, Box<dyn Error>> {
+    let dir = main_dir()?;
+    Ok(match profile()? {
+        Some(profile) => dir.join("profiles").join(profile),
+        None => dir,
+    })
+}
+
+/// The config file, in the config dir.
+pub fn path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(dir()?.join(FILE_NAME))
+}
+
+/// Reads the config file at `path` as written, without defaults for missing
+/// keys.
+fn read_table(path: &Path) -> Result<toml::Table, Box<dyn Error>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => {
+            Ok(toml::from_str(&contents).map_err(|err| format!("{}: {err}", path.display()))?)
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(toml::Table::new()),
+        Err(err) => Err(err)?,
+    }
+}
+
+/// The `data_dir` key of the config file, if it is set.
+pub fn data_dir() -> Result<Option<PathBuf>, Box<dyn Error>> {
+    Ok(match read_table(&path()?)?.remove("data_dir") {
+        Some(toml::Value::String(dir)) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => None,
+    })
+}
+
+/// Reads `config.toml` from the config dir, falling back to defaults for missing keys.
+pub fn load() -> Result<Config, Box<dyn Error>> {
+    load_from(&path()?)
+}
+
+/// Reads the config file at `path` rather than the one of the config dir.
+pub fn load_from(path: &Path) -> Result<Config, Box<dyn Error>> {
+    let table = read_table(path)?;
+    Ok(table
+        .try_into()
+        .map_err(|err| format!("{}: {err}", path.display()))?)
+}
+
+/// The limits of `max_daily`, `min_rest` and `break_rules`.
+pub fn limits(config: &Config) -> Result<Limits, Box<dyn Error>> {
+    Ok(Limits {
+        max_daily: hhmm_to_s(&config.max_daily)?,
+        min_rest: hhmm_to_s(&config.min_rest)?,
+        break_rules: report::parse_break_rules(&config.break_rules)?,
+    })
+}
+
+/// The target of each day of the week, `daily_target` on weekdays and
+/// nothing on weekends unless the `schedule` says otherwise.
+pub fn schedule(config: &Config) -> Result<Schedule, Box<dyn Error>> {
+    Schedule::parse(&config.schedule, hhmm_to_s(&config.daily_target)?)
+}
+
+/// The sum of the `schedule` if there is one, else `weekly_target`.
+pub fn weekly_target(config: &Config) -> Result<isize, Box<dyn Error>> {
+    if config.schedule.trim().is_empty() {
+        hhmm_to_s(&config.weekly_target)
+    } else {
+        Ok(schedule(config)?.weekly())
+    }
+}
+
+/// The effective configuration, defaults included.
+pub fn list() -> Result<toml::Table, Box<dyn Error>> {
+    Ok(toml::Table::try_from(load()?)?)
+}
+
+pub fn get(key: &str) -> Result<toml::Value, Box<dyn Error>> {
+    Ok(list()?
+        .remove(key)
+        .ok_or_else(|| format!("unknown config key '{key}'"))?)
+}
+
+/// Validates `value` for `key` and writes it to the config file.
+pub fn set(key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+    let value = match key {
+        "daily_target" | "weekly_target" | "max_daily" | "min_rest" | "break_snooze" => {
+            hhmm_to_s(value)?;
+            toml::Value::String(value.to_owned())
+        }
+        "format" => {
+            let vars = format::day_vars("", &DayInfo::default(), 0, 0);
+            format::render(value, &vars)?;
+            toml::Value::String(value.to_owned())
+        }
+        "schedule" => {
+            Schedule::parse(value, 0)?;
+            toml::Value::String(value.to_owned())
+        }
+        "holidays" => {
+            if !value.is_empty() && !holidays::REGIONS.contains(&value) {
+                Err(format!(
+                    "unknown region '{value}', expected one of {}",
+                    holidays::REGIONS.join(", ")
+                ))?
+            }
+            toml::Value::String(value.to_owned())
+        }
+        "sync_backend" => {
+            if !sync::BACKENDS.contains(&value) {
+                Err(format!(
+                    "unknown sync backend '{value}', expected one of {}",
+                    sync::BACKENDS.join(", ")
+                ))?
+            }
+            toml::Value::String(value.to_owned())
+        }
+        "sync_branch" => {
+            if value.is_empty() || value.starts_with('-') || value.contains(char::is_whitespace) {
+                Err(format!("invalid branch '{value}'"))?
+            }
+            toml::Value::String(value.to_owned())
+        }
+        "profile" => {
+            if !value.is_empty() {
+                check_profile(value)?;
+            }
+            toml::Value::String(value.to_owned())
+        }
+        "store" => {
+            if !store::BACKENDS.contains(&value) {
+                Err(format!(
+                    "unknown store '{value}', expected one of {}",
+                    store::BACKENDS.join(", ")
+                ))?
+            }
+            toml::Value::String(value.to_owned())
+        }
+        "gitlab_url"
+        | "gitlab_project"
+        | "slack_working"
+        | "slack_break"
+        | "mqtt_broker"
+        | "mqtt_topic"
+        | "mqtt_username"
+        | "influx_url"
+        | "sync_remote"
+        | "sync_username"
+        | "sync_region"
+        | "retention_archive"
+        | "currency"
+        | "timesheet_name"
+        | "timesheet_signatures" => toml::Value::String(value.to_owned()),
+        "timesheet_title" => {
+            let vars = timesheet::title_vars(&Config::default(), Local::now().date_naive());
+            format::render(value, &vars)?;
+            toml::Value::String(value.to_owned())
+        }
+        "rounding" => {
+            Rounding::parse(value, "interval")?;
+            toml::Value::String(value.to_owned())
+        }
+        "rounding_per" => {
+            Rounding::parse("nearest:15", value)?;
+            toml::Value::String(value.to_owned())
+        }
+        "rates" => {
+            Rates::parse(value)?;
+            toml::Value::String(value.to_owned())
+        }
+        "webhooks" => {
+            let mut urls = Vec::new();
+            for url in value
+                .split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+            {
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    Err(format!(
+                        "invalid webhook '{url}', expected an http or https URL"
+                    ))?
+                }
+                urls.push(toml::Value::String(url.to_owned()));
+            }
+            toml::Value::Array(urls)
+        }
+        "webhook_payload" => {
+            let config = Config {
+                webhook_payload: value.to_owned(),
+                ..Config::default()
+            };
+            let now = Local::now();
+            let record = Record {
+                kind: "strt".to_owned(),
+                timestamp: now.with_timezone(&now.offset().fix()),
+                project: None,
+                note: None,
+            };
+            hooks::payload(&config, &hooks::stamp_vars(now.date_naive(), &record, 0))?;
+            toml::Value::String(value.to_owned())
+        }
+        "break_rules" => {
+            report::parse_break_rules(value)?;
+            toml::Value::String(value.to_owned())
+        }
+        "min_gap" => {
+            let seconds: u32 = value
+                .parse()
+                .map_err(|_| format!("invalid number of seconds '{value}'"))?;
+            toml::Value::Integer(seconds.into())
+        }
+        "data_dir" => {
+            if !value.is_empty() && !Path::new(value).is_absolute() {
+                Err(format!(
+                    "invalid data_dir '{value}', expected an absolute path"
+                ))?
+            }
+            toml::Value::String(value.to_owned())
+        }
+        "retention" => {
+            if !value.is_empty() {
+                purge::cutoff(value, Local::now().date_naive())?;
+            }
+            toml::Value::String(value.to_owned())
+        }
+        "notify_target" | "notify_max" | "notify_budget" | "decimal_hours" | "audit"
+        | "auto_purge" | "retention_totals" => {
+            let enabled: bool = value
+                .parse()
+                .map_err(|_| format!("invalid value '{value}', expected true or false"))?;
+            toml::Value::Boolean(enabled)
+        }
+        "notify_break" | "break_reminder" => {
+            if !value.is_empty() {
+                hhmm_to_s(value)?;
+            }
+            toml::Value::String(value.to_owned())
+        }
+        "idle_minutes" => {
+            let minutes: u32 = value
+                .parse()
+                .map_err(|_| format!("invalid number of minutes '{value}'"))?;
+            toml::Value::Integer(minutes.into())
+        }
+        "vacation_days" => {
+            let days: u32 = value
+                .parse()
+                .map_err(|_| format!("invalid number of days '{value}'"))?;
+            toml::Value::Integer(days.into())
+        }
+        _ => Err(format!("unknown config key '{key}'"))?,
+    };
+
+    // The default profile is always set in the main config file.
+    let path = if key == "profile" {
+        main_dir()?.join(FILE_NAME)
+    } else {
+        path()?
+    };
+    let mut table = read_table(&path)?;
+    table.insert(key.to_owned(), value);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, toml::to_string(&table)?)?;
+    Ok(())
+}
+
+/// The `rounding` applied per `rounding_per`, unless overridden by `policy`.
+pub fn rounding(config: &Config, policy: Option<&str>) -> Result<Option<Rounding>, Box<dyn Error>> {
+    Rounding::parse(policy.unwrap_or(&config.rounding), &config.rounding_per)
+}
@@ -0,0 +1,274 @@
+use std::error::Error;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::Offset;
+
+use azk::config::{self, Config};
+use azk::notify::{BreakReminder, Milestones};
+use azk::store::{read_work_time, update_time, Store};
+use azk::time::hhmmss_to_s;
+use azk::{off, purge};
+
+use crate::{dbus, run_hooks};
+
+/// How often the idle time is checked.
+const POLL: Duration = Duration::from_secs(15);
+
+/// How long the user has not touched the keyboard or mouse, and whether the
+/// session is locked.
+struct Idle {
+    seconds: u64,
+    locked: bool,
+}
+
+/// The idle time of the X11 display, through `xprintidle`.
+fn x11_idle() -> Option<u64> {
+    std::env::var_os("DISPLAY")?;
+    let output = Command::new("xprintidle").output().ok()?;
+    let millis: u64 = String::from_utf8(output.stdout).ok()?.trim().parse().ok()?;
+    Some(millis / 1000)
+}
+
+/// The idle and lock hints logind keeps for the session, which Wayland
+/// compositors and screen lockers set.
+fn logind_idle() -> Option<Idle> {
+    let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".to_owned());
+    let output = Command::new("loginctl")
+        .args(["show-session", &session])
+        .args(["-p", "IdleHint", "-p", "IdleSinceHint", "-p", "LockedHint"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let text = String::from_utf8(output.stdout).ok()?;
+    let hint = |name: &str| {
+        text.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix('='))
+            .unwrap_or("")
+    };
+    let seconds = if hint("IdleHint") == "yes" {
+        let since: u64 = hint("IdleSinceHint").parse().ok()?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_micros() as u64;
+        now.saturating_sub(since) / 1_000_000
+    } else {
+        0
+    };
+    Some(Idle {
+        seconds,
+        locked: hint("LockedHint") == "yes",
+    })
+}
+
+/// The idle time by whatever tells it, or nothing if nothing does.
+fn idle() -> Option<Idle> {
+    match (x11_idle(), logind_idle()) {
+        (None, None) => None,
+        (x11, logind) => Some(Idle {
+            seconds: x11
+                .unwrap_or(0)
+                .max(logind.as_ref().map_or(0, |idle| idle.seconds)),
+            locked: logind.is_some_and(|idle| idle.locked),
+        }),
+    }
+}
+
+/// Sends true whenever logind is about to suspend the system and false when
+/// it woke up again, through `gdbus monitor`. Returns without sending
+/// anything if that cannot be run.
+fn watch_sleep(sender: Sender<bool>) -> Result<(), Box<dyn Error>> {
+    let mut child = Command::new("gdbus")
+        .args(["monitor", "--system", "--dest", "org.freedesktop.login1"])
+        .args(["--object-path", "/org/freedesktop/login1"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("gdbus: {err}"))?;
+    let stdout = child.stdout.take().ok_or("gdbus has no output")?;
+    for line in BufReader::new(stdout).lines() {
+        let line = line?;
+        let Some((_, args)) = line.split_once("PrepareForSleep (") else {
+            continue;
+        };
+        if sender.send(args.starts_with("true")).is_err() {
+            break;
+        }
+    }
+    child.kill()?;
+    Ok(())
+}
+
+/// Stops work before the system suspends, so that the time asleep is a
+/// break. Once awake, work starts again on activity as after being idle.
+fn suspend(store: &dyn Store, resume: &mut Option<Option<String>>) -> Result<(), Box<dyn Error>> {
+    let now = chrono::Local::now();
+    let today = now.date_naive();
+    let time = format!("{}", now.format("%H:%M:%S"));
+    let Some(day) = read_work_time(store, today, None)? else {
+        return Ok(());
+    };
+    let Some(last) = day.intervals.last().filter(|last| last.ongoing) else {
+        return Ok(());
+    };
+    if let Some(record) = update_time(store, today, &time, Some("stop:sleep"), None, None)? {
+        println!("{time} Stopped for suspend.");
+        run_hooks(store, today, &record);
+        *resume = Some(last.project.clone());
+    }
+    Ok(())
+}
+
+/// Checks the idle time once, stopping or starting work again as needed.
+/// `resume` holds the project to start again with once work was stopped for
+/// being idle.
+fn check(
+    store: &dyn Store,
+    limit: u64,
+    resume: &mut Option<Option<String>>,
+) -> Result<Option<Idle>, Box<dyn Error>> {
+    let now = chrono::Local::now();
+    let today = now.date_naive();
+    let time = format!("{}", now.format("%H:%M:%S"));
+    let Some(idle) = idle() else {
+        return Ok(None);
+    };
+    let day = read_work_time(store, today, None)?;
+    let ongoing = day
+        .as_ref()
+        .and_then(|day| day.intervals.last())
+        .filter(|last| last.ongoing);
+
+    if let Some(last) =
+        ongoing.filter(|_| resume.is_none() && (idle.locked || idle.seconds >= limit))
+    {
+        // Work stops when the session went idle, but not before it started,
+        // e.g. from another device.
+        let since = (now - chrono::Duration::seconds(idle.seconds as i64))
+            .max(last.started_at.with_timezone(&chrono::Local));
+        let since = format!("{}", since.format("%H:%M:%S"));
+        if let Some(record) = update_time(store, today, &since, Some("stop:idle"), None, None)? {
+            println!("{time} Stopped at {}, idle since.", record.time());
+            run_hooks(store, today, &record);
+            *resume = Some(last.project.clone());
+        }
+    } else if !idle.locked && idle.seconds < POLL.as_secs() {
+        if let Some(project) = resume.take() {
+            let record = if ongoing.is_some() {
+                None
+            } else {
+                update_time(store, today, &time, Some("strt"), project.as_ref(), None)?
+            };
+            if let Some(record) = record {
+                println!("{time} Started again.");
+                run_hooks(store, today, &record);
+            }
+        }
+    }
+    Ok(Some(idle))
+}
+
+/// Notifies of the milestones of today and reminds of breaks, see
+/// [`Milestones::check`] and [`BreakReminder::check`].
+fn notify(
+    store: &dyn Store,
+    config: &Config,
+    milestones: &mut Milestones,
+    reminder: &mut BreakReminder,
+    idle: &Idle,
+) -> Result<(), Box<dyn Error>> {
+    let now = chrono::Local::now();
+    let today = now.date_naive();
+    let now_s = hhmmss_to_s(&format!("{}", now.format("%H:%M:%S")));
+    let Some(day) = read_work_time(store, today, Some(now_s))? else {
+        return Ok(());
+    };
+    let target = if off::read_with_holidays(&config.holidays, today, today)?.is_empty() {
+        config::schedule(config)?.target(today)
+    } else {
+        0
+    };
+    milestones.check(config, today, &day, target, idle.seconds as isize)?;
+    reminder.check(config, &day, now.with_timezone(&now.offset().fix()))
+}
+
+/// Stops work once the session has been idle for `idle_minutes` or is
+/// locked, as of when it went idle, and starts it again on the project it
+/// was on when there is activity. Work also stops when the system suspends.
+/// Work stopped by hand is left alone. Notifies of milestones as configured,
+/// purges old days once a day with `auto_purge` on and serves `com.hylo.azk`
+/// on D-Bus. Runs until Ctrl-C.
+pub fn daemon(store: &dyn Store, config: &Config, idle_minutes: u32) -> Result<(), Box<dyn Error>> {
+    if idle().is_none() {
+        Err("cannot tell the idle time, install xprintidle or run in a logind session")?
+    }
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_running = running.clone();
+    ctrlc::set_handler(move || handler_running.store(false, Ordering::SeqCst))?;
+
+    let (sender, sleep) = mpsc::channel();
+    thread::spawn(move || {
+        if let Err(err) = watch_sleep(sender) {
+            eprintln!("Not stopping work on suspend, {err}");
+        }
+    });
+
+    let connection = dbus::serve()
+        .map_err(|err| eprintln!("Not serving D-Bus, {err}"))
+        .ok();
+    let mut properties = None;
+
+    let limit = u64::from(idle_minutes) * 60;
+    let mut resume = None;
+    let mut milestones = Milestones::default();
+    let mut reminder = BreakReminder::default();
+    let mut purged_on = None;
+    while running.load(Ordering::SeqCst) {
+        // The retention policy is applied once a day, if asked to.
+        let today = chrono::Local::now().date_naive();
+        if purged_on != Some(today) {
+            purged_on = Some(today);
+            match purge::apply(store, config, today) {
+                Ok(purged) if !purged.days.is_empty() => {
+                    eprintln!("Purged {} days as set by retention.", purged.days.len());
+                }
+                Ok(_) => {}
+                Err(err) => eprintln!("Cannot purge, {err}"),
+            }
+        }
+        // A failure, like a day read while it is written, is tried again
+        // at the next poll rather than stopping the daemon.
+        match check(store, limit, &mut resume) {
+            Ok(Some(idle)) => {
+                if let Err(err) = notify(store, config, &mut milestones, &mut reminder, &idle) {
+                    eprintln!("Cannot notify, {err}");
+                }
+            }
+            Ok(None) => {}
+            Err(err) => eprintln!("Cannot check for idleness, {err}"),
+        }
+        if let Some(connection) = &connection {
+            if let Err(err) = dbus::refresh(connection, &mut properties) {
+                eprintln!("Cannot signal D-Bus, {err}");
+            }
+        }
+        for _ in 0..POLL.as_millis() / 100 {
+            if !running.load(Ordering::SeqCst) {
+                break;
+            }
+            if let Ok(true) = sleep.try_recv() {
+                if let Err(err) = suspend(store, &mut resume) {
+                    eprintln!("Cannot stop work on suspend, {err}");
+                }
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use std::error::Error;
+
+use zbus::blocking::Connection;
+use zbus::{fdo, SignalContext};
+
+use azk::hooks;
+use azk::model::DayInfo;
+use azk::store::{self, read_work_time, update_time};
+use azk::time::{hhmmss_to_s, s_to_hhmm};
+
+/// The well-known name of the service on the session bus.
+const NAME: &str = "com.hylo.azk";
+const PATH: &str = "/com/hylo/azk";
+
+/// Today's work as of now, if there is any.
+fn today() -> Result<Option<DayInfo>, Box<dyn Error>> {
+    let now = chrono::Local::now();
+    let now_s = hhmmss_to_s(&format!("{}", now.format("%H:%M:%S")));
+    Ok(read_work_time(
+        store::open()?.as_ref(),
+        now.date_naive(),
+        Some(now_s),
+    )?)
+}
+
+fn state(day: Option<&DayInfo>) -> &'static str {
+    match day {
+        Some(day) if day.ongoing() => "working",
+        Some(day) if !day.intervals.is_empty() => "break",
+        _ => "idle",
+    }
+}
+
+/// Stamps now and runs the hooks, returning the time stamped.
+fn stamp() -> Result<String, Box<dyn Error>> {
+    let now = chrono::Local::now();
+    let today = now.date_naive();
+    let time = format!("{}", now.format("%H:%M:%S"));
+    let store = store::open()?;
+    let record = update_time(store.as_ref(), today, &time, None, None, None)?
+        .ok_or("nothing was stamped")?;
+    for failure in hooks::stamped(store.as_ref(), today, &record) {
+        eprintln!("{failure}");
+    }
+    Ok(time)
+}
+
+fn failed(err: Box<dyn Error>) -> fdo::Error {
+    fdo::Error::Failed(err.to_string())
+}
+
+struct Service;
+
+#[zbus::interface(name = "com.hylo.azk")]
+impl Service {
+    /// Toggles between work and break, as `azk stamp` does. Returns the time
+    /// stamped.
+    async fn stamp(
+        &self,
+        #[zbus(signal_context)] context: SignalContext<'_>,
+    ) -> fdo::Result<String> {
+        let time = stamp().map_err(failed)?;
+        self.state_changed(&context).await?;
+        self.today_changed(&context).await?;
+        Ok(time)
+    }
+
+    /// What `azk status` says, on one line.
+    fn status(&self) -> fdo::Result<String> {
+        let day = today().map_err(failed)?;
+        Ok(match &day {
+            Some(day) if day.ongoing() => format!("Working, {} today.", s_to_hhmm(day.duration())),
+            Some(day) if !day.intervals.is_empty() => {
+                format!("On a break, {} today.", s_to_hhmm(day.duration()))
+            }
+            _ => "Work hasn't started yet.".to_owned(),
+        })
+    }
+
+    /// The state, seconds of work and start of work of today, with the start
+    /// in HH:MM or empty if work has not started.
+    fn get_today(&self) -> fdo::Result<(String, i64, String)> {
+        let day = today().map_err(failed)?;
+        let (duration, start) = match &day {
+            Some(day) if !day.intervals.is_empty() => {
+                (day.duration() as i64, s_to_hhmm(day.start()))
+            }
+            _ => (0, String::new()),
+        };
+        Ok((state(day.as_ref()).to_owned(), duration, start))
+    }
+
+    /// `working`, `break` or `idle` if work has not started today.
+    #[zbus(property)]
+    fn state(&self) -> fdo::Result<String> {
+        Ok(state(today().map_err(failed)?.as_ref()).to_owned())
+    }
+
+    /// Seconds of work today.
+    #[zbus(property)]
+    fn today(&self) -> fdo::Result<i64> {
+        Ok(today()
+            .map_err(failed)?
+            .map_or(0, |day| day.duration() as i64))
+    }
+}
+
+/// Serves `com.hylo.azk` on the session bus until the connection is dropped.
+pub fn serve() -> Result<Connection, Box<dyn Error>> {
+    Ok(zbus::blocking::connection::Builder::session()?
+        .name(NAME)?
+        .serve_at(PATH, Service)?
+        .build()?)
+}
+
+/// Signals that the properties changed, e.g. by stamping from the command
+/// line, unless they are the same as `last`.
+pub fn refresh(
+    connection: &Connection,
+    last: &mut Option<(String, i64)>,
+) -> Result<(), Box<dyn Error>> {
+    let day = today()?;
+    let current = (
+        state(day.as_ref()).to_owned(),
+        day.map_or(0, |day| day.duration() as i64),
+    );
+    if last.as_ref() == Some(&current) {
+        return Ok(());
+    }
+    let iface = connection.object_server().interface::<_, Service>(PATH)?;
+    let context = iface.signal_context();
+    if last.as_ref().is_none_or(|(state, _)| *state != current.0) {
+        zbus::block_on(iface.get().state_changed(context))?;
+    }
+    if last.as_ref().is_none_or(|(_, today)| *today != current.1) {
+        zbus::block_on(iface.get().today_changed(context))?;
+    }
+    *last = Some(current);
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use std::error::Error;
+
+use chrono::NaiveDate;
+
+use crate::model::{DayInfo, Record};
+use crate::store::Store;
+
+pub struct Problem {
+    pub description: String,
+    /// Whether [`Diagnosis::fix`] takes care of it.
+    pub fixable: bool,
+}
+
+/// What is wrong with a day.
+pub struct Diagnosis {
+    pub date: NaiveDate,
+    pub problems: Vec<Problem>,
+    /// The day's records with the fixable problems fixed, if there are any.
+    pub fix: Option<Vec<Record>>,
+}
+
+/// Checks every recorded day for records that can't be read, are out of
+/// order, don't alternate between start and stop or are duplicated, for
+/// empty days and for days before `today` that were never stopped. Only
+/// removing duplicates and empty days counts as safe to fix.
+pub fn check(store: &dyn Store, today: NaiveDate) -> Result<Vec<Diagnosis>, Box<dyn Error>> {
+    let mut diagnoses = Vec::new();
+    for date in store.list_days()? {
+        let problem = |description: String, fixable| Problem {
+            description,
+            fixable,
+        };
+        let records = match store.read_day(date) {
+            Ok(records) => records.unwrap_or_default(),
+            Err(err) => {
+                diagnoses.push(Diagnosis {
+                    date,
+                    problems: vec![problem(err.to_string(), false)],
+                    fix: None,
+                });
+                continue;
+            }
+        };
+        let mut problems = Vec::new();
+        let mut fix = None;
+
+        if records.is_empty() {
+            problems.push(problem(
+                format!("{}: no records", store.location(date)),
+                true,
+            ));
+            fix = Some(Vec::new());
+        }
+
+        // Indices into `records` of the records that are kept.
+        let mut kept: Vec<usize> = Vec::new();
+        for (index, record) in records.iter().enumerate() {
+            let duplicate = kept.last().is_some_and(|&previous| {
+                let previous = &records[previous];
+                previous.kind == record.kind && previous.timestamp == record.timestamp
+            });
+            if duplicate {
+                problems.push(problem(
+                    format!(
+                        "{}: duplicate {} at {}",
+                        store.locate(date, index),
+                        record.kind,
+                        record.time()
+                    ),
+                    true,
+                ));
+            } else {
+                kept.push(index);
+            }
+        }
+        let deduplicated: Vec<Record> = kept.iter().map(|&index| records[index].clone()).collect();
+        if deduplicated.len() < records.len() {
+            fix = Some(deduplicated.clone());
+        }
+
+        match DayInfo::from_records(&deduplicated, None) {
+            Err((index, err)) => problems.push(problem(
+                format!("{}: {err}", store.locate(date, kept[index])),
+                false,
+            )),
+            Ok(day) if day.ongoing() && date < today => problems.push(problem(
+                format!(
+                    "{}: work was never stopped",
+                    store.locate(date, kept[kept.len() - 1])
+                ),
+                false,
+            )),
+            Ok(_) => {}
+        }
+
+        if !problems.is_empty() {
+            diagnoses.push(Diagnosis {
+                date,
+                problems,
+                fix,
+            });
+        }
+    }
+    Ok(diagnoses)
+}
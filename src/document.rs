@@ -0,0 +1,216 @@
+//! Reports as documents to paste into a wiki or an email: a table of the
+//! days of every week, the totals and the time per project, in Markdown or
+//! HTML.
+
+use chrono::{Datelike, NaiveDate};
+
+use crate::report::{self, BreakRule, Day};
+use crate::time::s_to_hhmm;
+
+/// The formats of `azk report --format` besides plain text.
+pub const FORMATS: [&str; 2] = ["md", "html"];
+
+struct Row {
+    date: NaiveDate,
+    start: isize,
+    end: isize,
+    breaks: isize,
+    net: isize,
+}
+
+struct Week {
+    label: String,
+    rows: Vec<Row>,
+    total: isize,
+}
+
+/// What a report holds, in any of the formats.
+struct Document {
+    title: String,
+    weeks: Vec<Week>,
+    gross: isize,
+    deducted: isize,
+    projects: Vec<(String, isize)>,
+}
+
+impl Document {
+    /// Gathers the days with work among `days`, by week.
+    fn new(from: NaiveDate, to: NaiveDate, days: &[Day], rules: &[BreakRule]) -> Document {
+        let mut weeks: Vec<Week> = Vec::new();
+        for day in days {
+            let Some(info) = day.info.as_ref().filter(|info| !info.intervals.is_empty()) else {
+                continue;
+            };
+            let row = Row {
+                date: day.date,
+                start: info.start(),
+                end: info.end(),
+                breaks: info.break_duration(),
+                net: report::net_duration(info, rules),
+            };
+            let label = report::label(day.date, report::GroupBy::Week);
+            match weeks.last_mut() {
+                Some(week) if week.label == label => {
+                    week.total += row.net;
+                    week.rows.push(row);
+                }
+                _ => weeks.push(Week {
+                    label,
+                    total: row.net,
+                    rows: vec![row],
+                }),
+            }
+        }
+        let infos = days.iter().filter_map(|day| day.info.as_ref());
+        let gross = infos.clone().map(|info| info.duration()).sum();
+        let deducted = infos
+            .clone()
+            .map(|info| report::deduction(info, rules))
+            .sum();
+        let totals = report::project_totals(infos);
+        let projects = if totals.keys().all(Option::is_none) {
+            Vec::new()
+        } else {
+            totals
+                .into_iter()
+                .map(|(project, total)| {
+                    (project.unwrap_or_else(|| "(no project)".to_owned()), total)
+                })
+                .collect()
+        };
+        Document {
+            title: format!("Work from {from} to {to}"),
+            weeks,
+            gross,
+            deducted,
+            projects,
+        }
+    }
+}
+
+fn weekday(date: NaiveDate) -> String {
+    format!("{} {}", date.weekday(), date.format("%Y-%m-%d"))
+}
+
+/// Escapes what would break a cell of a Markdown table.
+fn md_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The report of `days` from `from` to `to` as Markdown, with durations
+/// formatted by `hours`.
+pub fn markdown(
+    from: NaiveDate,
+    to: NaiveDate,
+    days: &[Day],
+    rules: &[BreakRule],
+    hours: fn(isize) -> String,
+) -> String {
+    let doc = Document::new(from, to, days, rules);
+    let mut text = format!("# {}\n", doc.title);
+    for week in &doc.weeks {
+        text += &format!("\n## Week {}\n\n", week.label);
+        text += "| Day | Start | End | Breaks | Work |\n";
+        text += "| --- | ---: | ---: | ---: | ---: |\n";
+        for row in &week.rows {
+            text += &format!(
+                "| {} | {} | {} | {} | {} |\n",
+                weekday(row.date),
+                s_to_hhmm(row.start),
+                s_to_hhmm(row.end),
+                hours(row.breaks),
+                hours(row.net)
+            );
+        }
+        text += &format!("| **Total** | | | | **{}** |\n", hours(week.total));
+    }
+
+    text += "\n## Totals\n\n";
+    if doc.deducted > 0 {
+        text += &format!("- Gross: {}\n", hours(doc.gross));
+        text += &format!("- Deducted for short breaks: {}\n", hours(doc.deducted));
+    }
+    text += &format!("- **Total: {}**\n", hours(doc.gross - doc.deducted));
+
+    if !doc.projects.is_empty() {
+        text += "\n## Projects\n\n| Project | Work |\n| --- | ---: |\n";
+        for (project, total) in &doc.projects {
+            text += &format!("| {} | {} |\n", md_escape(project), hours(*total));
+        }
+    }
+    text
+}
+
+/// The report of `days` from `from` to `to` as a standalone HTML page, with
+/// durations formatted by `hours`.
+pub fn html(
+    from: NaiveDate,
+    to: NaiveDate,
+    days: &[Day],
+    rules: &[BreakRule],
+    hours: fn(isize) -> String,
+) -> String {
+    let doc = Document::new(from, to, days, rules);
+    let mut text = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{title}</title>\n<style>\n\
+         body {{ font-family: sans-serif; }}\n\
+         table {{ border-collapse: collapse; margin-bottom: 1em; }}\n\
+         th, td {{ border: 1px solid #ccc; padding: 0.2em 0.6em; }}\n\
+         td.n {{ text-align: right; }}\n\
+         </style>\n</head>\n<body>\n<h1>{title}</h1>\n",
+        title = html_escape(&doc.title)
+    );
+    for week in &doc.weeks {
+        text += &format!("<h2>Week {}</h2>\n<table>\n", week.label);
+        text += "<tr><th>Day</th><th>Start</th><th>End</th><th>Breaks</th><th>Work</th></tr>\n";
+        for row in &week.rows {
+            text += &format!(
+                "<tr><td>{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td>\
+                 <td class=\"n\">{}</td><td class=\"n\">{}</td></tr>\n",
+                weekday(row.date),
+                s_to_hhmm(row.start),
+                s_to_hhmm(row.end),
+                hours(row.breaks),
+                hours(row.net)
+            );
+        }
+        text += &format!(
+            "<tr><th colspan=\"4\">Total</th><th class=\"n\">{}</th></tr>\n</table>\n",
+            hours(week.total)
+        );
+    }
+
+    text += "<h2>Totals</h2>\n<ul>\n";
+    if doc.deducted > 0 {
+        text += &format!("<li>Gross: {}</li>\n", hours(doc.gross));
+        text += &format!(
+            "<li>Deducted for short breaks: {}</li>\n",
+            hours(doc.deducted)
+        );
+    }
+    text += &format!(
+        "<li><strong>Total: {}</strong></li>\n</ul>\n",
+        hours(doc.gross - doc.deducted)
+    );
+
+    if !doc.projects.is_empty() {
+        text += "<h2>Projects</h2>\n<table>\n<tr><th>Project</th><th>Work</th></tr>\n";
+        for (project, total) in &doc.projects {
+            text += &format!(
+                "<tr><td>{}</td><td class=\"n\">{}</td></tr>\n",
+                html_escape(project),
+                hours(*total)
+            );
+        }
+        text += "</table>\n";
+    }
+    text + "</body>\n</html>\n"
+}
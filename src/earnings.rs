@@ -0,0 +1,186 @@
+//! Money earned on projects at their hourly rates.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::model::DayInfo;
+use crate::report::{self, Day, GroupBy};
+
+/// Hourly rates in cents per project, with a default for the rest.
+#[derive(Default)]
+pub struct Rates {
+    projects: BTreeMap<String, i64>,
+    default: Option<i64>,
+}
+
+/// Parses an amount like `95` or `120.50` into cents.
+fn parse_amount(input: &str) -> Option<i64> {
+    let amount: f64 = input.trim().parse().ok()?;
+    (amount.is_finite() && amount >= 0.0).then(|| (amount * 100.0).round() as i64)
+}
+
+impl Rates {
+    /// Parses rates written as `acme=95, globex=120.50, *=80`, where `*` is
+    /// the rate of the other projects, including work without a project.
+    pub fn parse(rates: &str) -> Result<Rates, Box<dyn Error>> {
+        let mut parsed = Rates::default();
+        for entry in rates.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (project, rate) = entry
+                .rsplit_once('=')
+                .ok_or_else(|| format!("invalid rate '{entry}', expected e.g. acme=95"))?;
+            let rate = parse_amount(rate)
+                .ok_or_else(|| format!("invalid rate '{entry}', expected e.g. acme=95"))?;
+            match project.trim() {
+                "*" => parsed.default = Some(rate),
+                project => {
+                    parsed.projects.insert(project.to_owned(), rate);
+                }
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// The rate of `project` in cents, if it has one.
+    pub fn rate(&self, project: Option<&str>) -> Option<i64> {
+        project
+            .and_then(|project| self.projects.get(project).copied())
+            .or(self.default)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.projects.is_empty() && self.default.is_none()
+    }
+}
+
+/// The cents earned in `seconds` at `rate` cents an hour.
+pub fn earned(seconds: isize, rate: i64) -> i64 {
+    (seconds as i64 * rate + 1800) / 3600
+}
+
+/// The cents earned per project in `infos`, and the time of the projects
+/// without a rate under `None`. Earnings count all recorded work, as
+/// deductions for short breaks belong to no project.
+pub fn project_earnings<'a>(
+    infos: impl IntoIterator<Item = &'a DayInfo>,
+    rates: &Rates,
+) -> BTreeMap<Option<String>, (isize, Option<i64>)> {
+    report::project_totals(infos)
+        .into_iter()
+        .map(|(project, seconds)| {
+            let earned = rates
+                .rate(project.as_deref())
+                .map(|rate| earned(seconds, rate));
+            (project, (seconds, earned))
+        })
+        .collect()
+}
+
+/// The cents earned in each day, week or month of `days`, see
+/// [`report::totals`].
+pub fn period_earnings(days: &[Day], group_by: GroupBy, rates: &Rates) -> Vec<(String, i64)> {
+    let mut totals: Vec<(String, i64)> = Vec::new();
+    for day in days {
+        let label = report::label(day.date, group_by);
+        let earned: i64 = project_earnings(day.info.as_ref(), rates)
+            .values()
+            .filter_map(|(_, earned)| *earned)
+            .sum();
+        match totals.last_mut() {
+            Some((last, total)) if *last == label => *total += earned,
+            _ => totals.push((label, earned)),
+        }
+    }
+    totals
+}
+
+/// Formats `cents` as in `1,234.50 EUR`.
+pub fn format_money(cents: i64, currency: &str) -> String {
+    let units = (cents.abs() / 100).to_string();
+    let mut grouped = String::new();
+    for (index, digit) in units.chars().enumerate() {
+        if index > 0 && (units.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    let sign = if cents < 0 { "-" } else { "" };
+    let money = format!("{sign}{grouped}.{:02}", cents.abs() % 100);
+    if currency.is_empty() {
+        money
+    } else {
+        format!("{money} {currency}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures::{day_of, record};
+    use crate::model::Record;
+
+    #[test]
+    fn parses_rates() {
+        let rates = Rates::parse("acme=95, globex=120.50, *=80").unwrap();
+        assert_eq!(rates.rate(Some("acme")), Some(9500));
+        assert_eq!(rates.rate(Some("globex")), Some(12050));
+        assert_eq!(rates.rate(Some("initech")), Some(8000));
+        assert_eq!(rates.rate(None), Some(8000));
+        let rates = Rates::parse("acme=95").unwrap();
+        assert_eq!(rates.rate(None), None);
+        assert!(Rates::parse("").unwrap().is_empty());
+        for rates in ["acme", "acme=x", "acme=-5", "acme=inf"] {
+            assert!(Rates::parse(rates).is_err(), "{rates}");
+        }
+    }
+
+    #[test]
+    fn rounds_earnings_to_the_cent() {
+        assert_eq!(earned(5400, 9500), 14250);
+        assert_eq!(earned(1, 1800), 1);
+        assert_eq!(earned(1, 1799), 0);
+        assert_eq!(earned(0, 9500), 0);
+    }
+
+    #[test]
+    fn sums_earnings_per_project_and_month() {
+        let rates = Rates::parse("acme=100").unwrap();
+        let acme = |date, time| Record {
+            project: Some("acme".to_owned()),
+            ..record(date, "strt", time)
+        };
+        let days = [
+            day_of(
+                "2024-01-31",
+                &[
+                    acme("2024-01-31", "09:00"),
+                    record("2024-01-31", "stop", "11:00"),
+                    record("2024-01-31", "strt", "12:00"),
+                    record("2024-01-31", "stop", "13:00"),
+                ],
+            ),
+            day_of(
+                "2024-02-01",
+                &[
+                    acme("2024-02-01", "09:00"),
+                    record("2024-02-01", "stop", "09:30"),
+                ],
+            ),
+        ];
+        let infos = days.iter().filter_map(|day| day.info.as_ref());
+        let earnings = project_earnings(infos, &rates);
+        assert_eq!(earnings[&Some("acme".to_owned())], (9000, Some(25000)));
+        assert_eq!(earnings[&None], (3600, None));
+        assert_eq!(
+            period_earnings(&days, GroupBy::Month, &rates),
+            [("2024-01".to_owned(), 20000), ("2024-02".to_owned(), 5000)]
+        );
+    }
+
+    #[test]
+    fn formats_money() {
+        assert_eq!(format_money(123450, "EUR"), "1,234.50 EUR");
+        assert_eq!(format_money(100000000, ""), "1,000,000.00");
+        assert_eq!(format_money(-5, "USD"), "-0.05 USD");
+        assert_eq!(format_money(0, ""), "0.00");
+    }
+}
@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+
+use azk::model::{DayInfo, Record};
+use azk::store::Store;
+use azk::time::{hhmmss_to_s, local_timestamp, parse_hhmm};
+
+const HELP: &str = "\
+Commands:
+  p                    print the records
+  set INDEX HH:MM      change the time of a record
+  del INDEX            delete a record
+  add strt|stop[:BREAK] HH:MM [PROJECT]
+                       insert a record, sorted by time
+  w                    validate, write and quit
+  q                    quit without writing";
+
+fn print_records(records: &[Record]) {
+    for (index, record) in records.iter().enumerate() {
+        let Record {
+            kind,
+            project,
+            note,
+            ..
+        } = record;
+        println!(
+            "{index:>3}  {kind}  {}  {}  {}",
+            record.time(),
+            project.as_deref().unwrap_or(""),
+            note.as_deref().unwrap_or("")
+        );
+    }
+}
+
+fn parse_index(records: &[Record], input: Option<&str>) -> Result<usize, Box<dyn Error>> {
+    let index = input.ok_or("missing INDEX")?.parse::<usize>()?;
+    if index >= records.len() {
+        Err(format!("no record #{index}"))?
+    }
+    Ok(index)
+}
+
+/// Parses a user-supplied `HH:MM` on `date` into a local timestamp.
+fn parse_timestamp(
+    date: NaiveDate,
+    input: Option<&str>,
+) -> Result<DateTime<FixedOffset>, Box<dyn Error>> {
+    let time = parse_hhmm(input.ok_or("missing HH:MM")?)?;
+    Ok(local_timestamp(date, hhmmss_to_s(&time))
+        .ok_or_else(|| format!("{date} {time} does not exist in the local time zone"))?)
+}
+
+fn apply(records: &mut Vec<Record>, date: NaiveDate, command: &str) -> Result<(), Box<dyn Error>> {
+    let mut words = command.split_whitespace();
+    match words.next() {
+        Some("p") => print_records(records),
+        Some("set") => {
+            let index = parse_index(records, words.next())?;
+            records[index].timestamp = parse_timestamp(date, words.next())?;
+            records.sort_by_key(|record| record.timestamp);
+        }
+        Some("del") => {
+            let index = parse_index(records, words.next())?;
+            records.remove(index);
+        }
+        Some("add") => {
+            let kind = match words.next() {
+                Some(kind) if matches!(kind, "strt" | "stop") || kind.starts_with("stop:") => {
+                    kind.to_owned()
+                }
+                _ => Err("expected strt, stop or stop:BREAK")?,
+            };
+            let timestamp = parse_timestamp(date, words.next())?;
+            let project = words.next().map(str::to_owned);
+            let index = records.partition_point(|record| record.timestamp <= timestamp);
+            records.insert(
+                index,
+                Record {
+                    kind,
+                    timestamp,
+                    project,
+                    note: None,
+                },
+            );
+        }
+        _ => println!("{HELP}"),
+    }
+    Ok(())
+}
+
+/// Edits the records of a day line by line, writing them back only once they
+/// pass validation.
+pub fn edit(store: &dyn Store, date: NaiveDate) -> Result<(), Box<dyn Error>> {
+    let mut records = store.read_day(date)?.unwrap_or_default();
+    let location = store.location(date);
+
+    println!("Editing {location}, ? for help.");
+    print_records(&records);
+
+    let stdin = io::stdin();
+    loop {
+        print!("edit> ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if stdin.lock().read_line(&mut input)? == 0 {
+            return Ok(());
+        }
+        match input.trim() {
+            "q" => return Ok(()),
+            "w" => match DayInfo::from_records(&records, None) {
+                Ok(_) => break,
+                Err((index, err)) => eprintln!("Not writing, #{index}: {err}"),
+            },
+            command => {
+                if let Err(err) = apply(&mut records, date, command) {
+                    eprintln!("{err}");
+                }
+            }
+        }
+    }
+
+    let _lock = store.lock()?;
+    store.write_day(date, &records)?;
+    println!("Wrote {location}.");
+    Ok(())
+}
@@ -0,0 +1,524 @@
+//! Work intervals in the formats of other tools.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveTime, TimeZone, Utc};
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, Int32Type, Int64Type};
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook};
+use serde::Serialize;
+
+use crate::report::{self, BreakRule, Day};
+use crate::time::s_to_hhmm;
+use crate::{gitlab, ics, secret};
+
+/// The formats of `azk export`.
+pub const FORMATS: [&str; 10] = [
+    "ics",
+    "timeclock",
+    "clockify",
+    "jira",
+    "jira-csv",
+    "gitlab",
+    "influx",
+    "xlsx",
+    "csv",
+    "parquet",
+];
+
+/// The keyring entry holding the token for writing to InfluxDB.
+pub const INFLUX_SECRET: &str = "influx";
+
+/// How intervals are exported, beyond the format.
+#[derive(Default)]
+pub struct Options {
+    /// Take the Jira issue key from the note rather than the project, if
+    /// the note mentions one.
+    pub issue_from_note: bool,
+    /// Sum the csv format per project over the whole range, rather than per
+    /// day and project.
+    pub per_project: bool,
+}
+
+/// Formats `days` in `format`, see [`FORMATS`].
+pub fn export(format: &str, days: &[Day], options: &Options) -> Result<String, Box<dyn Error>> {
+    match format {
+        "ics" => Ok(ics(days)),
+        "timeclock" => Ok(timeclock(days)),
+        "clockify" => clockify(days),
+        "csv" => csv(days, options),
+        "gitlab" => Ok(gitlab::text(&gitlab::spent(days))),
+        "influx" => Ok(influx(days)),
+        "xlsx" => Err("xlsx is not text, see export::xlsx")?,
+        "parquet" => Err("parquet is not text, see export::parquet")?,
+        "jira" => Ok(serde_json::to_string_pretty(&worklogs(days, options)?)? + "\n"),
+        "jira-csv" => {
+            let mut wtr = csv::Writer::from_writer(Vec::new());
+            for worklog in worklogs(days, options)? {
+                wtr.serialize(worklog)?;
+            }
+            Ok(String::from_utf8(wtr.into_inner()?)?)
+        }
+        _ => Err(format!(
+            "unknown export format '{format}', expected one of {}",
+            FORMATS.join(", ")
+        ))?,
+    }
+}
+
+fn ics_time(timestamp: &DateTime<FixedOffset>) -> String {
+    format!("{}", timestamp.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ"))
+}
+
+/// An iCalendar with an event for every work interval. An ongoing interval
+/// ends at the time it was read.
+fn ics(days: &[Day]) -> String {
+    let stamp = format!("{}", Utc::now().format("%Y%m%dT%H%M%SZ"));
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_owned(),
+        "VERSION:2.0".to_owned(),
+        "PRODID:-//azk//azk//EN".to_owned(),
+    ];
+    for interval in days
+        .iter()
+        .filter_map(|day| day.info.as_ref())
+        .flat_map(|info| &info.intervals)
+    {
+        let summary = match &interval.project {
+            Some(project) => format!("Work: {project}"),
+            None => "Work".to_owned(),
+        };
+        lines.extend([
+            "BEGIN:VEVENT".to_owned(),
+            format!("UID:{}@azk", ics_time(&interval.started_at)),
+            format!("DTSTAMP:{stamp}"),
+            format!("DTSTART:{}", ics_time(&interval.started_at)),
+            format!("DTEND:{}", ics_time(&interval.ended_at)),
+            format!("SUMMARY:{}", ics::escape(&summary)),
+            "END:VEVENT".to_owned(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_owned());
+    // Lines end with CRLF, as the format requires.
+    lines.join("\r\n") + "\r\n"
+}
+
+/// Clock-ins and clock-outs as read by ledger and hledger, with the project
+/// as the account. An ongoing interval is left clocked in.
+fn timeclock(days: &[Day]) -> String {
+    let mut text = String::new();
+    for interval in days
+        .iter()
+        .filter_map(|day| day.info.as_ref())
+        .flat_map(|info| &info.intervals)
+    {
+        let account = interval.project.as_deref().unwrap_or("work");
+        text += &format!(
+            "i {} {account}\n",
+            interval.started_at.format("%Y-%m-%d %H:%M:%S")
+        );
+        if !interval.ongoing {
+            text += &format!("o {}\n", interval.ended_at.format("%Y-%m-%d %H:%M:%S"));
+        }
+    }
+    text
+}
+
+/// A CSV file of the time spent per day and project, or per project with
+/// `per_project`, in seconds and in HH:MM, for pivot tables.
+fn csv(days: &[Day], options: &Options) -> Result<String, Box<dyn Error>> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record(["date", "project", "seconds", "hh:mm"])?;
+    let mut write = |date: &str, project: &Option<String>, seconds: isize| {
+        wtr.write_record([
+            date,
+            project.as_deref().unwrap_or(""),
+            &seconds.to_string(),
+            &s_to_hhmm(seconds),
+        ])
+    };
+    if options.per_project {
+        let totals = report::project_totals(days.iter().filter_map(|day| day.info.as_ref()));
+        for (project, seconds) in &totals {
+            write("", project, *seconds)?;
+        }
+    } else {
+        for day in days {
+            let date = day.date.to_string();
+            for (project, seconds) in &report::project_totals(day.info.as_ref()) {
+                write(&date, project, *seconds)?;
+            }
+        }
+    }
+    Ok(String::from_utf8(wtr.into_inner()?)?)
+}
+
+/// A CSV file in the columns Clockify imports time entries from, with the
+/// note as the description. Clockify also needs the user's email, which is
+/// left for filling in. Ongoing intervals are left out.
+fn clockify(days: &[Day]) -> Result<String, Box<dyn Error>> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record([
+        "Project",
+        "Client",
+        "Description",
+        "Task",
+        "Email",
+        "Tags",
+        "Billable",
+        "Start Date",
+        "Start Time",
+        "End Date",
+        "End Time",
+    ])?;
+    for interval in days
+        .iter()
+        .filter_map(|day| day.info.as_ref())
+        .flat_map(|info| &info.intervals)
+        .filter(|interval| !interval.ongoing)
+    {
+        wtr.write_record([
+            interval.project.as_deref().unwrap_or(""),
+            "",
+            interval.note.as_deref().unwrap_or(""),
+            "",
+            "",
+            "",
+            "No",
+            &interval.started_at.format("%Y-%m-%d").to_string(),
+            &interval.started_at.format("%H:%M:%S").to_string(),
+            &interval.ended_at.format("%Y-%m-%d").to_string(),
+            &interval.ended_at.format("%H:%M:%S").to_string(),
+        ])?;
+    }
+    Ok(String::from_utf8(wtr.into_inner()?)?)
+}
+
+/// A worklog in the fields of Jira's and Tempo's bulk import.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Worklog {
+    issue_key: String,
+    started: String,
+    time_spent_seconds: isize,
+    comment: String,
+}
+
+/// Whether `word` is an issue key like `AZK-42`.
+fn is_issue_key(word: &str) -> bool {
+    let Some((project, number)) = word.split_once('-') else {
+        return false;
+    };
+    project.starts_with(|c: char| c.is_ascii_uppercase())
+        && project
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        && !number.is_empty()
+        && number.chars().all(|c| c.is_ascii_digit())
+}
+
+/// The first issue key mentioned in `text`.
+fn find_issue_key(text: &str) -> Option<&str> {
+    text.split(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .find(|word| is_issue_key(word))
+}
+
+/// A worklog for every finished interval, logged on the issue named by its
+/// project or, with [`Options::issue_from_note`], by its note.
+fn worklogs(days: &[Day], options: &Options) -> Result<Vec<Worklog>, Box<dyn Error>> {
+    let mut worklogs = Vec::new();
+    for interval in days
+        .iter()
+        .filter_map(|day| day.info.as_ref())
+        .flat_map(|info| &info.intervals)
+        .filter(|interval| !interval.ongoing)
+    {
+        let note = interval.note.as_deref().unwrap_or("");
+        let from_note = options
+            .issue_from_note
+            .then(|| find_issue_key(note))
+            .flatten();
+        let from_project = interval
+            .project
+            .as_deref()
+            .filter(|project| is_issue_key(project));
+        let Some(issue_key) = from_note.or(from_project) else {
+            Err(format!(
+                "no issue key for the work from {} to {}, set the project to one{}",
+                interval.started_at.format("%Y-%m-%d %H:%M"),
+                interval.ended_at.format("%H:%M"),
+                if options.issue_from_note {
+                    " or mention one in the note"
+                } else {
+                    " or use --issue-from-note"
+                }
+            ))?
+        };
+        worklogs.push(Worklog {
+            issue_key: issue_key.to_owned(),
+            started: format!("{}", interval.started_at.format("%Y-%m-%dT%H:%M:%S%.3f%z")),
+            time_spent_seconds: interval.elapsed,
+            comment: note.to_owned(),
+        });
+    }
+    Ok(worklogs)
+}
+
+/// Escapes a tag value of the InfluxDB line protocol.
+fn influx_tag(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+/// Points in the InfluxDB line protocol, with timestamps in seconds: an
+/// `azk_interval` at the start of every interval and an `azk_daily` at the
+/// midnight of every day and project, both with the `seconds` of work and
+/// tagged by project. An ongoing interval counts up to the time it was read,
+/// and is overwritten when written again later.
+fn influx(days: &[Day]) -> String {
+    let tags = |project: Option<&String>| {
+        project.map_or(String::new(), |project| {
+            format!(",project={}", influx_tag(project))
+        })
+    };
+    let mut lines = String::new();
+    for info in days.iter().filter_map(|day| day.info.as_ref()) {
+        let mut daily: BTreeMap<Option<&String>, isize> = BTreeMap::new();
+        for interval in &info.intervals {
+            lines += &format!(
+                "azk_interval{} seconds={}i {}\n",
+                tags(interval.project.as_ref()),
+                interval.elapsed,
+                interval.started_at.timestamp()
+            );
+            *daily.entry(interval.project.as_ref()).or_default() += interval.elapsed;
+        }
+        let Some(first) = info.intervals.first() else {
+            continue;
+        };
+        let midnight = first
+            .started_at
+            .offset()
+            .from_local_datetime(&first.started_at.date_naive().and_time(NaiveTime::default()))
+            .earliest()
+            .map_or(first.started_at.timestamp(), |midnight| {
+                midnight.timestamp()
+            });
+        for (project, seconds) in daily {
+            lines += &format!("azk_daily{} seconds={seconds}i {midnight}\n", tags(project));
+        }
+    }
+    lines
+}
+
+/// Writes the points in `lines` to the write endpoint `url` of InfluxDB,
+/// with the token from the keyring if it holds one.
+pub fn push_influx(lines: &str, url: &str) -> Result<(), Box<dyn Error>> {
+    let mut request = ureq::post(url)
+        .timeout(Duration::from_secs(10))
+        .set("Content-Type", "text/plain; charset=utf-8");
+    if let Some(token) = secret::get(INFLUX_SECRET).ok().flatten() {
+        request = request.set("Authorization", &format!("Token {token}"));
+    }
+    request
+        .send_string(lines)
+        .map_err(|err| format!("{url}: {err}"))?;
+    Ok(())
+}
+
+/// A spreadsheet with a sheet of the days, with their start, end, breaks and
+/// net work after the deduction by `rules`, and a sheet summing them up by
+/// formulas and per project. Times and durations are Excel times, so they
+/// can be calculated with.
+pub fn xlsx(days: &[Day], rules: &[BreakRule]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let fraction = |seconds: isize| seconds as f64 / 86_400.0;
+    let bold = Format::new().set_bold();
+    let date = Format::new().set_num_format("ddd yyyy-mm-dd");
+    let time = Format::new().set_num_format("hh:mm");
+    let duration = Format::new().set_num_format("[h]:mm");
+    let total = Format::new().set_bold().set_num_format("[h]:mm");
+
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet().set_name("Days")?;
+    for (col, header) in [
+        "Date", "Start", "End", "Breaks", "Work", "Projects", "Notes",
+    ]
+    .iter()
+    .enumerate()
+    {
+        sheet.write_string_with_format(0, col as u16, *header, &bold)?;
+    }
+    sheet.set_column_width(0, 16)?;
+    sheet.set_column_width(5, 24)?;
+    sheet.set_column_width(6, 40)?;
+    sheet.set_freeze_panes(1, 0)?;
+
+    let mut row = 1;
+    for day in days {
+        let excel_date = ExcelDateTime::from_ymd(
+            day.date.year() as u16,
+            day.date.month() as u8,
+            day.date.day() as u8,
+        )?;
+        sheet.write_date_with_format(row, 0, &excel_date, &date)?;
+        if let Some(info) = day.info.as_ref().filter(|info| !info.intervals.is_empty()) {
+            sheet.write_number_with_format(row, 1, fraction(info.start()), &time)?;
+            sheet.write_number_with_format(row, 2, fraction(info.end()), &time)?;
+            sheet.write_number_with_format(row, 3, fraction(info.break_duration()), &duration)?;
+            let net = report::net_duration(info, rules);
+            sheet.write_number_with_format(row, 4, fraction(net), &duration)?;
+            let mut projects: Vec<&str> = info
+                .intervals
+                .iter()
+                .filter_map(|interval| interval.project.as_deref())
+                .collect();
+            projects.dedup();
+            sheet.write_string(row, 5, projects.join(", "))?;
+            let notes: Vec<&str> = info
+                .intervals
+                .iter()
+                .filter_map(|interval| interval.note.as_deref())
+                .collect();
+            sheet.write_string(row, 6, notes.join("; "))?;
+        }
+        row += 1;
+    }
+    let last = row;
+    sheet.write_string_with_format(row, 0, "Total", &bold)?;
+    sheet.write_formula_with_format(row, 3, format!("=SUM(D2:D{last})").as_str(), &total)?;
+    sheet.write_formula_with_format(row, 4, format!("=SUM(E2:E{last})").as_str(), &total)?;
+
+    let summary = workbook.add_worksheet().set_name("Summary")?;
+    summary.set_column_width(0, 20)?;
+    summary.write_string_with_format(0, 0, "Summary", &bold)?;
+    summary.write_string(1, 0, "Days worked")?;
+    summary.write_formula(1, 1, format!("=COUNTIF(Days!E2:E{last},\">0\")").as_str())?;
+    summary.write_string(2, 0, "Total")?;
+    summary.write_formula_with_format(2, 1, format!("=Days!E{}", last + 1).as_str(), &duration)?;
+    summary.write_string(3, 0, "Average per day")?;
+    summary.write_formula_with_format(3, 1, "=IFERROR(B3/B2,0)", &duration)?;
+    summary.write_string(4, 0, "Breaks")?;
+    summary.write_formula_with_format(4, 1, format!("=Days!D{}", last + 1).as_str(), &duration)?;
+
+    let totals = report::project_totals(days.iter().filter_map(|day| day.info.as_ref()));
+    if !totals.keys().all(Option::is_none) {
+        summary.write_string_with_format(6, 0, "Project", &bold)?;
+        summary.write_string_with_format(6, 1, "Work", &bold)?;
+        for (row, (project, seconds)) in totals.iter().enumerate() {
+            let row = row as u32 + 7;
+            summary.write_string(row, 0, project.as_deref().unwrap_or("(no project)"))?;
+            summary.write_number_with_format(row, 1, fraction(*seconds), &duration)?;
+        }
+    }
+    Ok(workbook.save_to_buffer()?)
+}
+
+/// The columns of the parquet format, one row per interval. Instants are in
+/// UTC, as parquet has no offsets; `date` is the day the interval is
+/// recorded on.
+const PARQUET_SCHEMA: &str = "
+message interval {
+    REQUIRED INT32 date (DATE);
+    REQUIRED INT64 start (TIMESTAMP(MILLIS,true));
+    REQUIRED INT64 end (TIMESTAMP(MILLIS,true));
+    REQUIRED INT64 seconds;
+    OPTIONAL BYTE_ARRAY project (UTF8);
+    OPTIONAL BYTE_ARRAY note (UTF8);
+    REQUIRED BOOLEAN ongoing;
+}
+";
+
+/// The values and definition levels of an optional text column.
+fn optional_column(values: Vec<Option<&str>>) -> (Vec<ByteArray>, Vec<i16>) {
+    let levels = values.iter().map(|value| value.is_some() as i16).collect();
+    (
+        values.into_iter().flatten().map(ByteArray::from).collect(),
+        levels,
+    )
+}
+
+/// Every interval of `days` as a parquet file, to query in a data frame.
+pub fn parquet(days: &[Day]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).expect("a valid date");
+    let intervals: Vec<_> = days
+        .iter()
+        .filter_map(|day| day.info.as_ref().map(|info| (day.date, info)))
+        .flat_map(|(date, info)| info.intervals.iter().map(move |interval| (date, interval)))
+        .collect();
+    let dates: Vec<i32> = intervals
+        .iter()
+        .map(|(date, _)| (*date - epoch).num_days() as i32)
+        .collect();
+    let starts: Vec<i64> = intervals
+        .iter()
+        .map(|(_, interval)| interval.started_at.timestamp_millis())
+        .collect();
+    let ends: Vec<i64> = intervals
+        .iter()
+        .map(|(_, interval)| interval.ended_at.timestamp_millis())
+        .collect();
+    let seconds: Vec<i64> = intervals
+        .iter()
+        .map(|(_, interval)| interval.duration() as i64)
+        .collect();
+    let (projects, project_levels) = optional_column(
+        intervals
+            .iter()
+            .map(|(_, interval)| interval.project.as_deref())
+            .collect(),
+    );
+    let (notes, note_levels) = optional_column(
+        intervals
+            .iter()
+            .map(|(_, interval)| interval.note.as_deref())
+            .collect(),
+    );
+    let ongoing: Vec<bool> = intervals
+        .iter()
+        .map(|(_, interval)| interval.ongoing)
+        .collect();
+
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+    let mut writer = SerializedFileWriter::new(Vec::new(), schema, Default::default())?;
+    let mut row_group = writer.next_row_group()?;
+    let mut column = row_group.next_column()?.ok_or("missing column date")?;
+    column
+        .typed::<Int32Type>()
+        .write_batch(&dates, None, None)?;
+    column.close()?;
+    for (name, values) in [("start", &starts), ("end", &ends), ("seconds", &seconds)] {
+        let mut column = row_group
+            .next_column()?
+            .ok_or(format!("missing column {name}"))?;
+        column
+            .typed::<Int64Type>()
+            .write_batch(values, None, None)?;
+        column.close()?;
+    }
+    for (name, values, levels) in [
+        ("project", &projects, &project_levels),
+        ("note", &notes, &note_levels),
+    ] {
+        let mut column = row_group
+            .next_column()?
+            .ok_or(format!("missing column {name}"))?;
+        column
+            .typed::<ByteArrayType>()
+            .write_batch(values, Some(levels), None)?;
+        column.close()?;
+    }
+    let mut column = row_group.next_column()?.ok_or("missing column ongoing")?;
+    column
+        .typed::<BoolType>()
+        .write_batch(&ongoing, None, None)?;
+    column.close()?;
+    row_group.close()?;
+    Ok(writer.into_inner()?)
+}
@@ -0,0 +1,100 @@
+use crate::model::DayInfo;
+use crate::time::s_to_hhmm;
+
+/// Fills the `{name}` placeholders of `template` from `vars`. `{{` and `}}`
+/// stand for literal braces.
+pub fn render(template: &str, vars: &[(&str, String)]) -> Result<String, String> {
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(pos) = rest.find(['{', '}']) {
+        output.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            output.push_str(&rest[..1]);
+            rest = &rest[2..];
+        } else if let Some(name) = rest.strip_prefix('{').and_then(|r| r.split_once('}')) {
+            let (name, after) = name;
+            let (_, value) = vars
+                .iter()
+                .find(|(var, _)| *var == name)
+                .ok_or_else(|| format!("unknown placeholder '{{{name}}}' in format"))?;
+            output.push_str(value);
+            rest = after;
+        } else {
+            Err(format!("unmatched '{}' in format", &rest[..1]))?
+        }
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// The placeholders available for a day, given the current time `now` and the daily `target`.
+pub fn day_vars(
+    date: &str,
+    day: &DayInfo,
+    now: isize,
+    target: isize,
+) -> Vec<(&'static str, String)> {
+    let state = if day.ongoing() {
+        "working"
+    } else if day.intervals.is_empty() {
+        "idle"
+    } else {
+        "break"
+    };
+    vec![
+        ("date", date.to_owned()),
+        ("state", state.to_owned()),
+        ("start", s_to_hhmm(day.start())),
+        ("end", s_to_hhmm(day.end())),
+        ("duration_hm", s_to_hhmm(day.duration())),
+        ("duration_s", day.duration().to_string()),
+        ("session_hm", s_to_hhmm(now - day.last)),
+        ("target_hm", s_to_hhmm(target)),
+        ("remaining_hm", s_to_hhmm((target - day.duration()).max(0))),
+    ]
+}
+
+/// The width of the bars drawn by [`bar`].
+pub const BAR_WIDTH: usize = 24;
+
+/// A bar for `duration` on a scale where `scale` fills [`BAR_WIDTH`]
+/// cells. Work up to the `target` is drawn solid, work beyond it shaded, and
+/// what is missing to the target dotted.
+pub fn bar(duration: isize, target: isize, scale: isize) -> String {
+    let cells = |seconds: isize| {
+        if scale <= 0 {
+            return 0;
+        }
+        let cells = (seconds.max(0) as f64 / scale as f64 * BAR_WIDTH as f64).round();
+        (cells as usize).min(BAR_WIDTH)
+    };
+    let (worked, target) = (cells(duration), cells(target));
+    (0..BAR_WIDTH)
+        .map(|cell| match (cell < worked, cell < target) {
+            (true, true) => '█',
+            (true, false) if target == 0 => '█',
+            (true, false) => '▓',
+            (false, true) => '·',
+            (false, false) => ' ',
+        })
+        .collect()
+}
+
+/// A line of one block for each of `values`, as high as the value between
+/// the lowest and the highest. Missing values are left blank.
+pub fn sparkline(values: &[Option<isize>]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let low = values.iter().flatten().copied().min().unwrap_or(0);
+    let high = values.iter().flatten().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|value| match value {
+            None => ' ',
+            Some(_) if high == low => BLOCKS[BLOCKS.len() / 2],
+            Some(value) => {
+                BLOCKS[((value - low) * (BLOCKS.len() as isize - 1) / (high - low)) as usize]
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,144 @@
+//! Time spent on GitLab issues, as `/spend` quick actions.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use chrono::NaiveDate;
+
+use crate::report::Day;
+use crate::secret;
+
+/// The keyring entry holding the access token for pushing.
+pub const SECRET: &str = "gitlab";
+
+/// The environment variable holding the access token if the keyring does
+/// not.
+pub const TOKEN_VAR: &str = "GITLAB_TOKEN";
+
+/// The access token for pushing, from the keyring or `$GITLAB_TOKEN`.
+pub fn token() -> Result<String, Box<dyn Error>> {
+    if let Some(token) = secret::get(SECRET).ok().flatten() {
+        return Ok(token);
+    }
+    std::env::var(TOKEN_VAR).map_err(|_| {
+        format!("there is no '{SECRET}' token in the keyring and ${TOKEN_VAR} is not set").into()
+    })
+}
+
+/// Seconds spent per issue reference, like `#12` or `group/project#12`, and
+/// day.
+pub type Spent = BTreeMap<String, BTreeMap<NaiveDate, isize>>;
+
+/// The first issue reference in `text`, like `#12` or `group/project#12`.
+fn find_reference(text: &str) -> Option<&str> {
+    let is_path = |c: char| c.is_ascii_alphanumeric() || "/-_.".contains(c);
+    text.match_indices('#').find_map(|(hash, _)| {
+        let digits = text[hash + 1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len() - hash - 1);
+        if digits == 0 {
+            return None;
+        }
+        let start = text[..hash]
+            .rfind(|c: char| !is_path(c))
+            .map_or(0, |i| i + 1);
+        Some(&text[start..hash + 1 + digits])
+    })
+}
+
+/// The time of the finished intervals whose note references an issue, per
+/// issue and day. Other work is left out.
+pub fn spent(days: &[Day]) -> Spent {
+    let mut spent = Spent::new();
+    for day in days {
+        let Some(info) = &day.info else {
+            continue;
+        };
+        for interval in info.intervals.iter().filter(|interval| !interval.ongoing) {
+            let Some(reference) = interval.note.as_deref().and_then(find_reference) else {
+                continue;
+            };
+            *spent
+                .entry(reference.to_owned())
+                .or_default()
+                .entry(day.date)
+                .or_default() += interval.elapsed;
+        }
+    }
+    spent
+}
+
+/// `seconds` as GitLab writes durations, e.g. `1h 30m`, to the minute.
+fn duration(seconds: isize) -> String {
+    let minutes = (seconds + 30) / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{minutes}m"),
+        (hours, 0) => format!("{hours}h"),
+        (hours, minutes) => format!("{hours}h {minutes}m"),
+    }
+}
+
+/// The `/spend` quick actions of one issue, one per day.
+fn quick_actions(days: &BTreeMap<NaiveDate, isize>) -> Vec<String> {
+    days.iter()
+        .filter(|(_, seconds)| **seconds >= 30)
+        .map(|(date, seconds)| format!("/spend {} {date}", duration(*seconds)))
+        .collect()
+}
+
+/// The quick actions of every issue below its reference, for pasting into
+/// the issues by hand.
+pub fn text(spent: &Spent) -> String {
+    spent
+        .iter()
+        .map(|(reference, days)| (reference, quick_actions(days)))
+        .filter(|(_, actions)| !actions.is_empty())
+        .map(|(reference, actions)| format!("{reference}\n{}\n", actions.join("\n")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Posts the quick actions of every issue as a comment on it, through the
+/// API of the GitLab at `url`. References without a project, like `#12`,
+/// are taken to be in `default_project`. Returns how many issues were
+/// commented on.
+pub fn push(
+    spent: &Spent,
+    url: &str,
+    default_project: &str,
+    token: &str,
+) -> Result<usize, Box<dyn Error>> {
+    // Every reference is resolved before anything is posted, so that a
+    // mistake leaves no issue commented on.
+    let mut comments = Vec::new();
+    for (reference, days) in spent {
+        let actions = quick_actions(days);
+        if actions.is_empty() {
+            continue;
+        }
+        let (project, iid) = reference.rsplit_once('#').unwrap_or(("", reference));
+        let project = if project.is_empty() {
+            default_project
+        } else {
+            project
+        };
+        if project.is_empty() {
+            Err(format!(
+                "{reference} names no project, set gitlab_project or write it as group/project{reference}"
+            ))?
+        }
+        let endpoint = format!(
+            "{}/api/v4/projects/{}/issues/{iid}/notes",
+            url.trim_end_matches('/'),
+            project.replace('/', "%2F")
+        );
+        comments.push((reference, endpoint, actions.join("\n")));
+    }
+    for (reference, endpoint, body) in &comments {
+        ureq::post(endpoint)
+            .set("PRIVATE-TOKEN", token)
+            .send_form(&[("body", body)])
+            .map_err(|err| format!("{reference}: {err}"))?;
+    }
+    Ok(comments.len())
+}
@@ -0,0 +1,82 @@
+//! A calendar of a year with a cell for every day, shaded by the work done
+//! that day.
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::time::s_to_hhmm;
+
+/// The shades from no work to the longest day, as background colors of the
+/// 256-color palette.
+const COLORS: [u8; 5] = [236, 22, 28, 34, 46];
+
+/// The shades without colors.
+const BLOCKS: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+const WEEKDAYS: [&str; 7] = ["Mon", "", "Wed", "", "Fri", "", "Sun"];
+
+/// A cell of two columns in `shade`, see [`COLORS`].
+fn cell(shade: usize, color: bool) -> String {
+    if color {
+        format!("\x1b[48;5;{}m  \x1b[0m", COLORS[shade])
+    } else {
+        BLOCKS[shade].to_string().repeat(2)
+    }
+}
+
+/// The shade of `duration` given the `longest` day: none for no work, and
+/// otherwise by the quarter of the longest day that it reaches.
+fn shade(duration: isize, longest: isize) -> usize {
+    if duration <= 0 || longest <= 0 {
+        0
+    } else {
+        (((duration * 4) as f64 / longest as f64).ceil() as usize).clamp(1, 4)
+    }
+}
+
+/// Draws `year` as rows of weekdays and columns of weeks starting on Monday,
+/// with the work of every day in `durations` as a shade, followed by a legend
+/// and the total. Shades are drawn with ANSI colors if `color` is set, and
+/// with blocks otherwise.
+pub fn render(year: i32, durations: &BTreeMap<NaiveDate, isize>, color: bool) -> String {
+    let first = NaiveDate::from_ymd_opt(year, 1, 1).expect("every year has a January 1");
+    let last = NaiveDate::from_ymd_opt(year, 12, 31).expect("every year has a December 31");
+    let monday = first - Duration::days(first.weekday().num_days_from_monday().into());
+    let weeks = ((last - monday).num_days() / 7 + 1) as usize;
+    let longest = durations.values().copied().max().unwrap_or(0);
+
+    let mut months = " ".repeat(4 + 2 * weeks);
+    for month in 1..=12 {
+        let start = NaiveDate::from_ymd_opt(year, month, 1).expect("the month exists");
+        let column = 4 + 2 * ((start - monday).num_days() / 7) as usize;
+        let name = start.format("%b").to_string();
+        months.replace_range(column..column + name.len(), &name);
+    }
+    let mut text = months.trim_end().to_owned() + "\n";
+
+    for (weekday, name) in WEEKDAYS.iter().enumerate() {
+        let mut row = format!("{name:<4}");
+        for week in 0..weeks {
+            let date = monday + Duration::days((week * 7 + weekday) as i64);
+            if date < first || date > last {
+                row += "  ";
+            } else {
+                let duration = durations.get(&date).copied().unwrap_or(0);
+                row += &cell(shade(duration, longest), color);
+            }
+        }
+        text += row.trim_end();
+        text += "\n";
+    }
+
+    let legend: String = (0..COLORS.len()).map(|shade| cell(shade, color)).collect();
+    let total: isize = durations.values().sum();
+    let worked = durations.values().filter(|duration| **duration > 0).count();
+    text += &format!(
+        "\n    Less {legend} More, up to {}\n    {} over {worked} days\n",
+        s_to_hhmm(longest),
+        s_to_hhmm(total)
+    );
+    text
+}
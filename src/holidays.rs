@@ -0,0 +1,195 @@
+//! Public holidays, computed for a region or imported from an ICS calendar.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::ics;
+use crate::store::data_dir;
+
+/// The holidays fetched, in the data dir.
+pub const FILE_NAME: &str = "holidays.csv";
+
+/// Germany as a whole, and its states by their ISO 3166-2 code.
+pub const REGIONS: [&str; 17] = [
+    "de", "de-bb", "de-be", "de-bw", "de-by", "de-hb", "de-he", "de-hh", "de-mv", "de-ni", "de-nw",
+    "de-rp", "de-sh", "de-sl", "de-sn", "de-st", "de-th",
+];
+
+#[derive(Deserialize, Serialize)]
+struct Holiday {
+    date: NaiveDate,
+    name: String,
+}
+
+/// Easter Sunday of `year`, by the anonymous Gregorian algorithm.
+fn easter(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32).expect("Easter is a valid day")
+}
+
+/// The holidays of `region` in `year`, see [`REGIONS`].
+pub fn region_holidays(
+    region: &str,
+    year: i32,
+) -> Result<Vec<(NaiveDate, &'static str)>, Box<dyn Error>> {
+    if !REGIONS.contains(&region) {
+        Err(format!(
+            "unknown region '{region}', expected one of {}",
+            REGIONS.join(", ")
+        ))?
+    }
+    let state = region.strip_prefix("de-").unwrap_or("");
+    let in_states = |states: &[&str]| states.contains(&state);
+    let day =
+        |month, day| NaiveDate::from_ymd_opt(year, month, day).expect("holidays are valid days");
+    let easter = easter(year);
+
+    let mut holidays = vec![
+        (day(1, 1), "New Year's Day"),
+        (easter - Duration::days(2), "Good Friday"),
+        (easter + Duration::days(1), "Easter Monday"),
+        (day(5, 1), "Labour Day"),
+        (easter + Duration::days(39), "Ascension Day"),
+        (easter + Duration::days(50), "Whit Monday"),
+        (day(10, 3), "German Unity Day"),
+        (day(12, 25), "Christmas Day"),
+        (day(12, 26), "Boxing Day"),
+    ];
+    if in_states(&["bw", "by", "st"]) {
+        holidays.push((day(1, 6), "Epiphany"));
+    }
+    if in_states(&["be", "mv"]) {
+        holidays.push((day(3, 8), "International Women's Day"));
+    }
+    if in_states(&["bw", "by", "he", "nw", "rp", "sl"]) {
+        holidays.push((easter + Duration::days(60), "Corpus Christi"));
+    }
+    if in_states(&["by", "sl"]) {
+        holidays.push((day(8, 15), "Assumption Day"));
+    }
+    if in_states(&["th"]) {
+        holidays.push((day(9, 20), "World Children's Day"));
+    }
+    if in_states(&["bb", "hb", "hh", "mv", "ni", "sh", "sn", "st", "th"]) {
+        holidays.push((day(10, 31), "Reformation Day"));
+    }
+    if in_states(&["bw", "by", "nw", "rp", "sl"]) {
+        holidays.push((day(11, 1), "All Saints' Day"));
+    }
+    if in_states(&["sn"]) {
+        // The Wednesday before November 23rd.
+        let mut date = day(11, 22);
+        while date.weekday() != Weekday::Wed {
+            date = date.pred_opt().expect("November has days before the 22nd");
+        }
+        holidays.push((date, "Repentance and Prayer Day"));
+    }
+    holidays.sort();
+    Ok(holidays)
+}
+
+fn path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(data_dir()?.join(FILE_NAME))
+}
+
+/// The holidays imported with [`import`].
+pub fn read_imported() -> Result<BTreeMap<NaiveDate, String>, Box<dyn Error>> {
+    let path = path()?;
+    let contents = match fs::read(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(err) => Err(format!("{}: {err}", path.display()))?,
+    };
+    let mut rdr = csv::Reader::from_reader(contents.as_slice());
+    let mut holidays = BTreeMap::new();
+    for holiday in rdr.deserialize::<Holiday>() {
+        let holiday = holiday.map_err(|err| format!("{}: {err}", path.display()))?;
+        holidays.insert(holiday.date, holiday.name);
+    }
+    Ok(holidays)
+}
+
+/// The holidays from `from` to `to` (inclusive), of `region` unless it is
+/// empty, and imported ones. Imported ones take precedence.
+pub fn between(
+    region: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<BTreeMap<NaiveDate, String>, Box<dyn Error>> {
+    let mut holidays = BTreeMap::new();
+    if !region.is_empty() {
+        for year in from.year()..=to.year() {
+            for (date, name) in region_holidays(region, year)? {
+                holidays.insert(date, name.to_owned());
+            }
+        }
+    }
+    holidays.extend(read_imported()?);
+    holidays.retain(|date, _| (from..=to).contains(date));
+    Ok(holidays)
+}
+
+/// The days of the events of an ICS calendar. An event covers the days from
+/// its start up to its end, exclusive.
+fn parse_ics(contents: &str) -> Result<Vec<(NaiveDate, String)>, Box<dyn Error>> {
+    let mut holidays = Vec::new();
+    for event in ics::parse(contents) {
+        let start = event
+            .start
+            .ok_or_else(|| format!("event '{}' has no start", event.summary))?
+            .date();
+        let end = event
+            .end
+            .map(|end| end.date())
+            .filter(|end| *end > start)
+            .unwrap_or(start + Duration::days(1));
+        for date in start.iter_days().take_while(|date| *date < end) {
+            holidays.push((date, event.summary.clone()));
+        }
+    }
+    Ok(holidays)
+}
+
+/// Adds the events of the ICS calendar at `ics` to the imported holidays.
+/// Returns how many days were imported.
+pub fn import(ics: &Path) -> Result<usize, Box<dyn Error>> {
+    let contents = fs::read_to_string(ics).map_err(|err| format!("{}: {err}", ics.display()))?;
+    let imported = parse_ics(&contents).map_err(|err| format!("{}: {err}", ics.display()))?;
+
+    let mut holidays = read_imported()?;
+    for (date, name) in &imported {
+        holidays.insert(*date, name.clone());
+    }
+
+    let path = path()?;
+    let tmp_path = path.with_extension("csv.tmp");
+    let file = File::create(&tmp_path)?;
+    let mut wtr = csv::Writer::from_writer(&file);
+    for (date, name) in holidays {
+        wtr.serialize(Holiday { date, name })?;
+    }
+    wtr.flush()?;
+    drop(wtr);
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(imported.len())
+}
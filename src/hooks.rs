@@ -0,0 +1,179 @@
+//! What happens after a stamp, as configured: webhooks are posted, the
+//! Slack status is set, the state is published over MQTT and hook scripts
+//! run.
+
+use std::error::Error;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use chrono::NaiveDate;
+
+use crate::config::{self, Config};
+use crate::model::{Interval, Record};
+use crate::store::{read_work_time, Store};
+use crate::time::s_to_hhmm;
+use crate::{format, mqtt, notify, projects, secret, slack};
+
+/// The payload posted to webhooks unless `webhook_payload` says otherwise.
+pub const DEFAULT_PAYLOAD: &str = r#"{{"event": "{event}", "kind": "{kind}", "date": "{date}", "time": "{time}", "project": "{project}", "note": "{note}", "duration_s": {duration_s}}}"#;
+
+/// The keyring entry holding the bearer token sent to webhooks.
+pub const SECRET: &str = "webhook";
+
+/// How long a webhook may take to answer before it is given up on.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The placeholders of a stamp: `event` is `start` or `stop`, `kind` the
+/// kind as recorded, e.g. `stop:lunch`, and `duration_hm` and `duration_s`
+/// the work of the day so far.
+pub fn stamp_vars(
+    date: NaiveDate,
+    record: &Record,
+    duration: isize,
+) -> Vec<(&'static str, String)> {
+    let event = if record.kind == "strt" {
+        "start"
+    } else {
+        "stop"
+    };
+    vec![
+        ("event", event.to_owned()),
+        ("kind", record.kind.clone()),
+        ("date", date.to_string()),
+        ("time", record.time()),
+        ("project", record.project.clone().unwrap_or_default()),
+        ("note", record.note.clone().unwrap_or_default()),
+        ("duration_hm", s_to_hhmm(duration)),
+        ("duration_s", duration.to_string()),
+    ]
+}
+
+/// The payload of `config` for a stamp, with the values escaped for JSON
+/// strings.
+pub fn payload(config: &Config, vars: &[(&'static str, String)]) -> Result<String, Box<dyn Error>> {
+    let escaped: Vec<_> = vars
+        .iter()
+        .map(|(name, value)| {
+            let quoted = serde_json::to_string(value)?;
+            Ok((*name, quoted[1..quoted.len() - 1].to_owned()))
+        })
+        .collect::<Result<_, serde_json::Error>>()?;
+    let template = if config.webhook_payload.is_empty() {
+        DEFAULT_PAYLOAD
+    } else {
+        &config.webhook_payload
+    };
+    Ok(format::render(template, &escaped)?)
+}
+
+/// Posts the payload of a stamp to every webhook.
+fn post_webhooks(config: &Config, vars: &[(&'static str, String)], failures: &mut Vec<String>) {
+    if config.webhooks.is_empty() {
+        return;
+    }
+    let body = match payload(config, vars) {
+        Ok(body) => body,
+        Err(err) => return failures.push(format!("webhook_payload: {err}")),
+    };
+    let token = secret::get(SECRET).ok().flatten();
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    for url in &config.webhooks {
+        let mut request = agent.post(url).set("Content-Type", "application/json");
+        if let Some(token) = &token {
+            request = request.set("Authorization", &format!("Bearer {token}"));
+        }
+        if let Err(err) = request.send_string(&body) {
+            failures.push(format!("webhook: {err}"));
+        }
+    }
+}
+
+/// Runs `hooks/on-start` or `hooks/on-stop` in the config dir, if there is
+/// one, with the placeholders of a stamp in the environment as `AZK_TIME`
+/// and so on. `AZK_DURATION` is the work of the day in HH:MM.
+fn run_script(vars: &[(&'static str, String)], failures: &mut Vec<String>) {
+    let event = vars
+        .iter()
+        .find(|(name, _)| *name == "event")
+        .map_or("", |(_, value)| value.as_str());
+    let script = match config::dir() {
+        Ok(dir) => dir.join("hooks").join(format!("on-{event}")),
+        Err(err) => return failures.push(err.to_string()),
+    };
+    if !script.exists() {
+        return;
+    }
+    let mut command = Command::new(&script);
+    command.stdin(Stdio::null());
+    for (name, value) in vars {
+        let name = match *name {
+            "duration_hm" => "duration",
+            name => name,
+        };
+        command.env(format!("AZK_{}", name.to_uppercase()), value);
+    }
+    match command.status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => failures.push(format!("{}: {status}", script.display())),
+        Err(err) => failures.push(format!("{}: {err}", script.display())),
+    }
+}
+
+/// Notifies if the stopped `interval` took its project past a threshold of
+/// its budget.
+fn notify_budget(
+    store: &dyn Store,
+    date: NaiveDate,
+    config: &Config,
+    interval: &Interval,
+) -> Result<(), Box<dyn Error>> {
+    if !config.notify_budget {
+        return Ok(());
+    }
+    for budget in projects::budgets(store, date, 0)? {
+        if interval.project.as_ref() == Some(&budget.project) {
+            notify::budget_crossed(config, &budget, interval.duration())?;
+        }
+    }
+    Ok(())
+}
+
+/// Reacts to `record` having been stamped on `date`. Returns what went
+/// wrong, e.g. a webhook that could not be reached, which does not undo the
+/// stamp.
+pub fn stamped(store: &dyn Store, date: NaiveDate, record: &Record) -> Vec<String> {
+    let config = match config::load() {
+        Ok(config) => config,
+        Err(err) => return vec![err.to_string()],
+    };
+    let day = match read_work_time(store, date, None) {
+        Ok(day) => day,
+        Err(err) => return vec![err.to_string()],
+    };
+    let duration = day.as_ref().map_or(0, |day| day.duration());
+    let vars = stamp_vars(date, record, duration);
+
+    let mut failures = Vec::new();
+    post_webhooks(&config, &vars, &mut failures);
+    if let Err(err) = slack::set_status(&config, record) {
+        failures.push(format!("Slack: {err}"));
+    }
+    let state = if record.kind == "strt" {
+        "working"
+    } else {
+        "break"
+    };
+    if let Err(err) = mqtt::publish_state(&config, state, duration) {
+        failures.push(format!("MQTT: {err}"));
+    }
+    if record.kind != "strt" {
+        let stopped = day.as_ref().and_then(|day| day.intervals.last());
+        if let Some(interval) = stopped.filter(|interval| interval.project.is_some()) {
+            if let Err(err) = notify_budget(store, date, &config, interval) {
+                failures.push(format!("Budget: {err}"));
+            }
+        }
+    }
+    run_script(&vars, &mut failures);
+    failures
+}
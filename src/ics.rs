@@ -0,0 +1,116 @@
+//! The parts of iCalendar (RFC 5545) that azk reads and writes.
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+
+/// When an event starts or ends.
+#[derive(Clone, Copy, Debug)]
+pub enum Time {
+    /// A whole day.
+    Date(NaiveDate),
+    Utc(NaiveDateTime),
+    /// A time on the wall clock, in the local time zone. Times given for
+    /// another time zone are taken as local as well.
+    Floating(NaiveDateTime),
+}
+
+impl Time {
+    fn parse(value: &str) -> Option<Time> {
+        if let Some(utc) = value.strip_suffix('Z') {
+            return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+                .ok()
+                .map(Time::Utc);
+        }
+        if let Ok(time) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+            return Some(Time::Floating(time));
+        }
+        NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .map(Time::Date)
+    }
+
+    /// The day on the local wall clock.
+    pub fn date(&self) -> NaiveDate {
+        match self {
+            Time::Date(date) => *date,
+            Time::Utc(time) => Utc
+                .from_utc_datetime(time)
+                .with_timezone(&Local)
+                .date_naive(),
+            Time::Floating(time) => time.date(),
+        }
+    }
+
+    /// The instant in the local time zone, unless it is a whole day or
+    /// skipped when the clocks go forward.
+    pub fn local(&self) -> Option<DateTime<FixedOffset>> {
+        let local = match self {
+            Time::Date(_) => return None,
+            Time::Utc(time) => Utc.from_utc_datetime(time).with_timezone(&Local),
+            Time::Floating(time) => Local.from_local_datetime(time).earliest()?,
+        };
+        Some(local.with_timezone(&local.offset().fix()))
+    }
+}
+
+pub struct Event {
+    pub start: Option<Time>,
+    pub end: Option<Time>,
+    pub summary: String,
+}
+
+/// Reads the events of a calendar, leaving out everything else.
+pub fn parse(contents: &str) -> Vec<Event> {
+    // Long lines are folded by starting the continuation with a space.
+    let unfolded = contents
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+
+    let mut events = Vec::new();
+    let mut event: Option<Event> = None;
+    for line in unfolded.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let name = key.split(';').next().unwrap_or(key);
+        match (name, event.as_mut()) {
+            ("BEGIN", _) if value == "VEVENT" => {
+                event = Some(Event {
+                    start: None,
+                    end: None,
+                    summary: String::new(),
+                })
+            }
+            ("DTSTART", Some(event)) => event.start = Time::parse(value),
+            ("DTEND", Some(event)) => event.end = Time::parse(value),
+            ("SUMMARY", Some(event)) => event.summary = unescape(value),
+            ("END", Some(_)) if value == "VEVENT" => events.extend(event.take()),
+            _ => {}
+        }
+    }
+    events
+}
+
+/// Escapes text for a property value.
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => unescaped.push('\n'),
+                Some(c) => unescaped.push(c),
+                None => {}
+            },
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
@@ -0,0 +1,279 @@
+//! Work recorded by other tools, added to the store as intervals.
+
+use std::error::Error;
+
+use chrono::{
+    DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Timelike,
+    Utc,
+};
+use serde::Deserialize;
+
+use crate::ics;
+use crate::store::{self, Store};
+use crate::time::local_timestamp;
+
+/// The formats of `azk import`.
+pub const FORMATS: [&str; 5] = ["ics", "toggl", "clockify", "watson", "timewarrior"];
+
+/// Fields given to every imported interval, as with `--as project=meetings`.
+#[derive(Default)]
+pub struct Overrides {
+    pub project: Option<String>,
+    pub note: Option<String>,
+}
+
+impl Overrides {
+    /// Parses assignments like `project=meetings` or `note=standup`.
+    pub fn parse(assignments: &[String]) -> Result<Overrides, Box<dyn Error>> {
+        let mut overrides = Overrides::default();
+        for assignment in assignments {
+            match assignment.split_once('=') {
+                Some(("project", value)) => overrides.project = Some(value.to_owned()),
+                Some(("note", value)) => overrides.note = Some(value.to_owned()),
+                _ => Err(format!(
+                    "invalid assignment '{assignment}', expected project=VALUE or note=VALUE"
+                ))?,
+            }
+        }
+        Ok(overrides)
+    }
+}
+
+/// A stretch of work as read from another tool.
+struct Entry {
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    project: Option<String>,
+    note: Option<String>,
+}
+
+/// What an import did.
+pub struct Imported {
+    pub added: usize,
+    /// Why each entry that was left out was, e.g. because it overlaps work
+    /// that is already recorded.
+    pub skipped: Vec<String>,
+}
+
+/// Adds the entries of `contents` in `format` (see [`FORMATS`]) from `from`
+/// to `to` (inclusive) to the store. Entries that are already recorded, e.g.
+/// by importing them before, or overlap recorded work are skipped, as are
+/// ones that span midnight.
+pub fn import(
+    store: &dyn Store,
+    format: &str,
+    contents: &str,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    overrides: &Overrides,
+) -> Result<Imported, Box<dyn Error>> {
+    let entries = match format {
+        "ics" => ics_entries(contents),
+        "toggl" | "clockify" => report_entries(contents)?,
+        "watson" => watson_entries(contents)?,
+        "timewarrior" => timewarrior_entries(contents)?,
+        _ => Err(format!(
+            "unknown import format '{format}', expected one of {}",
+            FORMATS.join(", ")
+        ))?,
+    };
+
+    let mut imported = Imported {
+        added: 0,
+        skipped: Vec::new(),
+    };
+    for entry in entries {
+        let date = entry.start.date_naive();
+        if from.is_some_and(|from| date < from) || to.is_some_and(|to| date > to) {
+            continue;
+        }
+        let start = format!("{}", entry.start.format("%H:%M:%S"));
+        let end = format!("{}", entry.end.format("%H:%M:%S"));
+        let described = format!("{date} {} - {}", &start[..5], &end[..5]);
+        if entry.end.date_naive() != date {
+            imported
+                .skipped
+                .push(format!("{described}: spans midnight"));
+            continue;
+        }
+        let duplicate = store::read_work_time(store, date, None)?.is_some_and(|day| {
+            day.intervals.iter().any(|interval| {
+                interval.started_at == entry.start && interval.ended_at == entry.end
+            })
+        });
+        if duplicate {
+            imported
+                .skipped
+                .push(format!("{described}: already recorded"));
+            continue;
+        }
+        let project = overrides.project.clone().or(entry.project);
+        let note = overrides.note.clone().or(entry.note);
+        match store::add_interval(store, date, &start, &end, project.as_ref(), note.as_ref()) {
+            Ok(()) => imported.added += 1,
+            Err(err) => imported.skipped.push(format!("{described}: {err}")),
+        }
+    }
+    Ok(imported)
+}
+
+/// The events of an ICS calendar with a start and an end time, with their
+/// summary as the note. Whole-day events are left out.
+fn ics_entries(contents: &str) -> Vec<Entry> {
+    ics::parse(contents)
+        .into_iter()
+        .filter_map(|event| {
+            Some(Entry {
+                start: event.start?.local()?,
+                end: event.end?.local()?,
+                project: None,
+                note: Some(event.summary).filter(|summary| !summary.is_empty()),
+            })
+        })
+        .collect()
+}
+
+/// A row of a detailed report exported as CSV by Toggl Track or Clockify, of
+/// the columns that are used.
+#[derive(Deserialize)]
+struct ReportRow {
+    #[serde(rename = "Project")]
+    project: Option<String>,
+    #[serde(rename = "Description")]
+    description: Option<String>,
+    #[serde(rename = "Start date", alias = "Start Date")]
+    start_date: String,
+    #[serde(rename = "Start time", alias = "Start Time")]
+    start_time: String,
+    #[serde(rename = "End date", alias = "End Date")]
+    end_date: String,
+    #[serde(rename = "End time", alias = "End Time")]
+    end_time: String,
+}
+
+/// Parses a day and a time of day on the local wall clock, in the formats
+/// the reports use depending on their settings.
+fn parse_local(date: &str, time: &str) -> Result<DateTime<FixedOffset>, Box<dyn Error>> {
+    let day = ["%Y-%m-%d", "%m/%d/%Y", "%d.%m.%Y", "%d/%m/%Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(date.trim(), format).ok())
+        .ok_or_else(|| format!("invalid date '{date}'"))?;
+    let time_of_day = ["%H:%M:%S", "%H:%M", "%I:%M:%S %p", "%I:%M %p"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(time.trim(), format).ok())
+        .ok_or_else(|| format!("invalid time '{time}'"))?;
+    Ok(
+        local_timestamp(day, time_of_day.num_seconds_from_midnight() as isize)
+            .ok_or_else(|| format!("{date} {time} does not exist in the local time zone"))?,
+    )
+}
+
+/// The time entries of a detailed report exported as CSV by Toggl Track or
+/// Clockify, with their description as the note.
+fn report_entries(contents: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let mut rdr = csv::Reader::from_reader(contents.as_bytes());
+    let mut entries = Vec::new();
+    for row in rdr.deserialize::<ReportRow>() {
+        let row = row?;
+        entries.push(Entry {
+            start: parse_local(&row.start_date, &row.start_time)?,
+            end: parse_local(&row.end_date, &row.end_time)?,
+            project: row.project,
+            note: row.description,
+        });
+    }
+    Ok(entries)
+}
+
+/// A frame of Watson: its start and stop as Unix timestamps, project, id,
+/// tags and when it was last changed.
+type WatsonFrame = (i64, i64, String, String, Vec<String>, i64);
+
+/// The frames of Watson's `frames` file, with their tags as the note.
+fn watson_entries(contents: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let local = |timestamp| -> Result<DateTime<FixedOffset>, Box<dyn Error>> {
+        let time = Local
+            .timestamp_opt(timestamp, 0)
+            .single()
+            .ok_or_else(|| format!("invalid timestamp {timestamp}"))?;
+        Ok(time.with_timezone(&time.offset().fix()))
+    };
+    let frames: Vec<WatsonFrame> = serde_json::from_str(contents)?;
+    let mut entries = Vec::new();
+    for (start, stop, project, _, tags, _) in frames {
+        entries.push(Entry {
+            start: local(start)?,
+            end: local(stop)?,
+            project: Some(project),
+            note: Some(tags.join(", ")).filter(|note| !note.is_empty()),
+        });
+    }
+    Ok(entries)
+}
+
+/// Splits the tags of a Timewarrior interval, which are quoted if they
+/// contain spaces.
+fn timewarrior_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut chars = text.trim().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' => {}
+            '"' => {
+                let mut word = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => word.extend(chars.next()),
+                        '"' => break,
+                        c => word.push(c),
+                    }
+                }
+                words.push(word);
+            }
+            c => {
+                let mut word = String::from(c);
+                word.extend(chars.by_ref().take_while(|c| *c != ' '));
+                words.push(word);
+            }
+        }
+    }
+    words
+}
+
+/// The closed intervals of Timewarrior data files, as in
+/// `inc 20261021T080000Z - 20261021T091500Z # azk review # "annotation"`.
+/// The first tag becomes the project, and the annotation or else the other
+/// tags the note.
+fn timewarrior_entries(contents: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let local = |time: &str| -> Result<DateTime<FixedOffset>, Box<dyn Error>> {
+        let time = NaiveDateTime::parse_from_str(time, "%Y%m%dT%H%M%SZ")
+            .map_err(|err| format!("invalid time '{time}': {err}"))?;
+        let time = Utc.from_utc_datetime(&time).with_timezone(&Local);
+        Ok(time.with_timezone(&time.offset().fix()))
+    };
+    let mut entries = Vec::new();
+    for line in contents.lines() {
+        let Some(line) = line.strip_prefix("inc ") else {
+            continue;
+        };
+        let mut parts = line.splitn(3, " # ");
+        let range = parts.next().unwrap_or("");
+        let Some((start, end)) = range.trim().split_once(" - ") else {
+            // The interval is still open.
+            continue;
+        };
+        let mut tags = timewarrior_words(parts.next().unwrap_or("")).into_iter();
+        let annotation = parts
+            .next()
+            .and_then(|annotation| timewarrior_words(annotation).into_iter().next());
+        let project = tags.next();
+        let note = annotation.unwrap_or_else(|| tags.collect::<Vec<_>>().join(", "));
+        entries.push(Entry {
+            start: local(start)?,
+            end: local(end)?,
+            project,
+            note: Some(note).filter(|note| !note.is_empty()),
+        });
+    }
+    Ok(entries)
+}
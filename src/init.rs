@@ -0,0 +1,242 @@
+//! `azk init`, which asks a new user the settings most need and writes
+//! them to the config file, then offers to install shell completions.
+
+use std::error::Error;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, Days, NaiveDate};
+use clap::Command;
+use clap_complete::Shell;
+
+use azk::config;
+use azk::schedule::Schedule;
+use azk::store::data_dir;
+use azk::time::{hhmm_to_s, s_to_hhmm};
+
+use crate::confirm;
+
+/// Asks `question`, returning the answer, or `default` if there is none.
+fn ask(question: &str, default: &str) -> Result<String, Box<dyn Error>> {
+    print!("{question} [{default}] ");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        Err("no answer")?
+    }
+    Ok(match input.trim() {
+        "" => default.to_owned(),
+        answer => answer.to_owned(),
+    })
+}
+
+/// Asks `question` until `parse` takes the answer.
+fn ask_until<T>(
+    question: &str,
+    default: &str,
+    parse: impl Fn(&str) -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    loop {
+        match parse(&ask(question, default)?) {
+            Ok(value) => return Ok(value),
+            Err(err) => println!("{err}"),
+        }
+    }
+}
+
+/// The days worked in `schedule`, as in `mon, tue, thu`.
+fn work_days(schedule: &Schedule) -> String {
+    // A Monday.
+    let monday = NaiveDate::from_ymd_opt(2024, 1, 1).expect("valid date");
+    (0..7)
+        .filter_map(|offset| monday.checked_add_days(Days::new(offset)))
+        .filter(|date| schedule.target(*date) > 0)
+        .map(|date| date.weekday().to_string().to_lowercase())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The schedule of working `target` on `days`, as in `mon-thu, sat`, and
+/// nothing on the others.
+fn parse_schedule(days: &str, target: &str) -> Result<Schedule, Box<dyn Error>> {
+    let mut schedule = String::from("mon-sun=00:00");
+    for days in days
+        .split(',')
+        .map(str::trim)
+        .filter(|days| !days.is_empty())
+    {
+        schedule += &format!(", {days}={target}");
+    }
+    Schedule::parse(&schedule, 0)
+}
+
+/// Where the completions of `shell` are picked up from, if azk knows.
+fn completions_path(shell: Shell) -> Option<(PathBuf, Option<&'static str>)> {
+    let dirs = directories::BaseDirs::new()?;
+    match shell {
+        Shell::Bash => Some((
+            dirs.data_dir().join("bash-completion/completions/azk"),
+            None,
+        )),
+        Shell::Zsh => Some((
+            dirs.home_dir().join(".zfunc/_azk"),
+            Some("Add 'fpath+=~/.zfunc' before compinit in ~/.zshrc to load them."),
+        )),
+        Shell::Fish => Some((dirs.config_dir().join("fish/completions/azk.fish"), None)),
+        _ => None,
+    }
+}
+
+/// What completes the names of the projects, as printed by `azk project list
+/// --names` when completing, in bash, zsh and fish.
+const BASH_PROJECTS: &str =
+    r#"COMPREPLY=($(compgen -W "$(azk project list --names 2>/dev/null)" -- "${cur}"))"#;
+const ZSH_PROJECTS: &str = r#"_azk_projects() {
+    local projects=(${(f)"$(azk project list --names 2>/dev/null)"})
+    compadd -a projects
+}
+"#;
+const FISH_PROJECTS: &str = r#"-r -f -a "(azk project list --names 2>/dev/null)""#;
+
+/// The completions of `cli` for `shell`, which also offer the known projects
+/// for `--project` in bash, zsh and fish.
+pub fn completions(shell: Shell, mut cli: Command) -> Vec<u8> {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut cli, "azk", &mut script);
+    let script = String::from_utf8(script).expect("completions are UTF-8");
+    let script = match shell {
+        Shell::Bash => ["--project", "-p"].iter().fold(script, |script, flag| {
+            let case = format!("{flag})\n                    ");
+            script.replace(
+                &format!(r#"{case}COMPREPLY=($(compgen -f "${{cur}}"))"#),
+                &format!("{case}{BASH_PROJECTS}"),
+            )
+        }),
+        Shell::Zsh => {
+            let script = script
+                .replace(":PROJECT:_default'", ":PROJECT:_azk_projects'")
+                .replace(
+                    "'::project -- The project the work is for:_default'",
+                    "'::project -- The project the work is for:_azk_projects'",
+                );
+            script.replacen(
+                "#compdef azk\n",
+                &format!("#compdef azk\n\n{ZSH_PROJECTS}"),
+                1,
+            )
+        }
+        Shell::Fish => script.replace(
+            "-l project -d 'The project the work is for' -r",
+            &format!("-l project -d 'The project the work is for' {FISH_PROJECTS}"),
+        ),
+        _ => script,
+    };
+    script.into_bytes()
+}
+
+fn install_completions(cli: Command, shell: Shell, path: &Path) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+    }
+    fs::write(path, completions(shell, cli)).map_err(|err| format!("{}: {err}", path.display()))?;
+    Ok(())
+}
+
+/// Asks for the daily target, the work days, how to print durations and
+/// where to keep the records, writes them to the config file, and offers to
+/// install the completions of `cli` for the user's shell.
+pub fn init(cli: Command) -> Result<(), Box<dyn Error>> {
+    if !io::stdin().is_terminal() {
+        Err("not a terminal, use 'azk config set' instead")?
+    }
+    let path = config::path()?;
+    if path.exists()
+        && !confirm(&format!(
+            "{} exists already. Go through the settings again?",
+            path.display()
+        ))?
+    {
+        return Ok(());
+    }
+    let config = config::load()?;
+    println!("Press enter to keep what is in brackets.");
+
+    let daily_target = ask_until(
+        "How long do you work a day, in HH:MM?",
+        &config.daily_target,
+        |answer| {
+            hhmm_to_s(answer)?;
+            Ok(answer.to_owned())
+        },
+    )?;
+    let default_days = if config.schedule.trim().is_empty() {
+        "mon-fri".to_owned()
+    } else {
+        work_days(&config::schedule(&config)?)
+    };
+    let schedule = ask_until(
+        "Which days do you work, as in mon-fri or mon-thu, sat?",
+        &default_days,
+        |answer| parse_schedule(answer, &daily_target),
+    )?;
+    let default_format = if config.decimal_hours {
+        "decimal"
+    } else {
+        "hh:mm"
+    };
+    let decimal_hours = ask_until(
+        "Print durations as hh:mm or in decimal hours?",
+        default_format,
+        |answer| match answer {
+            "hh:mm" => Ok(false),
+            "decimal" => Ok(true),
+            _ => Err("expected hh:mm or decimal")?,
+        },
+    )?;
+    let current_dir = data_dir()?;
+    let records_dir = ask_until(
+        "Where should the records be kept?",
+        &current_dir.display().to_string(),
+        |answer| Ok(std::path::absolute(answer)?),
+    )?;
+
+    config::set("daily_target", &daily_target)?;
+    if schedule == Schedule::parse("", hhmm_to_s(&daily_target)?)? {
+        config::set("weekly_target", &s_to_hhmm(schedule.weekly()))?;
+        config::set("schedule", "")?;
+    } else {
+        config::set("schedule", &schedule.to_string())?;
+    }
+    config::set("decimal_hours", &decimal_hours.to_string())?;
+    if records_dir != current_dir {
+        config::set("data_dir", &records_dir.display().to_string())?;
+    }
+    if records_dir != current_dir && fs::read_dir(&current_dir)?.next().is_some() {
+        println!(
+            "The records in {} were left there, move them to {} to keep them.",
+            current_dir.display(),
+            records_dir.display()
+        );
+    }
+    println!("Wrote {}.", path.display());
+
+    let Some(shell) = Shell::from_env() else {
+        return Ok(());
+    };
+    let Some((completions, hint)) = completions_path(shell) else {
+        println!("Run 'azk completions {shell}' for the completions of {shell}.");
+        return Ok(());
+    };
+    if confirm(&format!(
+        "Install the completions for {shell} to {}?",
+        completions.display()
+    ))? {
+        install_completions(cli, shell, &completions)?;
+        println!("Installed {}.", completions.display());
+        if let Some(hint) = hint {
+            println!("{hint}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,236 @@
+//! Lenient parsing of times and days typed on the command line.
+
+use std::error::Error;
+
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+
+use crate::time::{parse_day, parse_hhmm, SECONDS_PER_HOUR, SECONDS_PER_MINUTE};
+
+const SECONDS_PER_DAY: isize = 24 * SECONDS_PER_HOUR;
+
+fn format_hhmmss(s: isize) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        s / SECONDS_PER_HOUR,
+        (s % SECONDS_PER_HOUR) / SECONDS_PER_MINUTE,
+        s % SECONDS_PER_MINUTE
+    )
+}
+
+/// Parses `9am`, `9:30pm` or `12am` into seconds since midnight.
+fn parse_meridiem(input: &str) -> Option<isize> {
+    let (clock, pm) = match input.strip_suffix("am") {
+        Some(clock) => (clock, false),
+        None => (input.strip_suffix("pm")?, true),
+    };
+    let (hours, minutes) = clock.trim().split_once(':').unwrap_or((clock.trim(), "0"));
+    let hours: isize = hours.parse().ok()?;
+    let minutes: isize = minutes.parse().ok()?;
+    if !(1..=12).contains(&hours) || !(0..60).contains(&minutes) {
+        return None;
+    }
+    let hours = hours % 12 + if pm { 12 } else { 0 };
+    Some(hours * SECONDS_PER_HOUR + minutes * SECONDS_PER_MINUTE)
+}
+
+/// Parses `5 minutes ago`, `1 hour ago` or `90s ago` into a number of seconds.
+fn parse_ago(input: &str) -> Option<isize> {
+    let amount = input.strip_suffix("ago")?.trim();
+    let split = amount
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(amount.len());
+    let (count, unit) = amount.split_at(split);
+    let count: isize = count.parse().ok()?;
+    let unit = match unit.trim() {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "m" | "min" | "mins" | "minute" | "minutes" => SECONDS_PER_MINUTE,
+        "h" | "hour" | "hours" => SECONDS_PER_HOUR,
+        _ => return None,
+    };
+    count.checked_mul(unit)
+}
+
+/// Parses a time of day given as `HH:MM`, `HH:MM:SS`, `9am`, `5:30pm`,
+/// `noon`, `midnight`, `now` or relative to `now` as in `5 minutes ago`,
+/// into the stored `HH:MM:SS` form. `now` is in seconds since midnight.
+pub fn parse_time(input: &str, now: isize) -> Result<String, Box<dyn Error>> {
+    let normalized = input.trim().to_lowercase();
+    let seconds = match normalized.as_str() {
+        "now" => now,
+        "noon" => 12 * SECONDS_PER_HOUR,
+        "midnight" => 0,
+        text => {
+            if let Some(seconds) = parse_meridiem(text) {
+                seconds
+            } else if let Some(ago) = parse_ago(text) {
+                let seconds = now - ago;
+                if !(0..SECONDS_PER_DAY).contains(&seconds) {
+                    Err(format!("'{input}' is not on the same day"))?
+                }
+                seconds
+            } else {
+                return parse_hhmm(input).map_err(|_| {
+                    format!("invalid time '{input}', expected HH:MM, 9am, noon or '5 minutes ago'")
+                        .into()
+                });
+            }
+        }
+    };
+    Ok(format_hhmmss(seconds))
+}
+
+/// Parses a day given as `YYYY-MM-DD`, `today`, `yesterday`, a weekday like
+/// `monday` (the last one up to `today`), `last-monday` (the last one before
+/// `today`) or a number of days from `today` like `-2`.
+pub fn parse_date(input: &str, today: NaiveDate) -> Result<NaiveDate, Box<dyn Error>> {
+    let normalized = input.trim().to_lowercase();
+    let out_of_range = || format!("'{input}' is out of the range of days");
+    match normalized.as_str() {
+        "today" => return Ok(today),
+        "yesterday" => {
+            return Ok(today
+                .checked_sub_days(Days::new(1))
+                .ok_or_else(out_of_range)?)
+        }
+        _ => {}
+    }
+    if let Ok(offset) = normalized.parse::<i64>() {
+        if normalized.starts_with(['-', '+']) {
+            let days = Days::new(offset.unsigned_abs());
+            let date = if offset < 0 {
+                today.checked_sub_days(days)
+            } else {
+                today.checked_add_days(days)
+            };
+            return Ok(date.ok_or_else(out_of_range)?);
+        }
+    }
+
+    let (before_today, weekday) = match normalized.strip_prefix("last-") {
+        Some(weekday) => (true, weekday),
+        None => (false, normalized.as_str()),
+    };
+    if let Ok(weekday) = weekday.parse::<Weekday>() {
+        let mut days_back =
+            (7 + today.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+        if before_today && days_back == 0 {
+            days_back = 7;
+        }
+        return Ok(today
+            .checked_sub_days(Days::new(days_back.into()))
+            .ok_or_else(out_of_range)?);
+    }
+
+    parse_day(input).map_err(|_| {
+        format!("invalid day '{input}', expected YYYY-MM-DD, yesterday, monday, last-friday or -2")
+            .into()
+    })
+}
+
+/// Parses a range of days like `2024-07-01..2024-07-12` or a single day, see
+/// [`parse_date`], into its first and last day.
+pub fn parse_date_range(
+    input: &str,
+    today: NaiveDate,
+) -> Result<(NaiveDate, NaiveDate), Box<dyn Error>> {
+    let Some((first, last)) = input.split_once("..") else {
+        let date = parse_date(input, today)?;
+        return Ok((date, date));
+    };
+    let (first, last) = (parse_date(first, today)?, parse_date(last, today)?);
+    if last < first {
+        Err(format!("{last} is before {first}"))?
+    }
+    Ok((first, last))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(input: &str) -> NaiveDate {
+        parse_day(input).unwrap()
+    }
+
+    #[test]
+    fn parses_times() {
+        let now = 15 * SECONDS_PER_HOUR + 30 * SECONDS_PER_MINUTE;
+        assert_eq!(parse_time("09:15", now).unwrap(), "09:15:00");
+        assert_eq!(parse_time("9am", now).unwrap(), "09:00:00");
+        assert_eq!(parse_time("5:30PM", now).unwrap(), "17:30:00");
+        assert_eq!(parse_time("12am", now).unwrap(), "00:00:00");
+        assert_eq!(parse_time("12pm", now).unwrap(), "12:00:00");
+        assert_eq!(parse_time("noon", now).unwrap(), "12:00:00");
+        assert_eq!(parse_time("midnight", now).unwrap(), "00:00:00");
+        assert_eq!(parse_time("now", now).unwrap(), "15:30:00");
+        assert_eq!(parse_time("5 minutes ago", now).unwrap(), "15:25:00");
+        assert_eq!(parse_time("2h ago", now).unwrap(), "13:30:00");
+        assert_eq!(parse_time("90s ago", now).unwrap(), "15:28:30");
+    }
+
+    #[test]
+    fn refuses_invalid_times() {
+        assert!(parse_time("13pm", 0).is_err());
+        assert!(parse_time("0am", 0).is_err());
+        assert!(parse_time("9:60am", 0).is_err());
+        assert!(parse_time("soon", 0).is_err());
+        assert!(parse_time(&format!("{} hours ago", isize::MAX), 0).is_err());
+        // Before midnight.
+        assert!(parse_time("1 hour ago", 30 * SECONDS_PER_MINUTE).is_err());
+    }
+
+    #[test]
+    fn parses_relative_days() {
+        let today = date("2024-03-01");
+        assert_eq!(parse_date("today", today).unwrap(), today);
+        assert_eq!(parse_date("Yesterday", today).unwrap(), date("2024-02-29"));
+        assert_eq!(parse_date("-2", today).unwrap(), date("2024-02-28"));
+        assert_eq!(parse_date("+1", today).unwrap(), date("2024-03-02"));
+        assert_eq!(
+            parse_date("-1", date("2024-01-01")).unwrap(),
+            date("2023-12-31")
+        );
+        assert_eq!(
+            parse_date("+1", date("2023-02-28")).unwrap(),
+            date("2023-03-01")
+        );
+        assert_eq!(parse_date("2024-07-12", today).unwrap(), date("2024-07-12"));
+        assert!(parse_date(&format!("-{}", i64::MAX), today).is_err());
+        assert!(parse_date("+999999999", today).is_err());
+        assert!(parse_date("yesterday", NaiveDate::MIN).is_err());
+    }
+
+    #[test]
+    fn parses_weekdays_back_from_today() {
+        // A Wednesday.
+        let today = date("2024-05-01");
+        assert_eq!(parse_date("wednesday", today).unwrap(), today);
+        assert_eq!(
+            parse_date("last-wednesday", today).unwrap(),
+            date("2024-04-24")
+        );
+        assert_eq!(parse_date("monday", today).unwrap(), date("2024-04-29"));
+        // Across the start of the week.
+        assert_eq!(parse_date("thursday", today).unwrap(), date("2024-04-25"));
+        assert_eq!(parse_date("sun", today).unwrap(), date("2024-04-28"));
+        assert_eq!(
+            parse_date("last-sunday", today).unwrap(),
+            date("2024-04-28")
+        );
+    }
+
+    #[test]
+    fn parses_ranges() {
+        let today = date("2024-03-01");
+        assert_eq!(
+            parse_date_range("2024-02-27..today", today).unwrap(),
+            (date("2024-02-27"), today)
+        );
+        assert_eq!(
+            parse_date_range("-1", today).unwrap(),
+            (date("2024-02-29"), date("2024-02-29"))
+        );
+        assert!(parse_date_range("today..yesterday", today).is_err());
+        assert!(parse_date("someday", today).is_err());
+    }
+}
@@ -0,0 +1,40 @@
+//! Work time tracking over day files of start and stop stamps.
+
+pub mod audit;
+pub mod backup;
+pub mod balance;
+pub mod compliance;
+pub mod config;
+pub mod doctor;
+pub mod document;
+pub mod earnings;
+pub mod export;
+pub mod format;
+pub mod gitlab;
+pub mod heatmap;
+pub mod holidays;
+pub mod hooks;
+pub mod ics;
+pub mod import;
+pub mod input;
+pub mod model;
+pub mod mqtt;
+pub mod notify;
+pub mod off;
+pub mod pdf;
+pub mod period;
+pub mod plugin;
+pub mod projects;
+pub mod purge;
+pub mod query;
+pub mod report;
+pub mod rounding;
+pub mod schedule;
+pub mod script;
+pub mod secret;
+pub mod slack;
+pub mod store;
+pub mod sync;
+pub mod time;
+pub mod timesheet;
+pub mod totals;
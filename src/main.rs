@@ -1,163 +1,2075 @@
+mod daemon;
+mod dbus;
+mod edit;
+mod init;
+mod serve;
+mod tui;
+mod watch;
+
 use std::error::Error;
-use std::fs::{self, File};
-use std::path::PathBuf;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use chrono::Datelike;
 use clap::{arg, Command};
 
-use serde::{Deserialize, Serialize};
+use azk::model::DayInfo;
+use azk::script::Script;
+use azk::store::{self, read_work_time, update_time, Store};
+use azk::time::{
+    hhmmss_to_s, last_day_of_month, parse_hhmm, parse_iso_week, parse_month, s_to_decimal,
+    s_to_hhmm, s_to_signed_decimal, s_to_signed_hhmm,
+};
+use azk::{
+    audit, backup, balance, compliance, config, doctor, document, earnings, export, format, gitlab,
+    heatmap, holidays, hooks, import, input, off, period, plugin, projects, purge, query, report,
+    secret, sync, timesheet, totals,
+};
+
+/// Whether durations are printed in decimal hours, set once from
+/// `--decimal` or the config.
+static DECIMAL: AtomicBool = AtomicBool::new(false);
+
+/// Formats a duration for output, in HH:MM or in decimal hours.
+fn hours(s: isize) -> String {
+    if DECIMAL.load(Ordering::Relaxed) {
+        s_to_decimal(s)
+    } else {
+        s_to_hhmm(s)
+    }
+}
+
+fn signed_hours(s: isize) -> String {
+    if DECIMAL.load(Ordering::Relaxed) {
+        s_to_signed_decimal(s)
+    } else {
+        s_to_signed_hhmm(s)
+    }
+}
+
+/// Asks a yes/no question on the terminal, defaulting to no. Without a
+/// terminal there is nobody to ask, so the answer is an error.
+fn confirm(question: &str) -> Result<bool, Box<dyn Error>> {
+    if !io::stdin().is_terminal() {
+        Err("not a terminal, pass --yes to confirm")?
+    }
+    print!("{question} [y/N] ");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(matches!(input.trim(), "y" | "Y" | "yes"))
+}
 
-#[derive(Debug, Deserialize, Serialize)]
-struct Record {
-    kind: String,
-    time: String,
+/// Asks before stamping within the configured `min_gap` of the day's last
+/// stamp, which is most likely a double press. Without a terminal, that is
+/// refused.
+fn confirm_gap(
+    store: &dyn Store,
+    date: chrono::NaiveDate,
+    time: &str,
+) -> Result<bool, Box<dyn Error>> {
+    let min_gap = config::load()?.min_gap as isize;
+    let Some(gap) = store::since_last_stamp(store, date, time)? else {
+        return Ok(true);
+    };
+    if !(0..min_gap).contains(&gap) {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        Err(format!(
+            "the last stamp was only {gap}s before, pass --force to stamp anyway"
+        ))?
+    }
+    confirm(&format!(
+        "The last stamp was only {gap}s before. Stamp anyway?"
+    ))
 }
 
-struct DayInfo {
-    start: isize,
-    duration: isize,
+/// Runs what is configured to happen after a stamp, reporting what failed.
+fn run_hooks(store: &dyn Store, date: chrono::NaiveDate, record: &azk::model::Record) {
+    for failure in hooks::stamped(store, date, record) {
+        eprintln!("{failure}");
+    }
 }
 
-const SECONDS_PER_MINUTE: isize = 60;
-const SECONDS_PER_HOUR: isize = 60 * 60;
+/// How many days back to look for sessions that were never stopped.
+const DANGLING_LOOKBACK_DAYS: i64 = 7;
 
-fn hhmmss_to_s(hhmmss: &str) -> isize {
-    let mut iter = hhmmss
-        .splitn(3, ':')
-        .map(|n| n.parse::<isize>().expect("couldn't parse"));
+/// Closes sessions left open on previous days, either at `auto_close` or at
+/// a time asked for interactively. Without a terminal they are only reported.
+fn resolve_dangling(
+    store: &dyn Store,
+    today: chrono::NaiveDate,
+    auto_close: Option<&String>,
+) -> Result<(), Box<dyn Error>> {
+    let from = today - chrono::Duration::days(DANGLING_LOOKBACK_DAYS);
+    let yesterday = today - chrono::Duration::days(1);
 
-    let h = iter.next().unwrap();
-    let m = iter.next().unwrap();
-    let s = iter.next().unwrap();
+    for report::Day { date, info } in report::days(store, from, yesterday, today, 0)? {
+        if !info.is_some_and(|info| info.ongoing()) {
+            continue;
+        }
+
+        let time = match auto_close {
+            Some(at) => parse_hhmm(at)?,
+            None if io::stdin().is_terminal() => {
+                print!("Work on {date} was never stopped. Stop it at (HH:MM, empty to skip): ");
+                io::stdout().flush()?;
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+                match input.trim() {
+                    "" => continue,
+                    at => parse_hhmm(at)?,
+                }
+            }
+            None => {
+                eprintln!("Work on {date} was never stopped, see --auto-close.");
+                continue;
+            }
+        };
 
-    (h * SECONDS_PER_HOUR) + (m * SECONDS_PER_MINUTE) + s
+        if let Some(record) = update_time(store, date, &time, None, None, None)? {
+            run_hooks(store, date, &record);
+        }
+        println!("Updated {} with {time}.", store.location(date));
+    }
+    Ok(())
 }
 
-fn s_to_hhmm(s: isize) -> String {
-    let hours = s / SECONDS_PER_HOUR;
-    let minutes = (s % SECONDS_PER_HOUR) / SECONDS_PER_MINUTE;
+/// Describes how far today's `duration` is from the daily `target`. While
+/// working, the finish time is predicted from `now`.
+fn remaining_message(duration: isize, ongoing: bool, now: isize, target: isize) -> String {
+    let remaining = target - duration;
+    if remaining <= 0 {
+        format!("Daily target reached, {} over.", hours(-remaining))
+    } else if ongoing {
+        format!(
+            "{} remaining, done at ~{}.",
+            hours(remaining),
+            s_to_hhmm(now + remaining)
+        )
+    } else {
+        format!("{} remaining.", hours(remaining))
+    }
+}
 
-    format!("{:02}:{:02}", hours, minutes)
+/// Prints the time spent per project, unless no project was given at all,
+/// and rolled up per client if any of the projects has one.
+fn print_project_totals(
+    totals: &std::collections::BTreeMap<Option<String>, isize>,
+) -> Result<(), Box<dyn Error>> {
+    if totals.keys().all(Option::is_none) {
+        return Ok(());
+    }
+    let clients = projects::client_totals(totals, &projects::read()?);
+    if clients.keys().all(Option::is_none) {
+        for (project, total) in totals {
+            let project = project.as_deref().unwrap_or("(no project)");
+            println!("  {project:<14}{}", hours(*total));
+        }
+        return Ok(());
+    }
+    for (client, totals) in &clients {
+        let client = client.as_deref().unwrap_or("(no client)");
+        let total: isize = totals.iter().map(|(_, total)| total).sum();
+        println!("  {client:<14}{}", hours(total));
+        for (project, total) in totals {
+            let project = project.as_deref().unwrap_or("(none)");
+            println!("    {project:<12}{}", hours(*total));
+        }
+    }
+    Ok(())
 }
 
-fn _hhmmss_distance(from: &str, to: &str) -> String {
-    let from = hhmmss_to_s(from);
-    let to = hhmmss_to_s(to);
-    let result = (to - from).abs();
-    s_to_hhmm(result)
+/// Prints how much of `budgets` is used, and warns of those nearly or
+/// entirely used up.
+fn print_budgets(budgets: &[projects::BudgetUse]) {
+    if budgets.is_empty() {
+        return;
+    }
+    println!("Budgets");
+    for budget in budgets {
+        println!(
+            "  {:<14}{} of {} ({}%)",
+            budget.project,
+            hours(budget.used),
+            hours(budget.budget),
+            budget.percent()
+        );
+    }
+    for warning in budgets.iter().filter_map(projects::BudgetUse::warning) {
+        println!("warning: {warning}");
+    }
 }
 
-fn write_record(file: &File, record: Record) -> Result<(), Box<dyn Error>> {
-    let write_headers = file.metadata()?.len() == 0;
-    let mut wtr = csv::WriterBuilder::new()
-        .has_headers(write_headers)
-        .from_writer(file);
-    wtr.serialize(record)?;
-    wtr.flush()?;
+/// Prints the money earned in `days` per project and, if grouped, per
+/// period, and in total.
+fn print_earnings(
+    days: &[report::Day],
+    group_by: Option<report::GroupBy>,
+    config: &config::Config,
+) -> Result<(), Box<dyn Error>> {
+    let rates = earnings::Rates::parse(&config.rates)?;
+    if rates.is_empty() {
+        Err("no rates are set, see 'azk config set rates'")?
+    }
+    let money = |cents| earnings::format_money(cents, &config.currency);
+    println!("Earnings");
+    let projects =
+        earnings::project_earnings(days.iter().filter_map(|day| day.info.as_ref()), &rates);
+    for (project, (seconds, earned)) in &projects {
+        let project = project.as_deref().unwrap_or("(no project)");
+        match earned {
+            Some(earned) => println!("  {project:<14}{}  {}", hours(*seconds), money(*earned)),
+            None => println!("  {project:<14}{}  no rate", hours(*seconds)),
+        }
+    }
+    if let Some(group_by) = group_by {
+        for (label, earned) in earnings::period_earnings(days, group_by, &rates) {
+            println!("  {label:<14}{}", money(earned));
+        }
+    }
+    let total: i64 = projects.values().filter_map(|(_, earned)| *earned).sum();
+    println!("Earned          {}", money(total));
     Ok(())
 }
 
-fn read_work_time(file: &File) -> Result<DayInfo, Box<dyn Error>> {
-    let mut rdr = csv::Reader::from_reader(file);
-    let records = rdr.deserialize::<Record>();
+/// Prints what the days were found to break, and returns whether anything
+/// was worse than a warning.
+fn print_findings(findings: &[compliance::Finding]) -> bool {
+    for finding in findings {
+        println!(
+            "{}: {}: {}",
+            finding.severity, finding.date, finding.message
+        );
+    }
+    findings
+        .iter()
+        .any(|finding| finding.severity > compliance::Severity::Warning)
+}
+
+/// Prints `periods` of daily durations side by side under their `labels`,
+/// each after the first with its change from the one before, per day and in
+/// total. Rows are named by weekday if the periods are `weeks` starting on
+/// `first`, and numbered otherwise.
+fn print_comparison(
+    first: &chrono::NaiveDate,
+    weeks: bool,
+    labels: &[String],
+    periods: &[Vec<Option<isize>>],
+) {
+    let hhmm = |duration: Option<isize>| duration.map_or("--:--".to_owned(), hours);
+    let mut header = format!("{:<8}", "");
+    for (index, label) in labels.iter().enumerate() {
+        header += &format!("{label:>14}");
+        if index > 0 {
+            header += &format!("{:>8}", "");
+        }
+    }
+    println!("{}", header.trim_end());
+
+    let rows = periods.iter().map(Vec::len).max().unwrap_or(0);
+    for row in 0..rows {
+        let mut line = if weeks {
+            format!(
+                "{:<8}",
+                (*first + chrono::Duration::days(row as i64)).format("%a")
+            )
+        } else {
+            format!("{:<8}", format!("Day {}", row + 1))
+        };
+        for (index, period) in periods.iter().enumerate() {
+            let duration = period.get(row).copied().flatten();
+            line += &format!("{:>14}", hhmm(duration));
+            if index > 0 {
+                let before = periods[index - 1].get(row).copied().flatten();
+                let delta = duration.unwrap_or(0) - before.unwrap_or(0);
+                line += &format!("{:>8}", signed_hours(delta));
+            }
+        }
+        println!("{line}");
+    }
+
+    let mut line = format!("{:<8}", "Total");
+    let totals: Vec<isize> = periods
+        .iter()
+        .map(|period| period.iter().flatten().sum())
+        .collect();
+    for (index, total) in totals.iter().enumerate() {
+        line += &format!("{:>14}", hours(*total));
+        if index > 0 {
+            line += &format!("{:>8}", signed_hours(total - totals[index - 1]));
+        }
+    }
+    println!("{line}");
+}
 
-    let (starts, stops): (Vec<Record>, Vec<Record>) = records
-        .map(|x| match x {
-            Ok(record) => record,
-            Err(err) => panic!("{err}"),
-        })
-        .partition(|x| x.kind == "strt");
+/// Prints the days a pull merged, and those that need fixing since.
+fn print_merged(pulled: &sync::Pulled) {
+    if !pulled.merged.is_empty() {
+        let dates: Vec<String> = pulled.merged.iter().map(ToString::to_string).collect();
+        println!(
+            "Merged the stamps of {}, changed on both sides.",
+            dates.join(", ")
+        );
+    }
+    for date in &pulled.conflicts {
+        println!("{date} had the same stamps changed on both sides, both are kept, see 'azk edit {date}'.");
+    }
+    for (date, problem) in &pulled.problems {
+        println!("{date} does not add up after merging, see 'azk edit {date}': {problem}");
+    }
+}
 
-    let subtracting: isize = starts.iter().map(|x| hhmmss_to_s(&x.time)).sum();
-    let adding: isize = stops.iter().map(|x| hhmmss_to_s(&x.time)).sum();
-    let start = starts.get(0).map_or(0, |x| hhmmss_to_s(&x.time));
+/// Prints the vacation days taken in `year` against the `allowance`, unless
+/// there is none.
+fn print_vacation(
+    off: &std::collections::BTreeMap<chrono::NaiveDate, String>,
+    year: i32,
+    allowance: u32,
+) {
+    if allowance == 0 {
+        return;
+    }
+    let taken = off::vacation_taken(off, year);
+    println!(
+        "Vacation {taken} of {allowance} days taken in {year}, {} left",
+        allowance as isize - taken as isize
+    );
+}
 
-    Ok(DayInfo {
-        start,
-        duration: adding - subtracting,
-    })
+/// Notes what was deducted from the work of a day for breaks that were too
+/// short, if anything was.
+fn deducted_note(deducted: isize) -> Option<String> {
+    (deducted > 0).then(|| format!("({} deducted)", hours(deducted)))
 }
 
-fn update_time(file: &File, time: &str) -> Result<(), Box<dyn Error>> {
-    let DayInfo { duration, start: _ } = read_work_time(file)?;
+/// Prints the days of a `week` or `month`: their net work, if any, as time
+/// and as a bar against their target, followed by their notes. Days after
+/// `today` are not held against their target yet.
+fn print_days(period: &period::Period, today: chrono::NaiveDate) {
+    // As wide as the longest day, so the bars line up.
+    let width = hours(24 * 3600).len();
+    for day in &period.days {
+        let target = if day.date > today { 0 } else { day.target };
+        let notes: Vec<String> = day
+            .notes
+            .iter()
+            .filter_map(|note| match note {
+                period::Note::Deducted(deducted) => deducted_note(*deducted),
+                period::Note::Off(kind, Some(credited)) => {
+                    Some(format!("{kind}, {} credited", hours(*credited)))
+                }
+                period::Note::Off(kind, None) => Some(kind.clone()),
+                period::Note::Missing => Some("missing".to_owned()),
+            })
+            .collect();
+        let line = format!(
+            "{}  {:>width$}  {}  {}",
+            day.date.format("%a %Y-%m-%d"),
+            day.net.map_or("--:--".to_owned(), hours),
+            format::bar(day.net.unwrap_or(0), target, period.scale),
+            notes.join("  ")
+        );
+        println!("{}", line.trim_end());
+    }
+}
 
-    let new_kind = if duration < 0 { "stop" } else { "strt" };
+/// Prints the total time spent on breaks, broken down by kind if any was
+/// given, unless there were no breaks.
+fn print_break_totals(totals: &std::collections::BTreeMap<Option<String>, isize>) {
+    if totals.is_empty() {
+        return;
+    }
+    println!("Breaks          {}", hours(totals.values().sum()));
+    if totals.keys().all(Option::is_none) {
+        return;
+    }
+    for (category, total) in totals {
+        let category = category.as_deref().unwrap_or("(other)");
+        println!("  {category:<14}{}", hours(*total));
+    }
+}
 
-    let record = Record {
-        time: time.to_owned(),
-        kind: new_kind.to_owned(),
+/// Prints the result of a query as columns padded to their widest value.
+fn print_table(table: &query::Table) {
+    let mut widths: Vec<usize> = table.columns.iter().map(|c| c.chars().count()).collect();
+    for row in &table.rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+    let line = |values: &[String]| {
+        let cells: Vec<String> = values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{value:<width$}"))
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
     };
-
-    write_record(file, record)?;
-    Ok(())
+    line(&table.columns);
+    for row in &table.rows {
+        line(row);
+    }
 }
 
-fn cli(file_path: &str) -> Command {
+fn cli() -> Command {
     Command::new("azk")
         .about("A work time tracker")
         .subcommand_required(true)
         .arg_required_else_help(true)
-        .subcommand(Command::new("stamp").about(format!(
-            "Record a timestamp in {file_path} and toggle between work and break",
-        )))
+        .allow_external_subcommands(true)
+        .arg(arg!(--decimal "Print durations in decimal hours, e.g. 7.75 h, see the decimal_hours setting").global(true))
+        .arg(arg!(--"data-dir" <DIR> "Keep the records in DIR instead of the default, also set by $AZK_DATA_DIR").global(true))
+        .arg(arg!(--profile <NAME> "Use the config and records of the profile NAME, also set by $AZK_PROFILE or the profile setting").global(true))
+        .subcommand(
+            Command::new("stamp")
+                .about("Record a timestamp for today and toggle between work and break")
+                .arg(arg!(--at <TIME> "Record TIME instead of the current time, e.g. 17:45, 5pm or '10 min ago'"))
+                .arg(arg!(--date <DAY> "Record into the file of DAY instead of today, e.g. 2024-03-01, yesterday or -2").allow_negative_numbers(true))
+                .arg(arg!(--"auto-close" <TIME> "Stop sessions left open on previous days at TIME, in HH:MM"))
+                .arg(arg!(-p --project <PROJECT> "The project the work is for"))
+                .arg(arg!(-m --message <NOTE> "A note to keep with the stamp"))
+                .arg(arg!(--force "Stamp even right after the last stamp, see the min_gap setting")),
+        )
+        .subcommand(
+            Command::new("start")
+                .about("Record the start of work, unlike stamp never a break")
+                .arg(arg!(project: [PROJECT] "The project the work is for"))
+                .arg(arg!(-m --message <NOTE> "A note to keep with the stamp"))
+                .arg(arg!(--at <TIME> "Record TIME instead of the current time, e.g. 17:45, 5pm or '10 min ago'"))
+                .arg(arg!(--idempotent "Do nothing if work has already started")),
+        )
+        .subcommand(
+            Command::new("stop")
+                .about("Record the start of a break, unlike stamp never work")
+                .arg(arg!(--at <TIME> "Record TIME instead of the current time, e.g. 17:45, 5pm or '10 min ago'"))
+                .arg(arg!(-m --message <NOTE> "A note to keep with the stamp"))
+                .arg(arg!(--idempotent "Do nothing if already on a break")),
+        )
+        .subcommand(
+            Command::new("break")
+                .about("Record the start of a break of a kind, e.g. lunch or coffee")
+                .arg(arg!(<KIND> "The kind of break"))
+                .arg(arg!(-m --message <NOTE> "A note to keep with the stamp"))
+                .arg(arg!(--at <TIME> "Record TIME instead of the current time, e.g. 17:45, 5pm or '10 min ago'"))
+                .arg(arg!(--idempotent "Do nothing if already on a break")),
+        )
+        .subcommand(
+            Command::new("add")
+                .about("Record work from FROM to TO, e.g. tracked on paper")
+                .arg(arg!(<FROM> "The start of work, e.g. 09:00 or 9am"))
+                .arg(arg!(<TO> "The end of work, e.g. 12:30 or noon"))
+                .arg(arg!(--date <DAY> "Record into DAY instead of today, e.g. 2024-03-01, yesterday or -2").allow_negative_numbers(true))
+                .arg(arg!(-p --project <PROJECT> "The project the work is for"))
+                .arg(arg!(-m --message <NOTE> "A note to keep with the start")),
+        )
         .subcommand(
             Command::new("get")
                 .about("Get the work duration for the current day or [DAY]")
-                .arg(arg!(day: [DAY] "The day to get the work duration for, in YYYY-MM-DD")),
+                .arg(arg!(day: [DAY] "The day to get the work duration for, e.g. 2024-03-01, yesterday or -2").allow_negative_numbers(true))
+                .arg(arg!(-d --detailed "List every work interval and the breaks in between"))
+                .arg(arg!(-f --format <TEMPLATE> "Print TEMPLATE instead, e.g. '{duration_hm} since {start}'"))
+                .arg(arg!(--"auto-close" <TIME> "Stop sessions left open on previous days at TIME, in HH:MM")),
+        )
+        .subcommand(
+            Command::new("notes")
+                .about("List the notes of the current day or [DAY]")
+                .arg(arg!(day: [DAY] "The day to list the notes of, e.g. 2024-03-01, yesterday or -2").allow_negative_numbers(true)),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List the stamps of the current day or [DAY] as they are stored")
+                .arg(arg!(day: [DAY] "The day to list, e.g. 2024-03-01, yesterday or -2").allow_negative_numbers(true)),
+        )
+        .subcommand(
+            Command::new("week")
+                .about("Get the work durations for the current week or [WEEK]")
+                .arg(arg!(week: [WEEK] "The week to report on, in YYYY-Www")),
+        )
+        .subcommand(
+            Command::new("month")
+                .about("Get the work durations for the current month or [MONTH]")
+                .arg(arg!(month: [MONTH] "The month to report on, in YYYY-MM")),
+        )
+        .subcommand(
+            Command::new("compare")
+                .about("Compare the last weeks, or two ranges of days, side by side")
+                .arg(arg!(--weeks <N> "How many weeks up to the current one to compare").value_parser(clap::value_parser!(u32).range(2..=8)).default_value("2").conflicts_with("ranges"))
+                .arg(arg!(ranges: [RANGE] "Two ranges to compare day by day, e.g. 2024-03-04..2024-03-08 2024-03-11..2024-03-15").num_args(2).allow_negative_numbers(true)),
+        )
+        .subcommand(
+            Command::new("trends")
+                .about("Get the 7-day and 30-day rolling averages of the daily work as sparklines")
+                .arg(arg!(--days <N> "How many days up to today to show").value_parser(clap::value_parser!(u32).range(1..=366)).default_value("90"))
+                .arg(arg!(--series "Print the averages of every day instead")),
         )
+        .subcommand(
+            Command::new("stats")
+                .about("Get figures about the tracked days of [RANGE] or of all days")
+                .arg(arg!(range: [RANGE] "The days to look at, e.g. 2024-01-01..2024-06-30 or -30..today").allow_negative_numbers(true)),
+        )
+        .subcommand(
+            Command::new("query")
+                .about("Answer a question in SQL, over the tables intervals, breaks and days of all records")
+                .long_about("Answer a question in SQL, over the tables of all records:\n\n  \
+                    intervals(date, start, end, seconds, project, note, ongoing)\n  \
+                    breaks(date, start, end, seconds, category)\n  \
+                    days(date, start, end, breaks, gross, seconds)\n\n\
+                    Dates are YYYY-MM-DD, and the starts and ends of intervals and breaks YYYY-MM-DD HH:MM:SS. \
+                    The seconds of a day are its work after the deduction for short breaks.")
+                .arg(arg!(<SQL> "The query, e.g. \"SELECT project, sum(seconds) FROM intervals WHERE date >= '2024-01-01' GROUP BY 1\"")),
+        )
+        .subcommand(
+            Command::new("heatmap")
+                .about("Draw a calendar of the current year or [YEAR] shaded by the work of each day")
+                .arg(arg!(year: [YEAR] "The year to draw, e.g. 2024").value_parser(clap::value_parser!(i32).range(1..=9999))),
+        )
+        .subcommand(
+            Command::new("timesheet")
+                .about("Write a printable timesheet of the current month or [MONTH] as a PDF")
+                .arg(arg!(month: [MONTH] "The month of the timesheet, in YYYY-MM"))
+                .arg(arg!(-o --output <FILE> "Where to write the PDF, defaults to timesheet-YYYY-MM.pdf")),
+        )
+        .subcommand(
+            Command::new("report")
+                .about("Get the total work duration between two days")
+                .arg(arg!(--from <DAY> "The first day of the range, e.g. 2024-03-01, yesterday or -2").allow_negative_numbers(true).required(true))
+                .arg(arg!(--to <DAY> "The last day of the range, e.g. 2024-03-01 or yesterday, defaults to today").allow_negative_numbers(true))
+                .arg(
+                    arg!(--"group-by" <GROUP> "Break the total down per day, week or month")
+                        .value_parser(["day", "week", "month"]),
+                )
+                .arg(arg!(--strict "Exit with 2 if a working time limit was violated"))
+                .arg(arg!(--round <POLICY> "Round the durations as in the rounding setting, e.g. up:15, or not at all with none"))
+                .arg(arg!(--format <FORMAT> "Print a document with a table per week, the totals and the projects instead").value_parser(document::FORMATS))
+                .arg(arg!(--earnings "Show the money earned per project, and per day, week or month with --group-by, at the configured rates")),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Print the work intervals between two days in the format of another tool")
+                .arg(arg!(<FORMAT> "The format to print").value_parser(export::FORMATS))
+                .arg(arg!(--"issue-from-note" "Take the Jira issue key from the note, e.g. 'AZK-42: review', rather than the project"))
+                .arg(arg!(--push "Instead of printing, comment the /spend quick actions on the GitLab issues, with the token from the keyring entry 'gitlab' or $GITLAB_TOKEN, or write the points to influx_url"))
+                .arg(arg!(--from <DAY> "The first day of the range, defaults to the first recorded day").allow_negative_numbers(true))
+                .arg(arg!(--to <DAY> "The last day of the range, defaults to today").allow_negative_numbers(true))
+                .arg(arg!(--"group-by" <GROUP> "Sum the csv format per day and project, or per project").value_parser(["day", "project"]))
+                .arg(arg!(--month <MONTH> "Export the month MONTH, in YYYY-MM, rather than a range").conflicts_with_all(["from", "to"]))
+                .arg(arg!(-o --output <FILE> "Where to write the xlsx or parquet format, defaults to e.g. azk-MONTH.xlsx or azk-FROM-TO.parquet"))
+                .arg(arg!(--round <POLICY> "Round the intervals as in the rounding setting, e.g. up:15, or not at all with none")),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Record the work in a file written by another tool")
+                .arg(arg!(<FORMAT> "The format of the file").value_parser(import::FORMATS))
+                .arg(arg!(<FILE> "The file to import"))
+                .arg(arg!(--as <FIELD> "Set a field of every interval, e.g. project=meetings").action(clap::ArgAction::Append))
+                .arg(arg!(--from <DAY> "Leave out what is before DAY").allow_negative_numbers(true))
+                .arg(arg!(--to <DAY> "Leave out what is after DAY").allow_negative_numbers(true)),
+        )
+        .subcommand(
+            Command::new("edit")
+                .about("Edit the stamps of the current day or [DAY]")
+                .arg(arg!(day: [DAY] "The day to edit, e.g. 2024-03-01, yesterday or -2").allow_negative_numbers(true)),
+        )
+        .subcommand(
+            Command::new("undo")
+                .about("Remove the last stamp of today")
+                .arg(arg!(-y --yes "Don't ask for confirmation")),
+        )
+        .subcommand(
+            Command::new("delete")
+                .about("Remove the INDEXth stamp of today or [DAY], as numbered by list")
+                .arg_required_else_help(true)
+                .arg(arg!(day: [DAY] "The day to remove the stamp from, e.g. 2024-03-01, yesterday or -2").allow_negative_numbers(true))
+                .arg(arg!(index: [INDEX] "The number of the stamp, as shown by list"))
+                .arg(arg!(-y --yes "Don't ask for confirmation")),
+        )
+        .subcommand(
+            Command::new("amend")
+                .about("Change the time of the last stamp of today")
+                .arg(arg!(<TIME> "The corrected time, e.g. 17:45 or '15 min ago'"))
+                .arg(arg!(--date <DAY> "Amend the last stamp of DAY instead, e.g. 2024-03-01, yesterday or -2").allow_negative_numbers(true)),
+        )
+        .subcommand(
+            Command::new("off")
+                .about("Record days off work, or list those of this year")
+                .arg(arg!(kind: [KIND] "The kind of day off").value_parser(off::KINDS).requires("days"))
+                .arg(arg!(days: [DAYS] "The day or days off, e.g. 2024-07-01..2024-07-12 or yesterday").allow_negative_numbers(true))
+                .arg(arg!(--remove <DAYS> "Remove DAYS from the days off instead").allow_negative_numbers(true)),
+        )
+        .subcommand(
+            Command::new("project")
+                .about("Manage the known projects and the clients they are for")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Add a project, or move it to another client and restore it")
+                        .arg(arg!(<NAME> "The name of the project"))
+                        .arg(arg!(--client <CLIENT> "The client the project is for"))
+                        .arg(arg!(--budget <HOURS> "The hours budgeted for the project, in HH:MM, e.g. 120:00")),
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("List the projects by client")
+                        .arg(arg!(--all "Include archived projects"))
+                        .arg(arg!(--names "Print only the names of the projects in use, e.g. for shell completions")),
+                )
+                .subcommand(
+                    Command::new("archive")
+                        .about("Archive a project, so it is no longer listed")
+                        .arg(arg!(<NAME> "The name of the project")),
+                ),
+        )
+        .subcommand(
+            Command::new("holidays")
+                .about("List the public holidays of this year or [YEAR], see the holidays setting")
+                .arg(arg!(year: [YEAR] "The year to list").value_parser(clap::value_parser!(i32)))
+                .arg(arg!(--import <ICS> "Add the events of an ICS calendar as holidays")),
+        )
+        .subcommand(
+            Command::new("balance")
+                .about("Get the overtime balance of all recorded days against their target")
+                .arg(arg!(--"reset-monthly" "Start every month with a balance of zero")),
+        )
+        .subcommand(
+            Command::new("watch")
+                .about("Show the current session and today's total, updated every second"),
+        )
+        .subcommand(
+            Command::new("daemon")
+                .about("Stop work after some idle minutes, when the session is locked or the system suspends, and start it again on activity")
+                .arg(arg!(--idle <MINUTES> "Minutes without input before work is stopped, defaults to idle_minutes").value_parser(clap::value_parser!(u32))),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Serve a dashboard and answer HTTP requests to GET /status, POST /stamp and GET /report?from=DAY&to=DAY, with the token from the keyring or $AZK_SERVE_TOKEN")
+                .arg(arg!(--listen <ADDRESS> "The address to listen on").default_value("127.0.0.1:7878")),
+        )
+        .subcommand(
+            Command::new("tui").about("Open an interactive dashboard to review and stamp work"),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Show or change the configuration")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("Show all settings, including defaults"))
+                .subcommand(
+                    Command::new("get")
+                        .about("Show the value of a setting")
+                        .arg(arg!(<KEY> "The setting to show")),
+                )
+                .subcommand(
+                    Command::new("set")
+                        .about("Change the value of a setting")
+                        .arg(arg!(<KEY> "The setting to change"))
+                        .arg(arg!(<VALUE> "The new value")),
+                ),
+        )
+        .subcommand(
+            Command::new("secret")
+                .about("Manage the tokens and passwords kept in the system keyring, on Linux and macOS only")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("Show the entries azk reads and which are set"))
+                .subcommand(
+                    Command::new("set")
+                        .about("Keep a secret, asked for or read from stdin")
+                        .arg(arg!(<NAME> "The entry, see 'azk secret list'")),
+                )
+                .subcommand(
+                    Command::new("get")
+                        .about("Print a secret")
+                        .arg(arg!(<NAME> "The entry")),
+                )
+                .subcommand(
+                    Command::new("rm")
+                        .about("Remove a secret")
+                        .arg(arg!(<NAME> "The entry")),
+                ),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check all recorded days for problems")
+                .arg(arg!(--fix "Remove duplicate stamps and empty days")),
+        )
+        .subcommand(
+            Command::new("migrate")
+                .about("Upgrade the records stored by older versions to the current schema"),
+        )
+        .subcommand(
+            Command::new("backup")
+                .about("Back up the records and the config, or restore them")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("create")
+                        .about("Write a backup to a timestamped .tar.zst in the current directory or [PATH]")
+                        .arg(arg!([PATH] "The directory to write the backup to, or the file")),
+                )
+                .subcommand(
+                    Command::new("restore")
+                        .about("Replace the records and the config with those of a backup, saving the current ones next to it first")
+                        .arg(arg!(<FILE> "The backup to restore"))
+                        .arg(arg!(--force "Restore even if days recorded now are not in the backup")),
+                ),
+        )
+        .subcommand(
+            Command::new("sync")
+                .about("Pull the records other machines pushed to sync_remote, then push those changed here")
+                .after_help("Days changed on both sides are merged stamp by stamp against how they were when last in step, so that the stamps added, changed or deleted on either side stay so. Where both sides changed the same stamps, what either made of them is kept and the day reported.")
+                .subcommand(Command::new("push").about("Send the records changed here to sync_remote"))
+                .subcommand(Command::new("pull").about("Fetch the records changed on sync_remote"))
+                .subcommand(
+                    Command::new("status")
+                        .about("Show the files changed here and on sync_remote since the last sync"),
+                ),
+        )
+        .subcommand(
+            Command::new("archive")
+                .about("Roll the days of each month before MONTH into a compressed archive, which is still read as before")
+                .arg(arg!(--before <MONTH> "The first month left as it is, as in 2024-01").required(true)),
+        )
+        .subcommand(
+            Command::new("purge")
+                .about("Remove old days, archiving them and keeping their monthly totals first as set by the retention keys")
+                .arg(arg!(--"older-than" <AGE> "Remove the days older than AGE, as in 2y, 18m, 6w or 90d, rather than retention"))
+                .arg(arg!(--archive <DIR> "Archive the days to a tar.zst in DIR first, rather than to retention_archive"))
+                .arg(arg!(--delete "Do not archive the days, even with retention_archive set").conflicts_with("archive"))
+                .arg(arg!(--totals "Add the monthly totals of the days to purged-totals.csv in the data dir first, even with retention_totals off"))
+                .arg(arg!(-y --yes "Do not ask for confirmation")),
+        )
+        .subcommand(
+            Command::new("audit")
+                .about("Check the log of changes kept with 'audit' on")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("verify")
+                        .about("Check that the log was not changed and that the records are as it says"),
+                ),
+        )
+        .subcommand(
+            Command::new("encrypt")
+                .about("Encrypt the days with a passphrase, taken from the keyring entry 'encryption' or $AZK_PASSPHRASE, or asked for"),
+        )
+        .subcommand(
+            Command::new("decrypt").about("Store the days unencrypted again"),
+        )
+        .subcommand(
+            Command::new("convert")
+                .about("Copy all records to another store and switch to it")
+                .arg(
+                    arg!(<BACKEND> "The store to copy to")
+                        .value_parser(store::BACKENDS),
+                ),
+        )
+        .subcommand(
+            Command::new("init")
+                .about("Set up azk by answering a few questions, and install the shell completions"),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print the completions for SHELL, to be loaded by it")
+                .arg(arg!(<SHELL> "The shell").value_parser(clap::value_parser!(clap_complete::Shell))),
+        )
+        .subcommand(
+            Command::new("status")
+                .about("Show whether you are working or on a break, and the total for today")
+                .arg(arg!(-f --format <TEMPLATE> "Print TEMPLATE instead, e.g. '{state} {session_hm}'"))
+                .arg(arg!(-q --quiet "Print nothing, exit with 0 when working and 1 otherwise"))
+                .arg(arg!(--strict "Exit with 2 if a working time limit was violated today")),
+        )
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {err}");
+        std::process::exit(1);
+    }
+}
+
+/// Runs `azk config`.
+fn config_command(matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    match matches.subcommand() {
+        Some(("list", _)) => {
+            for (key, value) in config::list()? {
+                println!("{key} = {value}");
+            }
+        }
+        Some(("get", sub_matches)) => {
+            let key = sub_matches.get_one::<String>("KEY").unwrap();
+            match config::get(key)? {
+                toml::Value::String(value) => println!("{value}"),
+                value => println!("{value}"),
+            }
+        }
+        Some(("set", sub_matches)) => {
+            let key = sub_matches.get_one::<String>("KEY").unwrap();
+            let value = sub_matches.get_one::<String>("VALUE").unwrap();
+            config::set(key, value)?;
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
 }
 
-fn file_path(date: &str) -> Result<PathBuf, Box<dyn Error>> {
-    if let Some(proj_dirs) = directories::ProjectDirs::from("com", "hylo", "azk") {
-        let mut file_path = proj_dirs.data_dir().to_path_buf();
-        fs::create_dir_all(&file_path)?;
-        file_path.push(date);
-        file_path.set_extension("csv");
-        return Ok(file_path);
+/// Runs `azk secret`.
+fn secret_command(matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    match matches.subcommand() {
+        Some(("list", _)) => {
+            for (name, description) in secret::NAMES {
+                let set = if secret::get(name)?.is_some() {
+                    "set"
+                } else {
+                    "-"
+                };
+                println!("{name:<12}{set:<5}{description}");
+            }
+        }
+        Some(("set", sub_matches)) => {
+            let name = sub_matches.get_one::<String>("NAME").unwrap();
+            let value = if io::stdin().is_terminal() {
+                rpassword::prompt_password(format!("{name}: "))?
+            } else {
+                let mut line = String::new();
+                io::stdin().read_line(&mut line)?;
+                line.trim_end_matches(['\r', '\n']).to_owned()
+            };
+            if value.is_empty() {
+                Err("the secret is empty")?
+            }
+            secret::set(name, &value)?;
+        }
+        Some(("get", sub_matches)) => {
+            let name = sub_matches.get_one::<String>("NAME").unwrap();
+            let value = secret::get(name)?
+                .ok_or_else(|| format!("there is no '{name}' secret in the keyring"))?;
+            println!("{value}");
+        }
+        Some(("rm", sub_matches)) => {
+            let name = sub_matches.get_one::<String>("NAME").unwrap();
+            if !secret::remove(name)? {
+                Err(format!("there is no '{name}' secret in the keyring"))?
+            }
+        }
+        _ => unreachable!(),
     }
-    Err("path error")?
+    Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn run() -> Result<(), Box<dyn Error>> {
+    let matches = cli().get_matches();
+    // Through the environment, these also hold for hooks and other commands
+    // azk runs.
+    if let Some(dir) = matches.get_one::<String>("data-dir") {
+        std::env::set_var(store::DATA_DIR_VAR, std::path::absolute(dir)?);
+    }
+    if let Some(profile) = matches.get_one::<String>("profile") {
+        config::check_profile(profile)?;
+        std::env::set_var(config::PROFILE_VAR, profile);
+    }
     let now = chrono::Local::now();
     let date: String = format!("{}", now.format("%Y-%m-%d"));
     let time: String = format!("{}", now.format("%H:%M:%S"));
-    let file_path_today = file_path(&date)?;
-    let cli = cli(file_path_today.to_str().unwrap());
-
-    match cli.get_matches().subcommand() {
-        Some(("stamp", _)) => {
-            let file = File::options()
-                .write(true)
-                .read(true)
-                .create(true)
-                .append(true)
-                .open(&file_path_today)?;
-            update_time(&file, &time)?;
+    let today = now.date_naive();
+    // A broken config is reported by the commands that need it.
+    let decimal =
+        matches.get_flag("decimal") || config::load().is_ok_and(|config| config.decimal_hours);
+    DECIMAL.store(decimal, Ordering::Relaxed);
+
+    // These do not touch the records, so they work before the store, which
+    // needs the config and the user script, can be opened, and can fix them.
+    match matches.subcommand() {
+        Some(("config", sub_matches)) => return config_command(sub_matches),
+        Some(("secret", sub_matches)) => return secret_command(sub_matches),
+        Some(("init", _)) => return init::init(cli()),
+        Some(("completions", sub_matches)) => {
+            let shell = *sub_matches
+                .get_one::<clap_complete::Shell>("SHELL")
+                .unwrap();
+            io::stdout().write_all(&init::completions(shell, cli()))?;
+            return Ok(());
+        }
+        _ => {}
+    }
+    let store = store::open()?;
+    let store = store.as_ref();
+
+    match matches.subcommand() {
+        Some(("stamp", sub_matches)) => {
+            resolve_dangling(store, today, sub_matches.get_one("auto-close"))?;
+            let time = match sub_matches.get_one::<String>("at") {
+                Some(at) => input::parse_time(at, hhmmss_to_s(&time))?,
+                None => time,
+            };
+            let date = match sub_matches.get_one::<String>("date") {
+                Some(day) => input::parse_date(day, today)?,
+                None => today,
+            };
+            if !sub_matches.get_flag("force") && !confirm_gap(store, date, &time)? {
+                std::process::exit(1);
+            }
+            if let Some(record) = update_time(
+                store,
+                date,
+                &time,
+                None,
+                sub_matches.get_one("project"),
+                sub_matches.get_one("message"),
+            )? {
+                run_hooks(store, date, &record);
+            }
+            println!("Updated {} with {time}.", store.location(date))
+        }
+        Some((command @ ("start" | "stop" | "break"), sub_matches)) => {
+            let time = match sub_matches.get_one::<String>("at") {
+                Some(at) => input::parse_time(at, hhmmss_to_s(&time))?,
+                None => time,
+            };
+            let (kind, project) = match command {
+                "start" => ("strt".to_owned(), sub_matches.get_one("project")),
+                "stop" => ("stop".to_owned(), None),
+                _ => {
+                    let category = sub_matches.get_one::<String>("KIND").unwrap().trim();
+                    if category.is_empty() {
+                        Err("the kind of break must not be empty")?
+                    }
+                    (format!("stop:{category}"), None)
+                }
+            };
+            let note = sub_matches.get_one("message");
+            if let Some(record) = update_time(store, today, &time, Some(&kind), project, note)? {
+                run_hooks(store, today, &record);
+                println!("Updated {} with {time}.", store.location(today))
+            } else {
+                let state = if command == "start" {
+                    "Already working."
+                } else {
+                    "Already on a break."
+                };
+                if sub_matches.get_flag("idempotent") {
+                    println!("{state}");
+                } else {
+                    eprintln!("{state}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(("add", sub_matches)) => {
+            let from = input::parse_time(
+                sub_matches.get_one::<String>("FROM").unwrap(),
+                hhmmss_to_s(&time),
+            )?;
+            let to = input::parse_time(
+                sub_matches.get_one::<String>("TO").unwrap(),
+                hhmmss_to_s(&time),
+            )?;
+            let date = match sub_matches.get_one::<String>("date") {
+                Some(day) => input::parse_date(day, today)?,
+                None => today,
+            };
+            store::add_interval(
+                store,
+                date,
+                &from,
+                &to,
+                sub_matches.get_one("project"),
+                sub_matches.get_one("message"),
+            )?;
             println!(
-                "Updated {file_path} with {time}.",
-                file_path = file_path_today.display()
-            )
+                "Added {} - {} to {}.",
+                &from[..5],
+                &to[..5],
+                store.location(date)
+            );
         }
         Some(("get", sub_matches)) => {
-            let date_iso8601 = sub_matches.get_one::<String>("day").unwrap_or(&date);
-            let file_path = file_path(date_iso8601)?;
+            resolve_dangling(store, today, sub_matches.get_one("auto-close"))?;
+            let day_date = match sub_matches.get_one::<String>("day") {
+                Some(day) => input::parse_date(day, today)?,
+                None => today,
+            };
+            let date_iso8601 = format!("{day_date}");
 
-            if let Ok(file) = File::open(file_path) {
-                let DayInfo { start, duration } = read_work_time(&file)?;
+            if let Some(day) = read_work_time(store, day_date, Some(hhmmss_to_s(&time)))? {
+                let ongoing = day.ongoing();
 
-                if duration < 0 {
-                    eprintln!("Work ain't over yet.");
+                if ongoing && day_date != today {
+                    eprintln!("Work on {date_iso8601} was never stopped.");
                     std::process::exit(1);
+                }
+
+                let config = config::load()?;
+                let target = config::schedule(&config)?.target(day_date);
+                if let Some(template) = sub_matches.get_one("format").or(config.format.as_ref()) {
+                    let vars = format::day_vars(&date_iso8601, &day, hhmmss_to_s(&time), target);
+                    println!("{}", format::render(template, &vars)?);
+                    return Ok(());
+                }
+
+                let deducted =
+                    report::deduction(&day, &report::parse_break_rules(&config.break_rules)?);
+                let duration = day.duration() - deducted;
+                let duration_hhmm = hours(duration);
+                let from_hhmm = s_to_hhmm(day.start());
+                let to_hhmm = s_to_hhmm(day.end());
+                if ongoing {
+                    println!("Worked for {duration_hhmm} on {date_iso8601} so far.\nFrom {from_hhmm} until now ({to_hhmm})")
                 } else {
-                    let duration_hhmm = s_to_hhmm(duration);
-                    let from_hhmm = s_to_hhmm(start);
-                    let to_hhmm = s_to_hhmm(start + duration);
                     println!("Worked for {duration_hhmm} on {date_iso8601}.\nFrom {from_hhmm} to {to_hhmm}")
                 }
+                if deducted > 0 {
+                    println!(
+                        "{} worked, {} deducted for breaks that were too short.",
+                        hours(day.duration()),
+                        hours(deducted)
+                    );
+                }
+
+                if day_date == today {
+                    let now_s = hhmmss_to_s(&time);
+                    println!("{}", remaining_message(duration, ongoing, now_s, target));
+                } else {
+                    println!(
+                        "{} against the daily target.",
+                        signed_hours(duration - target)
+                    );
+                }
+                print_project_totals(&report::project_totals([&day]))?;
+                print_break_totals(&report::break_totals([&day]));
+
+                if sub_matches.get_flag("detailed") {
+                    for (index, interval) in day.intervals.iter().enumerate() {
+                        if let Some(b) = index.checked_sub(1).map(|index| &day.breaks[index]) {
+                            println!(
+                                "  {:<15}{}",
+                                b.category.as_deref().unwrap_or("break"),
+                                hours(b.duration())
+                            );
+                        }
+                        let to_hhmm = if interval.ongoing {
+                            "now  ".to_owned()
+                        } else {
+                            s_to_hhmm(interval.end)
+                        };
+                        println!(
+                            "  {} - {to_hhmm}  {}  {}",
+                            s_to_hhmm(interval.start),
+                            hours(interval.duration()),
+                            interval.project.as_deref().unwrap_or("")
+                        );
+                    }
+                }
+            } else if day_date == today {
+                eprintln!("Work hasn't started yet.");
+                std::process::exit(1);
             } else {
+                eprintln!(
+                    "No records for {date_iso8601} in {}.",
+                    store.location(day_date)
+                );
+                std::process::exit(1);
+            }
+        }
+        Some(("notes", sub_matches)) => {
+            let date = match sub_matches.get_one::<String>("day") {
+                Some(day) => input::parse_date(day, today)?,
+                None => today,
+            };
+            let Some(records) = store.read_day(date)? else {
+                eprintln!("No records for {date}.");
+                std::process::exit(1);
+            };
+
+            for record in records {
+                if let Some(note) = &record.note {
+                    println!("{}  {}  {note}", &record.time()[..5], record.kind);
+                }
+            }
+        }
+        Some(("list", sub_matches)) => {
+            let date = match sub_matches.get_one::<String>("day") {
+                Some(day) => input::parse_date(day, today)?,
+                None => today,
+            };
+            let Some(records) = store.read_day(date)? else {
+                eprintln!("No records for {date} in {}.", store.location(date));
+                std::process::exit(1);
+            };
+
+            let kind_width = records
+                .iter()
+                .map(|record| record.kind.len())
+                .fold(4, usize::max);
+            println!(
+                "{:>3}  {:<kind_width$}  time      {:<14}note",
+                "#", "kind", "project"
+            );
+            for (index, record) in records.iter().enumerate() {
+                let line = format!(
+                    "{:>3}  {:<kind_width$}  {}  {:<14}{}",
+                    index + 1,
+                    record.kind,
+                    record.time(),
+                    record.project.as_deref().unwrap_or(""),
+                    record.note.as_deref().unwrap_or("")
+                );
+                println!("{}", line.trim_end());
+            }
+        }
+        Some(("week", sub_matches)) => {
+            let monday = match sub_matches.get_one::<String>("week") {
+                Some(week) => parse_iso_week(week)?,
+                None => today.week(chrono::Weekday::Mon).first_day(),
+            };
+            let config = config::load()?;
+            let target = config::weekly_target(&config)?;
+            let week = period::week(store, &config, monday, today, hhmmss_to_s(&time))?;
+
+            let iso_week = monday.iso_week();
+            println!("Week {}-W{:02}", iso_week.year(), iso_week.week());
+            print_days(&week, today);
+            println!(
+                "Total           {}\nTarget          {} ({})",
+                hours(week.total),
+                hours(target),
+                signed_hours(week.total - target)
+            );
+        }
+        Some(("month", sub_matches)) => {
+            let first = match sub_matches.get_one::<String>("month") {
+                Some(month) => parse_month(month)?,
+                None => today.with_day(1).unwrap(),
+            };
+            let config = config::load()?;
+            let month = period::month(store, &config, first, today, hhmmss_to_s(&time))?;
+
+            println!("Month {}", first.format("%Y-%m"));
+            print_days(&month, today);
+            println!(
+                "Total           {}\nAverage         {} over {} days",
+                hours(month.total),
+                hours(month.average()),
+                month.tracked
+            );
+        }
+        Some(("compare", sub_matches)) => {
+            let weeks = sub_matches.get_many::<String>("ranges").is_none();
+            let ranges: Vec<(chrono::NaiveDate, chrono::NaiveDate)> = match sub_matches
+                .get_many::<String>("ranges")
+            {
+                Some(ranges) => ranges
+                    .map(|range| input::parse_date_range(range, today))
+                    .collect::<Result<_, _>>()?,
+                None => period::last_weeks(today, *sub_matches.get_one::<u32>("weeks").unwrap()),
+            };
+            let rules = report::parse_break_rules(&config::load()?.break_rules)?;
+            let periods = period::compare(store, &ranges, today, hhmmss_to_s(&time), &rules)?;
+            let labels: Vec<String> = ranges
+                .iter()
+                .map(|range| period::range_label(*range, weeks))
+                .collect();
+            print_comparison(&ranges[0].0, weeks, &labels, &periods);
+        }
+        Some(("trends", sub_matches)) => {
+            let shown = *sub_matches.get_one::<u32>("days").unwrap() as usize;
+            let config = config::load()?;
+            let trends = period::trends(store, &config, shown, today, hhmmss_to_s(&time))?;
+
+            if sub_matches.get_flag("series") {
+                let hhmm = |average: Option<isize>| average.map_or("--:--".to_owned(), hours);
+                for (index, date) in trends.dates.iter().enumerate() {
+                    println!(
+                        "{}  {}  {}  {}",
+                        date.format("%a %Y-%m-%d"),
+                        hhmm(trends.durations[index]),
+                        hhmm(trends.week[index]),
+                        hhmm(trends.month[index])
+                    );
+                }
+                return Ok(());
+            }
+            println!("From {} to {today}", trends.dates[0]);
+            for (name, averages) in [("7 days", &trends.week), ("30 days", &trends.month)] {
+                let low = averages.iter().flatten().min().copied().unwrap_or(0);
+                let high = averages.iter().flatten().max().copied().unwrap_or(0);
+                let last = averages.last().copied().flatten().unwrap_or(0);
+                println!(
+                    "{name:<8}{}  {}, from {} to {}",
+                    format::sparkline(averages),
+                    hours(last),
+                    hours(low),
+                    hours(high)
+                );
+            }
+        }
+        Some(("query", sub_matches)) => {
+            let listed = store.list_days()?;
+            let from = listed.iter().min().copied().unwrap_or(today);
+            let days = report::days(store, from, today, today, hhmmss_to_s(&time))?;
+            let rules = report::parse_break_rules(&config::load()?.break_rules)?;
+            let sql = sub_matches.get_one::<String>("SQL").unwrap();
+            print_table(&query::run(&days, &rules, sql)?);
+        }
+        Some(("stats", sub_matches)) => {
+            let (from, to) = match sub_matches.get_one::<String>("range") {
+                Some(range) => input::parse_date_range(range, today)?,
+                None => {
+                    let listed = store.list_days()?;
+                    let purged = purge::read_totals()?;
+                    let first = listed
+                        .iter()
+                        .copied()
+                        .chain(purged.values().map(|total| total.first))
+                        .min()
+                        .unwrap_or(today);
+                    let last = listed.iter().max().copied().unwrap_or(today);
+                    (first, last.max(today))
+                }
+            };
+            let days = report::days(store, from, to, today, hhmmss_to_s(&time))?;
+            let rules = report::parse_break_rules(&config::load()?.break_rules)?;
+            let purged = purge::totals_between(from, to)?;
+            println!("From {from} to {to}");
+            let Some(mut stats) = report::stats(&days, &rules) else {
+                if purged.is_empty() {
+                    println!("No work was tracked.");
+                } else {
+                    let days: usize = purged.iter().map(|total| total.days).sum();
+                    let total: isize = purged.iter().map(|total| total.seconds).sum();
+                    println!(
+                        "Days tracked    {days}, all purged\n\
+                         Total           {}\n\
+                         Average         {}",
+                        hours(total),
+                        hours(total / days as isize)
+                    );
+                }
+                return Ok(());
+            };
+            if !purged.is_empty() {
+                stats.add_purged(&purged);
+                println!(
+                    "Of the days tracked, {} are purged and only count towards the total and the average.",
+                    stats.purged
+                );
+            }
+            println!(
+                "Days tracked    {}\n\
+                 Total           {}\n\
+                 Average         {}\n\
+                 Median          {}\n\
+                 Average start   {}\n\
+                 Average end     {}\n\
+                 Longest day     {} on {}\n\
+                 Longest streak  {} days, until {}\n\
+                 Breaks          {}",
+                stats.days,
+                hours(stats.total),
+                hours(stats.average),
+                hours(stats.median),
+                s_to_hhmm(stats.average_start),
+                s_to_hhmm(stats.average_end),
+                hours(stats.longest.1),
+                stats.longest.0,
+                stats.streak.0,
+                stats.streak.1,
+                hours(stats.breaks)
+            );
+        }
+        Some(("heatmap", sub_matches)) => {
+            let year = sub_matches
+                .get_one::<i32>("year")
+                .copied()
+                .unwrap_or(today.year());
+            let first = chrono::NaiveDate::from_ymd_opt(year, 1, 1).unwrap();
+            let last = chrono::NaiveDate::from_ymd_opt(year, 12, 31).unwrap();
+            let rules = report::parse_break_rules(&config::load()?.break_rules)?;
+            let durations = totals::days(store, first, last, today, hhmmss_to_s(&time), &rules)?
+                .into_iter()
+                .filter_map(|(date, net)| Some((date, net?.net)))
+                .collect();
+            let color = io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+            print!("{}", heatmap::render(year, &durations, color));
+        }
+        Some(("timesheet", sub_matches)) => {
+            let first = match sub_matches.get_one::<String>("month") {
+                Some(month) => parse_month(month)?,
+                None => today.with_day(1).unwrap(),
+            };
+            let last = last_day_of_month(first);
+            let days = report::days(store, first, last, today, hhmmss_to_s(&time))?;
+            let config = config::load()?;
+            let rules = report::parse_break_rules(&config.break_rules)?;
+            let off = off::read_with_holidays(&config.holidays, first, last)?;
+            let pdf = timesheet::pdf(&config, first, &days, &off, &rules, hours)?;
+            let output = match sub_matches.get_one::<String>("output") {
+                Some(output) => output.clone(),
+                None => format!("timesheet-{}.pdf", first.format("%Y-%m")),
+            };
+            std::fs::write(&output, pdf).map_err(|err| format!("{output}: {err}"))?;
+            println!("Wrote {output}.");
+        }
+        Some(("report", sub_matches)) => {
+            let from = input::parse_date(sub_matches.get_one::<String>("from").unwrap(), today)?;
+            let to = match sub_matches.get_one::<String>("to") {
+                Some(day) => input::parse_date(day, today)?,
+                None => today,
+            };
+            if to < from {
+                Err(format!("--to {to} is before --from {from}"))?
+            }
+            let mut days = report::days(store, from, to, today, hhmmss_to_s(&time))?;
+            let config = config::load()?;
+            let rules = report::parse_break_rules(&config.break_rules)?;
+            let round = sub_matches.get_one::<String>("round").map(String::as_str);
+            if let Some(rounding) = config::rounding(&config, round)? {
+                rounding.apply(&mut days);
+            }
+            match sub_matches.get_one::<String>("format").map(String::as_str) {
+                Some("md") => {
+                    print!("{}", document::markdown(from, to, &days, &rules, hours));
+                    return Ok(());
+                }
+                Some("html") => {
+                    print!("{}", document::html(from, to, &days, &rules, hours));
+                    return Ok(());
+                }
+                _ => {}
+            }
+
+            println!("Report {from} to {to}");
+            let group_by = match sub_matches
+                .get_one::<String>("group-by")
+                .map(String::as_str)
+            {
+                Some("day") => Some(report::GroupBy::Day),
+                Some("week") => Some(report::GroupBy::Week),
+                Some("month") => Some(report::GroupBy::Month),
+                _ => None,
+            };
+            let purged = purge::totals_between(from, to)?;
+            if let Some(group_by) = group_by {
+                let mut totals = report::totals(&days, group_by, &rules);
+                if group_by == report::GroupBy::Month {
+                    for total in &purged {
+                        match totals.iter_mut().find(|(label, _)| *label == total.month) {
+                            Some((_, seconds)) => *seconds += total.seconds,
+                            None => totals.push((total.month.clone(), total.seconds)),
+                        }
+                    }
+                    totals.sort();
+                }
+                for (label, total) in totals {
+                    println!("{label:<14}  {}", hours(total));
+                }
+            }
+            let gross: isize = days
+                .iter()
+                .filter_map(|day| day.info.as_ref())
+                .map(DayInfo::duration)
+                .sum();
+            let deducted: isize = days
+                .iter()
+                .filter_map(|day| day.info.as_ref())
+                .map(|info| report::deduction(info, &rules))
+                .sum();
+            if deducted > 0 {
+                println!(
+                    "Gross           {}\nDeducted        {}",
+                    hours(gross),
+                    hours(deducted)
+                );
+            }
+            let purged_days: usize = purged.iter().map(|total| total.days).sum();
+            let purged_total: isize = purged.iter().map(|total| total.seconds).sum();
+            if purged_days > 0 {
+                println!(
+                    "Purged          {} for {purged_days} days",
+                    hours(purged_total)
+                );
+            }
+            println!("Total           {}", hours(gross - deducted + purged_total));
+            let schedule = config::schedule(&config)?;
+            let days_off: Vec<chrono::NaiveDate> =
+                off::read_with_holidays(&config.holidays, from, to)?
+                    .into_keys()
+                    .filter(|date| (from..=to).contains(date))
+                    .collect();
+            if !days_off.is_empty() {
+                let credited: isize = days_off.iter().map(|date| schedule.target(*date)).sum();
+                println!(
+                    "Credited        {} for {} days off",
+                    hours(credited),
+                    days_off.len()
+                );
+            }
+            if let Some(script) = Script::load()? {
+                let worked: Vec<_> = days
+                    .iter()
+                    .filter_map(|day| Some((day.date, day.info.as_ref()?)))
+                    .collect();
+                for (name, value) in script.report_fields(&worked)? {
+                    println!("{name:<14}  {value}");
+                }
+            }
+            print_project_totals(&report::project_totals(
+                days.iter().filter_map(|day| day.info.as_ref()),
+            ))?;
+            print_break_totals(&report::break_totals(
+                days.iter().filter_map(|day| day.info.as_ref()),
+            ));
+            let reported = report::project_totals(days.iter().filter_map(|day| day.info.as_ref()));
+            let budgets: Vec<_> = projects::budgets(store, today, hhmmss_to_s(&time))?
+                .into_iter()
+                .filter(|budget| reported.contains_key(&Some(budget.project.clone())))
+                .collect();
+            print_budgets(&budgets);
+            if sub_matches.get_flag("earnings") {
+                print_earnings(&days, group_by, &config)?;
+            }
+
+            let findings = compliance::check(
+                store,
+                from,
+                to,
+                today,
+                hhmmss_to_s(&time),
+                &config::limits(&config)?,
+            )?;
+            if print_findings(&findings) && sub_matches.get_flag("strict") {
+                std::process::exit(2);
+            }
+        }
+        Some(("export", sub_matches)) => {
+            let month = match sub_matches.get_one::<String>("month") {
+                Some(month) => Some(parse_month(month)?),
+                None => None,
+            };
+            let from = match (month, sub_matches.get_one::<String>("from")) {
+                (Some(first), _) => first,
+                (None, Some(day)) => input::parse_date(day, today)?,
+                (None, None) => store.list_days()?.first().copied().unwrap_or(today),
+            };
+            let to = match (month, sub_matches.get_one::<String>("to")) {
+                (Some(first), _) => last_day_of_month(first),
+                (None, Some(day)) => input::parse_date(day, today)?,
+                (None, None) => today,
+            };
+            if to < from {
+                Err(format!("--to {to} is before --from {from}"))?
+            }
+            let mut days = report::days(store, from, to, today, hhmmss_to_s(&time))?;
+            let config = config::load()?;
+            let round = sub_matches.get_one::<String>("round").map(String::as_str);
+            if let Some(rounding) = config::rounding(&config, round)? {
+                rounding.apply(&mut days);
+            }
+            let format = sub_matches.get_one::<String>("FORMAT").unwrap();
+            if format == "xlsx" || format == "parquet" {
+                let output = match (sub_matches.get_one::<String>("output"), month) {
+                    (Some(output), _) => output.clone(),
+                    (None, Some(first)) => format!("azk-{}.{format}", first.format("%Y-%m")),
+                    (None, None) => format!("azk-{from}-{to}.{format}"),
+                };
+                let contents = if format == "xlsx" {
+                    export::xlsx(&days, &report::parse_break_rules(&config.break_rules)?)?
+                } else {
+                    export::parquet(&days)?
+                };
+                std::fs::write(&output, contents).map_err(|err| format!("{output}: {err}"))?;
+                println!("Wrote {output}.");
+                return Ok(());
+            }
+            if sub_matches.get_flag("push") && format == "influx" {
+                if config.influx_url.is_empty() {
+                    Err("influx_url is not set")?
+                }
+                let lines = export::export(format, &days, &export::Options::default())?;
+                export::push_influx(&lines, &config.influx_url)?;
+                println!("Wrote {} points to InfluxDB.", lines.lines().count());
+                return Ok(());
+            }
+            if sub_matches.get_flag("push") {
+                if format != "gitlab" {
+                    Err("--push only works with the gitlab and influx formats")?
+                }
+                let token = gitlab::token()?;
+                let pushed = gitlab::push(
+                    &gitlab::spent(&days),
+                    &config.gitlab_url,
+                    &config.gitlab_project,
+                    &token,
+                )?;
+                println!("Commented time spent on {pushed} issues.");
+                return Ok(());
+            }
+            let options = export::Options {
+                issue_from_note: sub_matches.get_flag("issue-from-note"),
+                per_project: sub_matches
+                    .get_one::<String>("group-by")
+                    .is_some_and(|group| group == "project"),
+            };
+            print!("{}", export::export(format, &days, &options)?);
+        }
+        Some(("import", sub_matches)) => {
+            let file = sub_matches.get_one::<String>("FILE").unwrap();
+            let contents = std::fs::read_to_string(file).map_err(|err| format!("{file}: {err}"))?;
+            let assignments: Vec<String> = sub_matches
+                .get_many::<String>("as")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+            let parse_day = |name| {
+                sub_matches
+                    .get_one::<String>(name)
+                    .map(|day| input::parse_date(day, today))
+                    .transpose()
+            };
+            let imported = import::import(
+                store,
+                sub_matches.get_one::<String>("FORMAT").unwrap(),
+                &contents,
+                parse_day("from")?,
+                parse_day("to")?,
+                &import::Overrides::parse(&assignments)?,
+            )?;
+            for skipped in &imported.skipped {
+                eprintln!("Skipped {skipped}");
+            }
+            println!(
+                "Imported {} intervals, skipped {}.",
+                imported.added,
+                imported.skipped.len()
+            );
+        }
+        Some(("edit", sub_matches)) => {
+            let date = match sub_matches.get_one::<String>("day") {
+                Some(day) => input::parse_date(day, today)?,
+                None => today,
+            };
+            edit::edit(store, date)?;
+        }
+        Some(("undo", sub_matches)) => {
+            let _lock = store.lock()?;
+            let Some(mut records) = store.read_day(today)? else {
                 eprintln!("Work hasn't started yet.");
                 std::process::exit(1);
+            };
+            let Some(record) = records.pop() else {
+                eprintln!("Nothing to undo.");
+                std::process::exit(1);
+            };
+            let (kind, time) = (&record.kind, record.time());
+
+            let location = store.location(today);
+            if !sub_matches.get_flag("yes")
+                && !confirm(&format!("Remove {kind} at {time} from {location}?"))?
+            {
+                std::process::exit(1);
+            }
+            store.write_day(today, &records)?;
+            println!("Removed {kind} at {time} from {location}.");
+        }
+        Some(("delete", sub_matches)) => {
+            // With a single argument, it is the index.
+            let (day, index) = match (
+                sub_matches.get_one::<String>("day"),
+                sub_matches.get_one::<String>("index"),
+            ) {
+                (Some(day), Some(index)) => (Some(day), index),
+                (Some(index), None) => (None, index),
+                _ => unreachable!(),
+            };
+            let date = match day {
+                Some(day) => input::parse_date(day, today)?,
+                None => today,
+            };
+            let index: usize = index
+                .parse()
+                .map_err(|_| format!("invalid index '{index}', expected a number from list"))?;
+
+            let _lock = store.lock()?;
+            let location = store.location(date);
+            let Some(mut records) = store.read_day(date)? else {
+                eprintln!("No records for {date} in {location}.");
+                std::process::exit(1);
+            };
+            if index == 0 || index > records.len() {
+                Err(format!(
+                    "no stamp #{index} in {location}, it has {}",
+                    records.len()
+                ))?
+            }
+            let record = records.remove(index - 1);
+            let (kind, time) = (&record.kind, record.time());
+
+            if !sub_matches.get_flag("yes") {
+                println!("Remove #{index}, {kind} at {time} from {location}.");
+                if let Err((_, err)) = DayInfo::from_records(&records, None) {
+                    println!("Afterwards, the day won't add up: {err}");
+                }
+                if !confirm("Remove it?")? {
+                    std::process::exit(1);
+                }
+            }
+            store.write_day(date, &records)?;
+            println!("Removed {kind} at {time} from {location}.");
+        }
+        Some(("amend", sub_matches)) => {
+            let time = input::parse_time(
+                sub_matches.get_one::<String>("TIME").unwrap(),
+                hhmmss_to_s(&time),
+            )?;
+            let date = match sub_matches.get_one::<String>("date") {
+                Some(day) => input::parse_date(day, today)?,
+                None => today,
+            };
+            let original = store::amend_last(store, date, &time)?;
+            println!(
+                "Moved the last stamp in {} from {original} to {time}.",
+                store.location(date)
+            );
+        }
+        Some(("balance", sub_matches)) => {
+            let config = config::load()?;
+            let schedule = config::schedule(&config)?;
+            let rules = report::parse_break_rules(&config.break_rules)?;
+            let first = store.list_days()?.first().copied().unwrap_or(today);
+            let off = off::read_with_holidays(&config.holidays, first, today)?;
+            let months =
+                balance::monthly(store, today, hhmmss_to_s(&time), &schedule, &rules, &off)?;
+            let running = balance::running(&months, sub_matches.get_flag("reset-monthly"));
+
+            for (month, running) in months.iter().zip(&running) {
+                println!(
+                    "{}  {:>7}  over {:>2} days  running {:>7}",
+                    month.month,
+                    signed_hours(month.balance),
+                    month.days,
+                    signed_hours(*running)
+                );
             }
+            println!(
+                "Balance  {}",
+                signed_hours(running.last().copied().unwrap_or(0))
+            );
+            print_vacation(&off, today.year(), config.vacation_days);
+        }
+        Some(("off", sub_matches)) => {
+            let config = config::load()?;
+            if let Some(days) = sub_matches.get_one::<String>("remove") {
+                let (first, last) = input::parse_date_range(days, today)?;
+                let dates: Vec<_> = first.iter_days().take_while(|date| *date <= last).collect();
+                let removed = off::remove(store, &dates)?;
+                println!("Removed {removed} days off.");
+            } else if let Some(kind) = sub_matches.get_one::<String>("kind") {
+                let days = sub_matches.get_one::<String>("days").unwrap();
+                let (first, last) = input::parse_date_range(days, today)?;
+                // A range leaves out the days without a target, like weekends,
+                // and holidays.
+                let schedule = config::schedule(&config)?;
+                let holidays = holidays::between(&config.holidays, first, last)?;
+                let dates: Vec<_> = first
+                    .iter_days()
+                    .take_while(|date| *date <= last)
+                    .filter(|date| {
+                        first == last
+                            || (schedule.target(*date) > 0 && !holidays.contains_key(date))
+                    })
+                    .collect();
+                off::add(store, &dates, kind)?;
+                println!("Recorded {} days of {kind}.", dates.len());
+                print_vacation(&off::read()?, first.year(), config.vacation_days);
+            } else {
+                let off = off::read()?;
+                for (date, kind) in off.iter().filter(|(date, _)| date.year() == today.year()) {
+                    println!("{}  {kind}", date.format("%a %Y-%m-%d"));
+                }
+                print_vacation(&off, today.year(), config.vacation_days);
+            }
+        }
+        Some(("sync", sub_matches)) => {
+            let config = config::load()?;
+            let host = std::fs::read_to_string("/etc/hostname").unwrap_or_default();
+            let message = match host.trim() {
+                "" => format!("Sync at {date} {time}"),
+                host => format!("Sync from {host} at {date} {time}"),
+            };
+            let remote = &config.sync_remote;
+            match sub_matches.subcommand() {
+                Some(("push", _)) => {
+                    let pushed = sync::push(store, &config, &message)?;
+                    println!("Pushed {} changed files to {remote}.", pushed.len());
+                }
+                Some(("pull", _)) => {
+                    let pulled = sync::pull(store, &config, &message)?;
+                    println!("Pulled {} changed files from {remote}.", pulled.files.len());
+                    print_merged(&pulled);
+                }
+                Some(("status", _)) => {
+                    let status = sync::status(store, &config)?;
+                    if status.is_empty() {
+                        println!("In sync with {remote}.");
+                    }
+                    for (heading, files) in [
+                        "Changed here:".to_owned(),
+                        format!("Changed on {remote}:"),
+                        "Changed on both sides:".to_owned(),
+                    ]
+                    .iter()
+                    .zip([&status.local, &status.remote, &status.both])
+                    {
+                        if !files.is_empty() {
+                            println!("{heading}");
+                            for file in files {
+                                println!("  {file}");
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    let pulled = sync::pull(store, &config, &message)?;
+                    let pushed = sync::push(store, &config, &message)?;
+                    println!(
+                        "Pulled {} and pushed {} changed files, in sync with {remote}.",
+                        pulled.files.len(),
+                        pushed.len()
+                    );
+                    print_merged(&pulled);
+                }
+            }
+        }
+        Some(("backup", sub_matches)) => match sub_matches.subcommand() {
+            Some(("create", sub_matches)) => {
+                let dest = sub_matches
+                    .get_one::<String>("PATH")
+                    .map_or(".", String::as_str);
+                let (path, manifest) = backup::create(store, std::path::Path::new(dest), now)?;
+                println!("Backed up {} days to {}.", manifest.days, path.display());
+            }
+            Some(("restore", sub_matches)) => {
+                let path = sub_matches.get_one::<String>("FILE").unwrap();
+                let force = sub_matches.get_flag("force");
+                let restored = backup::restore(store, std::path::Path::new(path), force, now)?;
+                if let Some(saved) = restored.saved {
+                    println!("Saved the records replaced to {}.", saved.display());
+                }
+                println!(
+                    "Restored {} days from the backup of {}.",
+                    restored.manifest.days, restored.manifest.created
+                );
+            }
+            _ => unreachable!(),
+        },
+        Some(("project", sub_matches)) => match sub_matches.subcommand() {
+            Some(("add", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("NAME").unwrap();
+                let client = sub_matches
+                    .get_one::<String>("client")
+                    .map_or("", String::as_str);
+                let budget = sub_matches
+                    .get_one::<String>("budget")
+                    .map_or("", String::as_str);
+                projects::add(store, name, client, budget)?;
+                match client {
+                    "" => println!("Added project {name}."),
+                    client => println!("Added project {name} for {client}."),
+                }
+            }
+            Some(("list", sub_matches)) => {
+                let all = sub_matches.get_flag("all");
+                let listed = projects::read()?
+                    .into_values()
+                    .filter(|project| all || !project.archived);
+                if sub_matches.get_flag("names") {
+                    for project in listed {
+                        println!("{}", project.name);
+                    }
+                    return Ok(());
+                }
+                let mut clients: std::collections::BTreeMap<String, Vec<projects::Project>> =
+                    std::collections::BTreeMap::new();
+                for project in listed {
+                    clients
+                        .entry(project.client.clone())
+                        .or_default()
+                        .push(project);
+                }
+                for (client, projects) in clients {
+                    println!(
+                        "{}",
+                        if client.is_empty() {
+                            "(no client)"
+                        } else {
+                            &client
+                        }
+                    );
+                    for project in projects {
+                        let mut line = format!("  {:<14}", project.name);
+                        if !project.budget.is_empty() {
+                            line += &format!("{} budget  ", project.budget);
+                        }
+                        if project.archived {
+                            line += "archived";
+                        }
+                        println!("{}", line.trim_end());
+                    }
+                }
+            }
+            Some(("archive", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("NAME").unwrap();
+                projects::archive(store, name)?;
+                println!("Archived project {name}.");
+            }
+            _ => unreachable!(),
+        },
+        Some(("holidays", sub_matches)) => {
+            if let Some(ics) = sub_matches.get_one::<String>("import") {
+                let imported = holidays::import(std::path::Path::new(ics))?;
+                println!("Imported {imported} holidays from {ics}.");
+                return Ok(());
+            }
+            let year = sub_matches
+                .get_one::<i32>("year")
+                .copied()
+                .unwrap_or(today.year());
+            let first = chrono::NaiveDate::from_ymd_opt(year, 1, 1).ok_or("invalid year")?;
+            let last = chrono::NaiveDate::from_ymd_opt(year, 12, 31).ok_or("invalid year")?;
+            let config = config::load()?;
+            for (date, name) in holidays::between(&config.holidays, first, last)? {
+                println!("{}  {name}", date.format("%a %Y-%m-%d"));
+            }
+        }
+        Some(("watch", _)) => watch::watch(store)?,
+        Some(("daemon", sub_matches)) => {
+            let config = config::load()?;
+            let idle_minutes = match sub_matches.get_one::<u32>("idle") {
+                Some(minutes) => *minutes,
+                None => config.idle_minutes,
+            };
+            daemon::daemon(store, &config, idle_minutes)?
+        }
+        Some(("serve", sub_matches)) => {
+            serve::serve(store, sub_matches.get_one::<String>("listen").unwrap())?
+        }
+        Some(("tui", _)) => tui::tui(store)?,
+        Some(("doctor", sub_matches)) => {
+            let fix = sub_matches.get_flag("fix");
+            let _lock = if fix { Some(store.lock()?) } else { None };
+            let mut remaining = 0;
+            let mut fixable = 0;
+            for diagnosis in doctor::check(store, today)? {
+                for problem in &diagnosis.problems {
+                    println!("{}", problem.description);
+                    if !problem.fixable {
+                        remaining += 1;
+                    } else if !fix {
+                        fixable += 1;
+                        remaining += 1;
+                    }
+                }
+                if let (true, Some(records)) = (fix, &diagnosis.fix) {
+                    store.write_day(diagnosis.date, records)?;
+                    println!("Fixed {}.", store.location(diagnosis.date));
+                }
+            }
+
+            if remaining == 0 {
+                println!("No problems left.");
+            } else {
+                if fixable > 0 {
+                    println!("{fixable} of {remaining} problems can be fixed with --fix.");
+                }
+                std::process::exit(1);
+            }
+        }
+        Some(("migrate", _)) => {
+            let migrated = store::migrate(store)?;
+            for (date, schema) in &migrated {
+                println!(
+                    "Upgraded {} from schema {schema} to {}.",
+                    store.location(*date),
+                    store::SCHEMA_VERSION
+                );
+            }
+            if migrated.is_empty() {
+                println!("Already at schema {}.", store::SCHEMA_VERSION);
+            }
+        }
+        Some(("archive", sub_matches)) => {
+            let before = parse_month(sub_matches.get_one::<String>("before").unwrap())?;
+            let archived = store::archive(&config::load()?.store, before)?;
+            for (month, days) in &archived {
+                println!("Archived {days} days of {}.", month.format("%Y-%m"));
+            }
+            if archived.is_empty() {
+                println!("No days before {} to archive.", before.format("%Y-%m"));
+            }
+        }
+        Some(("purge", sub_matches)) => {
+            let config = config::load()?;
+            let age = match sub_matches.get_one::<String>("older-than") {
+                Some(age) => age,
+                None if config.retention.is_empty() => Err("pass --older-than or set retention")?,
+                None => &config.retention,
+            };
+            let cutoff = purge::cutoff(age, today)?;
+            let archive_dir = match sub_matches.get_one::<String>("archive") {
+                Some(dir) => Some(std::path::PathBuf::from(dir)),
+                None if sub_matches.get_flag("delete") || config.retention_archive.is_empty() => {
+                    None
+                }
+                None => Some(std::path::PathBuf::from(&config.retention_archive)),
+            };
+            let totals = sub_matches.get_flag("totals") || config.retention_totals;
+
+            let days = purge::days_before(store, cutoff)?;
+            if days.is_empty() {
+                println!("No days before {cutoff}.");
+                return Ok(());
+            }
+            let question = match &archive_dir {
+                Some(dir) => format!(
+                    "Archive the {} days before {cutoff} to {} and remove them?",
+                    days.len(),
+                    dir.display()
+                ),
+                None => format!("Delete the {} days before {cutoff}?", days.len()),
+            };
+            if !sub_matches.get_flag("yes") && !confirm(&question)? {
+                std::process::exit(1);
+            }
+            let purged = purge::purge(store, &config, cutoff, archive_dir.as_deref(), totals)?;
+            if let Some(archive) = &purged.archive {
+                println!(
+                    "Archived {} days to {}.",
+                    purged.days.len(),
+                    archive.display()
+                );
+            }
+            if totals {
+                println!("Added their monthly totals to {}.", purge::TOTALS_FILE);
+            }
+            println!("Removed {} days before {cutoff}.", purged.days.len());
+        }
+        Some(("audit", sub_matches)) => match sub_matches.subcommand() {
+            Some(("verify", _)) => {
+                let data_dir = store::data_dir()?;
+                let verified = audit::verify(store, &data_dir)?;
+                for problem in &verified.problems {
+                    println!("{problem}");
+                }
+                if !verified.problems.is_empty() {
+                    Err(format!(
+                        "{} problems in {}",
+                        verified.problems.len(),
+                        data_dir.join(audit::FILE_NAME).display()
+                    ))?
+                }
+                println!(
+                    "The {} entries of the log are intact and match the {} recorded days.",
+                    verified.entries, verified.days
+                );
+            }
+            _ => unreachable!(),
+        },
+        Some(("encrypt", _)) => {
+            let passphrase = store::passphrase(true)?;
+            let days = store::encrypt(&config::load()?.store, Some(&passphrase))?;
+            println!("Encrypted {days} days.");
+        }
+        Some(("decrypt", _)) => {
+            let days = store::encrypt(&config::load()?.store, None)?;
+            println!("Decrypted {days} days.");
+        }
+        Some(("convert", sub_matches)) => {
+            let backend = sub_matches.get_one::<String>("BACKEND").unwrap();
+            if *backend == config::load()?.store {
+                Err(format!("already using the {backend} store"))?
+            }
+            let target = store::open_backend(backend)?;
+            if !target.list_days()?.is_empty() {
+                Err(format!(
+                    "the {backend} store already has records in {}",
+                    target.location(today)
+                ))?
+            }
+
+            let _lock = store.lock()?;
+            let dates = store.list_days()?;
+            for date in &dates {
+                let records = store.read_day(*date)?.unwrap_or_default();
+                target.write_day(*date, &records)?;
+            }
+            config::set("store", backend)?;
+            println!(
+                "Copied {} days to {}, now using the {backend} store.",
+                dates.len(),
+                target.location(today)
+            );
+        }
+        Some(("status", sub_matches)) => {
+            let now_s = hhmmss_to_s(&time);
+            if sub_matches.get_flag("quiet") {
+                let working =
+                    read_work_time(store, today, Some(now_s))?.is_some_and(|day| day.ongoing());
+                std::process::exit(if working { 0 } else { 1 });
+            }
+            let config = config::load()?;
+            let target = config::schedule(&config)?.target(today);
+            if let Some(template) = sub_matches.get_one("format").or(config.format.as_ref()) {
+                let day = read_work_time(store, today, Some(now_s))?.unwrap_or_default();
+                let vars = format::day_vars(&date, &day, now_s, target);
+                println!("{}", format::render(template, &vars)?);
+                return Ok(());
+            }
+
+            if let Some(day) = read_work_time(store, today, Some(now_s))? {
+                let deducted =
+                    report::deduction(&day, &report::parse_break_rules(&config.break_rules)?);
+                let duration = day.duration() - deducted;
+                let session_hhmm = hours(now_s - day.last);
+                let total_hhmm = hours(duration);
+
+                if day.ongoing() {
+                    println!("Working for {session_hhmm}.\nWorked {total_hhmm} today so far.")
+                } else {
+                    println!("On a break for {session_hhmm}.\nWorked {total_hhmm} today so far.")
+                }
+                if deducted > 0 {
+                    println!(
+                        "{} worked, {} deducted for breaks that were too short.",
+                        hours(day.duration()),
+                        hours(deducted)
+                    );
+                }
+                println!(
+                    "{}",
+                    remaining_message(duration, day.ongoing(), now_s, target)
+                );
+                let project = day.intervals.last().and_then(|last| last.project.as_ref());
+                let budgets: Vec<_> = projects::budgets(store, today, now_s)?
+                    .into_iter()
+                    .filter(|budget| Some(&budget.project) == project)
+                    .collect();
+                print_budgets(&budgets);
+
+                let limits = config::limits(&config)?;
+                let findings = compliance::check(store, today, today, today, now_s, &limits)?;
+                if print_findings(&findings) && sub_matches.get_flag("strict") {
+                    std::process::exit(2);
+                }
+            } else {
+                println!("Work hasn't started yet.");
+            }
+        }
+        Some((name, sub_matches)) => {
+            let args: Vec<std::ffi::OsString> = sub_matches
+                .get_many::<std::ffi::OsString>("")
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect();
+            std::process::exit(plugin::run(name, &args, today)?);
         }
         _ => unreachable!(),
     }
@@ -0,0 +1,251 @@
+use chrono::{DateTime, FixedOffset, Timelike};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Record {
+    pub kind: String,
+    /// When the stamp was taken, with the UTC offset in effect then.
+    pub timestamp: DateTime<FixedOffset>,
+    pub project: Option<String>,
+    pub note: Option<String>,
+}
+
+impl Record {
+    /// The wall-clock time of the stamp, in HH:MM:SS.
+    pub fn time(&self) -> String {
+        format!("{}", self.timestamp.format("%H:%M:%S"))
+    }
+
+    /// The kind of break a stop starts, stored as in `stop:lunch`.
+    pub fn break_category(&self) -> Option<&str> {
+        self.kind.strip_prefix("stop:")
+    }
+}
+
+/// Seconds since midnight on the wall clock.
+fn wall_seconds(timestamp: &DateTime<FixedOffset>) -> isize {
+    timestamp.num_seconds_from_midnight() as isize
+}
+
+/// A stretch of work, in seconds since midnight on the wall clock. An ongoing
+/// interval has no stop yet and provisionally ends at the time it was read.
+pub struct Interval {
+    pub start: isize,
+    pub end: isize,
+    /// Seconds that actually passed, which differ from `end - start` if the
+    /// clocks were changed in between.
+    pub elapsed: isize,
+    pub ongoing: bool,
+    /// The project given when work was started.
+    pub project: Option<String>,
+    /// The note given when work was started.
+    pub note: Option<String>,
+    /// The instants of `start` and `end`.
+    pub started_at: DateTime<FixedOffset>,
+    pub ended_at: DateTime<FixedOffset>,
+}
+
+impl Interval {
+    /// Work from the `start` record until `end`.
+    fn new(start: &Record, end: DateTime<FixedOffset>, ongoing: bool) -> Interval {
+        Interval {
+            start: wall_seconds(&start.timestamp),
+            end: wall_seconds(&end),
+            elapsed: (end - start.timestamp).num_seconds() as isize,
+            ongoing,
+            project: start.project.clone(),
+            note: start.note.clone(),
+            started_at: start.timestamp,
+            ended_at: end,
+        }
+    }
+
+    pub fn duration(&self) -> isize {
+        self.elapsed
+    }
+}
+
+/// The time between a stop and the next start, in seconds since midnight on
+/// the wall clock.
+pub struct Break {
+    pub start: isize,
+    pub end: isize,
+    /// Seconds that actually passed, as in [`Interval::elapsed`].
+    pub elapsed: isize,
+    /// The kind of break given when work was stopped.
+    pub category: Option<String>,
+}
+
+impl Break {
+    pub fn duration(&self) -> isize {
+        self.elapsed
+    }
+}
+
+#[derive(Default)]
+pub struct DayInfo {
+    pub intervals: Vec<Interval>,
+    /// The breaks between the intervals, so one less. Time after the last
+    /// stop is not a break.
+    pub breaks: Vec<Break>,
+    /// Time of the day's last record.
+    pub last: isize,
+}
+
+impl DayInfo {
+    pub fn start(&self) -> isize {
+        self.intervals.first().map_or(0, |x| x.start)
+    }
+
+    pub fn end(&self) -> isize {
+        self.intervals.last().map_or(0, |x| x.end)
+    }
+
+    pub fn duration(&self) -> isize {
+        self.intervals.iter().map(Interval::duration).sum()
+    }
+
+    /// The time spent on breaks.
+    pub fn break_duration(&self) -> isize {
+        self.breaks.iter().map(Break::duration).sum()
+    }
+
+    pub fn ongoing(&self) -> bool {
+        self.intervals.last().is_some_and(|x| x.ongoing)
+    }
+
+    /// Pairs starts with the following stops, see [`DayFold`]. Fails with
+    /// the index of the first record that is out of order or does not
+    /// alternate with the previous one.
+    pub fn from_records(
+        records: &[Record],
+        now: Option<DateTime<FixedOffset>>,
+    ) -> Result<DayInfo, (usize, RecordError)> {
+        let mut fold = DayFold::default();
+        for (index, record) in records.iter().enumerate() {
+            fold.push(record).map_err(|err| (index, err))?;
+        }
+        Ok(fold.finish(now))
+    }
+}
+
+/// Pairs starts with the following stops as the records come, so that a day
+/// is read without holding all of its records. A stop may name a kind of
+/// break, see [`Record::break_category`].
+#[derive(Default)]
+pub struct DayFold {
+    intervals: Vec<Interval>,
+    breaks: Vec<Break>,
+    stopped: Option<(DateTime<FixedOffset>, Option<String>)>,
+    open: Option<Record>,
+    last: Option<DateTime<FixedOffset>>,
+}
+
+impl DayFold {
+    /// Adds the next record. Fails if it is out of order or does not
+    /// alternate with the previous one.
+    pub fn push(&mut self, record: &Record) -> Result<(), RecordError> {
+        let timestamp = record.timestamp;
+        if self.last.is_some_and(|last| timestamp < last) {
+            return Err(RecordError::OutOfOrder(record.time()));
+        }
+        self.last = Some(timestamp);
+        let kind = match record.break_category() {
+            Some(_) => "stop",
+            None => record.kind.as_str(),
+        };
+        match (kind, self.open.take()) {
+            ("strt", None) => {
+                if let Some((stop, category)) = self.stopped.take() {
+                    self.breaks.push(Break {
+                        start: wall_seconds(&stop),
+                        end: wall_seconds(&timestamp),
+                        elapsed: (timestamp - stop).num_seconds() as isize,
+                        category,
+                    });
+                }
+                self.open = Some(record.clone());
+            }
+            ("stop", Some(start)) => {
+                self.intervals.push(Interval::new(&start, timestamp, false));
+                self.stopped = Some((timestamp, record.break_category().map(str::to_owned)));
+            }
+            _ => {
+                return Err(RecordError::UnexpectedKind {
+                    kind: record.kind.clone(),
+                    time: record.time(),
+                })
+            }
+        }
+        Ok(())
+    }
+
+    /// The day of the records added. A start without a stop is ongoing and
+    /// ends at `now`, or at its start if `now` is not given.
+    pub fn finish(mut self, now: Option<DateTime<FixedOffset>>) -> DayInfo {
+        if let Some(start) = self.open {
+            let end = now.unwrap_or(start.timestamp);
+            self.intervals.push(Interval::new(&start, end, true));
+        }
+        DayInfo {
+            intervals: self.intervals,
+            breaks: self.breaks,
+            last: self.last.as_ref().map_or(0, wall_seconds),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RecordError {
+    #[error("invalid time '{0}', expected HH:MM:SS")]
+    InvalidTime(String),
+    #[error("unexpected kind '{kind}' at {time}")]
+    UnexpectedKind { kind: String, time: String },
+    #[error("{0} is before the previous stamp")]
+    OutOfOrder(String),
+}
+
+/// Records and days in a fixed offset, to test with.
+#[cfg(test)]
+pub mod fixtures {
+    use chrono::{DateTime, NaiveDate};
+
+    use super::{DayInfo, Record};
+    use crate::report::Day;
+
+    /// A record of `kind` at `time` on `date`, with the time as in `09:00`
+    /// or `09:00:30`.
+    pub fn record(date: &str, kind: &str, time: &str) -> Record {
+        let seconds = if time.len() == 5 { ":00" } else { "" };
+        Record {
+            kind: kind.to_owned(),
+            timestamp: DateTime::parse_from_rfc3339(&format!("{date}T{time}{seconds}+01:00"))
+                .unwrap(),
+            project: None,
+            note: None,
+        }
+    }
+
+    /// The day of `records` on `date`.
+    pub fn day_of(date: &str, records: &[Record]) -> Day {
+        Day {
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            info: Some(DayInfo::from_records(records, None).unwrap()),
+        }
+    }
+
+    /// A day of alternating starts and stops at `times` on `date`.
+    pub fn day(date: &str, times: &[&str]) -> Day {
+        let records: Vec<Record> = times
+            .iter()
+            .enumerate()
+            .map(|(index, time)| record(date, if index % 2 == 0 { "strt" } else { "stop" }, time))
+            .collect();
+        day_of(date, &records)
+    }
+
+    /// The info of a [`day`].
+    pub fn info(date: &str, times: &[&str]) -> DayInfo {
+        day(date, times).info.unwrap()
+    }
+}
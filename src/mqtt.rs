@@ -0,0 +1,125 @@
+//! The working state and the day's total published to an MQTT broker, e.g.
+//! for Home Assistant. Speaks just enough of MQTT 3.1.1 to publish retained
+//! messages at most once, over plain TCP.
+
+use std::error::Error;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::secret;
+
+/// The keyring entry holding the password for `mqtt_username`.
+pub const SECRET: &str = "mqtt";
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Appends `length` in the variable-length encoding of MQTT.
+fn push_length(packet: &mut Vec<u8>, mut length: usize) {
+    loop {
+        let byte = (length % 128) as u8;
+        length /= 128;
+        if length == 0 {
+            packet.push(byte);
+            return;
+        }
+        packet.push(byte | 0x80);
+    }
+}
+
+/// Appends a string prefixed by its length.
+fn push_str(packet: &mut Vec<u8>, text: &str) {
+    packet.extend((text.len() as u16).to_be_bytes());
+    packet.extend(text.as_bytes());
+}
+
+/// A control packet of `kind`, with the fixed header in front of `body`.
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![kind];
+    push_length(&mut packet, body.len());
+    packet.extend(body);
+    packet
+}
+
+/// Publishes `messages` of topic and payload to the broker at `broker`, as
+/// in `localhost:1883`, as retained messages.
+fn publish(
+    broker: &str,
+    username: &str,
+    password: Option<&str>,
+    messages: &[(String, String)],
+) -> Result<(), Box<dyn Error>> {
+    let broker = if broker.contains(':') {
+        broker.to_owned()
+    } else {
+        format!("{broker}:1883")
+    };
+    let mut stream = TcpStream::connect(&broker).map_err(|err| format!("{broker}: {err}"))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut connect = Vec::new();
+    push_str(&mut connect, "MQTT");
+    connect.push(4);
+    let mut flags = 0x02;
+    if !username.is_empty() {
+        flags |= 0x80;
+        if password.is_some() {
+            flags |= 0x40;
+        }
+    }
+    connect.push(flags);
+    connect.extend(60u16.to_be_bytes());
+    push_str(&mut connect, &format!("azk-{}", std::process::id()));
+    if !username.is_empty() {
+        push_str(&mut connect, username);
+        if let Some(password) = password {
+            push_str(&mut connect, password);
+        }
+    }
+    stream.write_all(&packet(0x10, &connect))?;
+
+    let mut connack = [0; 4];
+    stream.read_exact(&mut connack)?;
+    match connack {
+        [0x20, 2, _, 0] => {}
+        [0x20, 2, _, 4 | 5] => Err(format!("{broker} refused the username or password"))?,
+        [0x20, 2, _, code] => Err(format!("{broker} refused the connection ({code})"))?,
+        _ => Err(format!("{broker} does not speak MQTT"))?,
+    }
+
+    for (topic, payload) in messages {
+        let mut publish = Vec::new();
+        push_str(&mut publish, topic);
+        publish.extend(payload.as_bytes());
+        // At most once, retained.
+        stream.write_all(&packet(0x31, &publish))?;
+    }
+    stream.write_all(&packet(0xe0, &[]))?;
+    Ok(())
+}
+
+/// Publishes `state`, one of `working` and `break`, to `{mqtt_topic}/state`
+/// and the seconds worked today to `{mqtt_topic}/today`. Does nothing unless
+/// `mqtt_broker` is set.
+pub fn publish_state(config: &Config, state: &str, today: isize) -> Result<(), Box<dyn Error>> {
+    if config.mqtt_broker.is_empty() {
+        return Ok(());
+    }
+    let password = if config.mqtt_username.is_empty() {
+        None
+    } else {
+        secret::get(SECRET)?
+    };
+    let topic = config.mqtt_topic.trim_end_matches('/');
+    publish(
+        &config.mqtt_broker,
+        &config.mqtt_username,
+        password.as_deref(),
+        &[
+            (format!("{topic}/state"), state.to_owned()),
+            (format!("{topic}/today"), today.to_string()),
+        ],
+    )
+}
@@ -0,0 +1,186 @@
+//! Desktop notifications for the milestones of a day and reminders of
+//! breaks, each opted into in the config.
+
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate};
+
+use crate::config::Config;
+use crate::model::DayInfo;
+use crate::projects::{self, BudgetUse};
+use crate::time::{hhmm_to_s, s_to_hhmm};
+
+fn send(summary: &str, body: &str) -> Result<(), Box<dyn Error>> {
+    notify_rust::Notification::new()
+        .appname("azk")
+        .summary(summary)
+        .body(body)
+        .show()?;
+    Ok(())
+}
+
+/// Which milestones were notified of on a day, so that each is only
+/// notified of once.
+#[derive(Default)]
+pub struct Milestones {
+    date: Option<NaiveDate>,
+    target: bool,
+    max: bool,
+    away: bool,
+}
+
+impl Milestones {
+    /// Notifies of the milestones `day` reached that were not notified of
+    /// yet: the daily `target` with `notify_target`, `max_daily` with
+    /// `notify_max`, and having been idle for `notify_break` while working.
+    pub fn check(
+        &mut self,
+        config: &Config,
+        date: NaiveDate,
+        day: &DayInfo,
+        target: isize,
+        idle: isize,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.date != Some(date) {
+            *self = Milestones {
+                date: Some(date),
+                ..Milestones::default()
+            };
+        }
+        let duration = day.duration();
+
+        if config.notify_target && target > 0 && duration >= target && !self.target {
+            self.target = true;
+            send(
+                "Daily target reached",
+                &format!("{} of work today.", s_to_hhmm(duration)),
+            )?;
+        }
+        let max_daily = hhmm_to_s(&config.max_daily)?;
+        if config.notify_max && duration > max_daily && !self.max {
+            self.max = true;
+            send(
+                "Maximum exceeded",
+                &format!(
+                    "{} of work today, more than {}.",
+                    s_to_hhmm(duration),
+                    config.max_daily
+                ),
+            )?;
+        }
+        if !config.notify_break.is_empty() {
+            let away = day.ongoing() && idle >= hhmm_to_s(&config.notify_break)?;
+            if away && !self.away {
+                send(
+                    "Break not stamped",
+                    &format!(
+                        "Idle for {} while working, stamp the break with `azk stop`.",
+                        s_to_hhmm(idle)
+                    ),
+                )?;
+            }
+            self.away = away;
+        }
+        Ok(())
+    }
+}
+
+/// Reminds of taking a break after `break_reminder` of continuous work, once
+/// per session. The reminder can be snoozed for `break_snooze`.
+#[derive(Default)]
+pub struct BreakReminder {
+    /// When the session reminded of started.
+    session: Option<DateTime<FixedOffset>>,
+    /// When to remind next, if at all.
+    next: Option<DateTime<FixedOffset>>,
+    /// Set when the reminder was snoozed, from the thread waiting for it.
+    snoozed: Arc<AtomicBool>,
+}
+
+impl BreakReminder {
+    /// Reminds of a break if the ongoing session of `day` has lasted long
+    /// enough at `now`.
+    pub fn check(
+        &mut self,
+        config: &Config,
+        day: &DayInfo,
+        now: DateTime<FixedOffset>,
+    ) -> Result<(), Box<dyn Error>> {
+        if config.break_reminder.is_empty() {
+            return Ok(());
+        }
+        let Some(session) = day.intervals.last().filter(|last| last.ongoing) else {
+            self.session = None;
+            return Ok(());
+        };
+        if self.session != Some(session.started_at) {
+            self.session = Some(session.started_at);
+            self.next = Some(
+                session.started_at + Duration::seconds(hhmm_to_s(&config.break_reminder)? as i64),
+            );
+        }
+        if self.snoozed.swap(false, Ordering::SeqCst) {
+            self.next = Some(now + Duration::seconds(hhmm_to_s(&config.break_snooze)? as i64));
+        }
+        if self.next.is_none_or(|next| now < next) {
+            return Ok(());
+        }
+        self.next = None;
+
+        let mut notification = notify_rust::Notification::new();
+        notification
+            .appname("azk")
+            .summary("Time for a break")
+            .body(&format!(
+                "Working for {} without a break.",
+                s_to_hhmm((now - session.started_at).num_seconds() as isize)
+            ))
+            .action("snooze", &format!("Snooze for {}", config.break_snooze));
+        let handle = notification.show()?;
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            let snoozed = self.snoozed.clone();
+            thread::spawn(move || {
+                handle.wait_for_action(|action| {
+                    if action == "snooze" {
+                        snoozed.store(true, Ordering::SeqCst);
+                    }
+                })
+            });
+        }
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        drop(handle);
+        Ok(())
+    }
+}
+
+/// Notifies that `budget` crossed [`projects::WARN_PERCENT`] or its end by
+/// the last `added` seconds of work, if `notify_budget` is set.
+pub fn budget_crossed(
+    config: &Config,
+    budget: &BudgetUse,
+    added: isize,
+) -> Result<(), Box<dyn Error>> {
+    if !config.notify_budget || budget.budget <= 0 {
+        return Ok(());
+    }
+    let before = (budget.used - added) * 100 / budget.budget;
+    let crossed = [projects::WARN_PERCENT, 100]
+        .into_iter()
+        .any(|percent| before < percent && budget.percent() >= percent);
+    if crossed {
+        send(
+            &format!("Budget of {}", budget.project),
+            &format!(
+                "{} of {} used ({}%).",
+                s_to_hhmm(budget.used),
+                s_to_hhmm(budget.budget),
+                budget.percent()
+            ),
+        )?;
+    }
+    Ok(())
+}
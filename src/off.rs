@@ -0,0 +1,113 @@
+//! Days off work, kept apart from the records of work in `off.csv`.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::holidays;
+use crate::store::{data_dir, Store};
+
+/// The days off, in the data dir.
+pub const FILE_NAME: &str = "off.csv";
+
+/// The kinds of days off, as given to `azk off`.
+pub const KINDS: [&str; 2] = ["vacation", "sick"];
+
+#[derive(Deserialize, Serialize)]
+struct DayOff {
+    date: NaiveDate,
+    kind: String,
+}
+
+fn path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(data_dir()?.join(FILE_NAME))
+}
+
+/// The kind of every day off.
+pub fn read() -> Result<BTreeMap<NaiveDate, String>, Box<dyn Error>> {
+    let path = path()?;
+    let contents = match fs::read(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(err) => Err(format!("{}: {err}", path.display()))?,
+    };
+    let mut rdr = csv::Reader::from_reader(contents.as_slice());
+    let mut days = BTreeMap::new();
+    for day in rdr.deserialize::<DayOff>() {
+        let day = day.map_err(|err| format!("{}: {err}", path.display()))?;
+        days.insert(day.date, day.kind);
+    }
+    Ok(days)
+}
+
+/// The days off, along with the public holidays of `region` from `from` to
+/// `to` named after them, see [`holidays::between`]. Recorded days off take
+/// precedence.
+pub fn read_with_holidays(
+    region: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<BTreeMap<NaiveDate, String>, Box<dyn Error>> {
+    let mut days = holidays::between(region, from, to)?;
+    days.extend(read()?);
+    Ok(days)
+}
+
+/// Replaces all days off, through a temporary file.
+fn write(days: &BTreeMap<NaiveDate, String>) -> Result<(), Box<dyn Error>> {
+    let path = path()?;
+    let tmp_path = path.with_extension("csv.tmp");
+    let file = File::create(&tmp_path)?;
+    let mut wtr = csv::Writer::from_writer(&file);
+    for (date, kind) in days {
+        wtr.serialize(DayOff {
+            date: *date,
+            kind: kind.clone(),
+        })?;
+    }
+    wtr.flush()?;
+    drop(wtr);
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Records `dates` as days off of `kind`, replacing what they were before.
+pub fn add(store: &dyn Store, dates: &[NaiveDate], kind: &str) -> Result<(), Box<dyn Error>> {
+    if !KINDS.contains(&kind) {
+        Err(format!(
+            "unknown kind of day off '{kind}', expected one of {}",
+            KINDS.join(", ")
+        ))?
+    }
+    let _lock = store.lock()?;
+    let mut days = read()?;
+    for date in dates {
+        days.insert(*date, kind.to_owned());
+    }
+    write(&days)
+}
+
+/// Removes `dates` from the days off. Returns how many of them were off.
+pub fn remove(store: &dyn Store, dates: &[NaiveDate]) -> Result<usize, Box<dyn Error>> {
+    let _lock = store.lock()?;
+    let mut days = read()?;
+    let removed = dates
+        .iter()
+        .filter(|date| days.remove(date).is_some())
+        .count();
+    write(&days)?;
+    Ok(removed)
+}
+
+/// How many vacation days were taken in `year`.
+pub fn vacation_taken(days: &BTreeMap<NaiveDate, String>, year: i32) -> usize {
+    days.iter()
+        .filter(|(date, kind)| date.year() == year && *kind == "vacation")
+        .count()
+}
@@ -0,0 +1,115 @@
+//! Just enough of PDF to print text and lines on A4 pages, in the built-in
+//! Helvetica fonts, so nothing has to be embedded.
+
+use std::fmt::Write;
+
+/// The size of an A4 page, in points.
+pub const WIDTH: f32 = 595.0;
+pub const HEIGHT: f32 = 842.0;
+
+/// A page drawn from the bottom left, in points.
+#[derive(Default)]
+pub struct Page {
+    content: String,
+}
+
+/// Encodes `text` as a PDF string in WinAnsi, the encoding of the built-in
+/// fonts. What it cannot hold is replaced by `?`.
+fn string(text: &str) -> String {
+    let mut encoded = String::from("(");
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                encoded.push('\\');
+                encoded.push(c);
+            }
+            ' '..='~' => encoded.push(c),
+            '€' => encoded.push_str("\\200"),
+            '–' => encoded.push_str("\\226"),
+            '\u{a0}'..='\u{ff}' => {
+                write!(encoded, "\\{:03o}", c as u32).expect("writing to a string");
+            }
+            _ => encoded.push('?'),
+        }
+    }
+    encoded.push(')');
+    encoded
+}
+
+impl Page {
+    /// Writes `text` with its baseline starting at `x`, `y`, in bold if `bold`.
+    pub fn text(&mut self, x: f32, y: f32, size: f32, bold: bool, text: &str) {
+        let font = if bold { "F2" } else { "F1" };
+        writeln!(
+            self.content,
+            "BT /{font} {size} Tf {x} {y} Td {} Tj ET",
+            string(text)
+        )
+        .expect("writing to a string");
+    }
+
+    /// Writes `text` so that it ends at `x`, assuming Helvetica's widths
+    /// for digits and punctuation, which suits numbers.
+    pub fn text_right(&mut self, x: f32, y: f32, size: f32, bold: bool, text: &str) {
+        // Digits are 0.556 em wide in Helvetica, and so is most else in a
+        // duration or a time.
+        let width = text.chars().count() as f32 * 0.556 * size;
+        self.text(x - width, y, size, bold, text);
+    }
+
+    /// Draws a line from `x1`, `y1` to `x2`, `y2`, `width` points thick.
+    pub fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, width: f32) {
+        writeln!(self.content, "{width} w {x1} {y1} m {x2} {y2} l S").expect("writing to a string");
+    }
+}
+
+/// A document of `pages`.
+pub fn document(pages: &[Page]) -> Vec<u8> {
+    let mut objects: Vec<String> = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_owned(),
+        String::new(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_owned(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+            .to_owned(),
+    ];
+    let mut kids = Vec::new();
+    for page in pages {
+        let content = objects.len() + 1;
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            page.content.len(),
+            page.content
+        ));
+        kids.push(format!("{} 0 R", objects.len() + 1));
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {WIDTH} {HEIGHT}] \
+             /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {content} 0 R >>"
+        ));
+    }
+    objects[1] = format!(
+        "<< /Type /Pages /Kids [{}] /Count {} >>",
+        kids.join(" "),
+        pages.len()
+    );
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        writeln!(pdf, "{} 0 obj\n{object}\nendobj", index + 1).expect("writing to a string");
+    }
+    let xref = pdf.len();
+    writeln!(pdf, "xref\n0 {}\n0000000000 65535 f ", objects.len() + 1)
+        .expect("writing to a string");
+    for offset in offsets {
+        writeln!(pdf, "{offset:010} 00000 n ").expect("writing to a string");
+    }
+    write!(
+        pdf,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+        objects.len() + 1
+    )
+    .expect("writing to a string");
+    pdf.into_bytes()
+}
@@ -0,0 +1,277 @@
+//! The figures of a week, a month, of ranges compared side by side and of
+//! the rolling averages, as `azk week`, `month`, `compare` and `trends` print
+//! them.
+
+use std::error::Error;
+
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+
+use crate::config::{self, Config};
+use crate::report::{self, BreakRule};
+use crate::schedule::Schedule;
+use crate::store::Store;
+use crate::totals;
+use crate::{off, time};
+
+/// What is noted after a day of a week or a month.
+pub enum Note {
+    /// How much was deducted for breaks.
+    Deducted(isize),
+    /// A day off of the kind, with the target credited for it, if any.
+    Off(String, Option<isize>),
+    /// Nothing was recorded on a past day with a target.
+    Missing,
+}
+
+/// A day of a week or a month.
+pub struct DayLine {
+    pub date: NaiveDate,
+    /// The net work, if any was recorded.
+    pub net: Option<isize>,
+    /// What the day is held against, none for days off.
+    pub target: isize,
+    pub notes: Vec<Note>,
+}
+
+/// The days of a week or a month, with what their bars are scaled to: the
+/// longest day, or the longest target if no day reached it.
+pub struct Period {
+    pub days: Vec<DayLine>,
+    pub scale: isize,
+    /// The net work, with the targets of days off in a week.
+    pub total: isize,
+    /// How many days had work.
+    pub tracked: isize,
+}
+
+impl Period {
+    /// The average work of the days with any.
+    pub fn average(&self) -> isize {
+        if self.tracked > 0 {
+            self.total / self.tracked
+        } else {
+            0
+        }
+    }
+}
+
+fn scale(days: &totals::Days, schedule: &Schedule) -> isize {
+    days.iter()
+        .map(|(date, net)| net.map_or(0, |total| total.net).max(schedule.target(*date)))
+        .max()
+        .unwrap_or(0)
+}
+
+/// The week from `monday`, where days off are credited with their target.
+pub fn week(
+    store: &dyn Store,
+    config: &Config,
+    monday: NaiveDate,
+    today: NaiveDate,
+    now: isize,
+) -> Result<Period, Box<dyn Error>> {
+    let sunday = monday + Days::new(6);
+    let rules = report::parse_break_rules(&config.break_rules)?;
+    let schedule = config::schedule(config)?;
+    let off = off::read_with_holidays(&config.holidays, monday, sunday)?;
+
+    let days = totals::days(store, monday, sunday, today, now, &rules)?;
+    let scale = scale(&days, &schedule);
+    let mut total = 0;
+    let mut tracked = 0;
+    let mut lines = Vec::new();
+    for (date, net) in days {
+        let mut notes = Vec::new();
+        if let Some(day) = net {
+            total += day.net;
+            tracked += 1;
+            if day.deducted > 0 {
+                notes.push(Note::Deducted(day.deducted));
+            }
+        }
+        let mut target = schedule.target(date);
+        if let Some(kind) = off.get(&date) {
+            total += target;
+            notes.push(Note::Off(kind.clone(), Some(target)));
+            target = 0;
+        }
+        lines.push(DayLine {
+            date,
+            net: net.map(|day| day.net),
+            target,
+            notes,
+        });
+    }
+    Ok(Period {
+        days: lines,
+        scale,
+        total,
+        tracked,
+    })
+}
+
+/// The month from its `first` day, where days without work are noted as off
+/// or missing.
+pub fn month(
+    store: &dyn Store,
+    config: &Config,
+    first: NaiveDate,
+    today: NaiveDate,
+    now: isize,
+) -> Result<Period, Box<dyn Error>> {
+    let last = time::last_day_of_month(first);
+    let rules = report::parse_break_rules(&config.break_rules)?;
+    let schedule = config::schedule(config)?;
+    let off = off::read_with_holidays(&config.holidays, first, last)?;
+
+    let days = totals::days(store, first, last, today, now, &rules)?;
+    let scale = scale(&days, &schedule);
+    let mut total = 0;
+    let mut tracked = 0;
+    let mut lines = Vec::new();
+    for (date, net) in days {
+        let target = if off.contains_key(&date) {
+            0
+        } else {
+            schedule.target(date)
+        };
+        let notes = match net {
+            Some(day) => {
+                total += day.net;
+                tracked += 1;
+                (day.deducted > 0)
+                    .then_some(Note::Deducted(day.deducted))
+                    .into_iter()
+                    .collect()
+            }
+            None => match off.get(&date) {
+                Some(kind) => vec![Note::Off(kind.clone(), None)],
+                None if target > 0 && date <= today => vec![Note::Missing],
+                None => Vec::new(),
+            },
+        };
+        lines.push(DayLine {
+            date,
+            net: net.map(|day| day.net),
+            target,
+            notes,
+        });
+    }
+    Ok(Period {
+        days: lines,
+        scale,
+        total,
+        tracked,
+    })
+}
+
+/// The last `weeks` weeks up to the one of `today`, oldest first.
+pub fn last_weeks(today: NaiveDate, weeks: u32) -> Vec<(NaiveDate, NaiveDate)> {
+    let monday = today.week(Weekday::Mon).first_day();
+    (0..weeks)
+        .rev()
+        .map(|back| {
+            let first = monday - Days::new(7 * u64::from(back));
+            (first, first + Days::new(6))
+        })
+        .collect()
+}
+
+/// How a range is headed in a comparison, as an ISO week for `weeks`.
+pub fn range_label((from, to): (NaiveDate, NaiveDate), weeks: bool) -> String {
+    if weeks {
+        let iso_week = from.iso_week();
+        format!("{}-W{:02}", iso_week.year(), iso_week.week())
+    } else {
+        format!("{}..{}", from.format("%m-%d"), to.format("%m-%d"))
+    }
+}
+
+/// The net work of each day of each of `ranges`, `None` for days without
+/// records.
+pub fn compare(
+    store: &dyn Store,
+    ranges: &[(NaiveDate, NaiveDate)],
+    today: NaiveDate,
+    now: isize,
+    rules: &[BreakRule],
+) -> Result<Vec<Vec<Option<isize>>>, Box<dyn Error>> {
+    let mut periods = Vec::new();
+    for (from, to) in ranges {
+        let days = report::days(store, *from, *to, today, now)?;
+        periods.push(
+            days.iter()
+                .map(|day| Some(report::net_duration(day.info.as_ref()?, rules)))
+                .collect(),
+        );
+    }
+    Ok(periods)
+}
+
+/// The daily work of the days up to today, with its 7-day and 30-day
+/// rolling averages. Days without work only count if work was due.
+pub struct Trends {
+    pub dates: Vec<NaiveDate>,
+    pub durations: Vec<Option<isize>>,
+    pub week: Vec<Option<isize>>,
+    pub month: Vec<Option<isize>>,
+}
+
+/// The [`Trends`] of the `shown` days up to `today`.
+pub fn trends(
+    store: &dyn Store,
+    config: &Config,
+    shown: usize,
+    today: NaiveDate,
+    now: isize,
+) -> Result<Trends, Box<dyn Error>> {
+    // Read a month more, so the first averages shown are complete.
+    let from = today - Days::new((shown + 29) as u64 - 1);
+    let days = report::days(store, from, today, today, now)?;
+    let rules = report::parse_break_rules(&config.break_rules)?;
+    let schedule = config::schedule(config)?;
+    let mut durations: Vec<Option<isize>> = days
+        .iter()
+        .map(|day| match &day.info {
+            Some(info) if !info.intervals.is_empty() => Some(report::net_duration(info, &rules)),
+            _ => (schedule.target(day.date) > 0).then_some(0),
+        })
+        .collect();
+    let skip = durations.len() - shown;
+    let week = report::rolling_average(&durations, 7).split_off(skip);
+    let month = report::rolling_average(&durations, 30).split_off(skip);
+    Ok(Trends {
+        dates: days[skip..].iter().map(|day| day.date).collect(),
+        durations: durations.split_off(skip),
+        week,
+        month,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(input: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(input, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn counts_weeks_back_from_the_current_one() {
+        assert_eq!(
+            last_weeks(date("2024-01-03"), 2),
+            [
+                (date("2023-12-25"), date("2023-12-31")),
+                (date("2024-01-01"), date("2024-01-07")),
+            ]
+        );
+        assert_eq!(
+            range_label((date("2023-12-25"), date("2023-12-31")), true),
+            "2023-W52"
+        );
+        assert_eq!(
+            range_label((date("2023-12-25"), date("2024-01-02")), false),
+            "12-25..01-02"
+        );
+    }
+}
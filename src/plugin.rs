@@ -0,0 +1,93 @@
+//! Commands added by other programs: like git and cargo, `azk foo ARGS`
+//! runs `azk-foo ARGS` from the PATH when azk has no `foo` command. The
+//! plugin gets where azk keeps things in the environment, as `AZK_DATA_DIR`,
+//! `AZK_CONFIG_DIR`, `AZK_PROFILE`, `AZK_VERSION` and `AZK_BIN`, and all of
+//! that and the config as a [`Context`] in JSON on stdin.
+
+use std::error::Error;
+use std::ffi::OsString;
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::config;
+use crate::store::{data_dir, DATA_DIR_VAR};
+
+/// The version of [`Context`], raised when fields change or go.
+const CONTEXT_VERSION: u32 = 1;
+
+/// What a plugin is passed on stdin.
+#[derive(Serialize)]
+pub struct Context {
+    pub context_version: u32,
+    /// The version of azk.
+    pub version: String,
+    pub data_dir: PathBuf,
+    pub config_dir: PathBuf,
+    /// The profile in use, if any.
+    pub profile: Option<String>,
+    /// The settings, including defaults, as `azk config list` shows them.
+    pub config: toml::Table,
+    pub today: NaiveDate,
+    /// The arguments after the command.
+    pub args: Vec<String>,
+}
+
+/// The program of the plugin `name` on the PATH, if there is one.
+pub fn find(name: &str) -> Option<PathBuf> {
+    let program = format!("azk-{name}{}", std::env::consts::EXE_SUFFIX);
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(&program))
+        .find(|path| path.is_file())
+}
+
+/// Runs the plugin `name` with `args` and returns its exit code.
+pub fn run(name: &str, args: &[OsString], today: NaiveDate) -> Result<i32, Box<dyn Error>> {
+    let program = find(name).ok_or_else(|| {
+        format!("unknown command '{name}', and there is no azk-{name} on the PATH")
+    })?;
+    let context = Context {
+        context_version: CONTEXT_VERSION,
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        data_dir: data_dir()?,
+        config_dir: config::dir()?,
+        profile: config::profile()?,
+        config: config::list()?,
+        today,
+        args: args
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect(),
+    };
+
+    let mut command = Command::new(&program);
+    command
+        .args(args)
+        .env(DATA_DIR_VAR, &context.data_dir)
+        .env("AZK_CONFIG_DIR", &context.config_dir)
+        .env(
+            config::PROFILE_VAR,
+            context.profile.as_deref().unwrap_or_default(),
+        )
+        .env("AZK_VERSION", &context.version)
+        .stdin(Stdio::piped());
+    if let Ok(exe) = std::env::current_exe() {
+        command.env("AZK_BIN", exe);
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|err| format!("cannot run {}: {err}", program.display()))?;
+    let input = serde_json::to_vec(&context)?;
+    let mut stdin = child.stdin.take().expect("the plugin's stdin is piped");
+    // Plugins need not read it.
+    match stdin.write_all(&input) {
+        Err(err) if err.kind() != ErrorKind::BrokenPipe => Err(err)?,
+        _ => {}
+    }
+    drop(stdin);
+    let status = child.wait()?;
+    Ok(status.code().unwrap_or(1))
+}
@@ -0,0 +1,198 @@
+//! Known projects and the clients they are for, kept in `projects.csv` next
+//! to the records. Work may name any project; listing it here only groups it
+//! under a client and offers its name.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::store::{data_dir, read_work_time, Store};
+use crate::time::{hhmm_to_s, s_to_hhmm};
+
+/// The projects, in the data dir.
+pub const FILE_NAME: &str = "projects.csv";
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Project {
+    pub name: String,
+    /// Empty for a project of no client.
+    pub client: String,
+    /// Archived projects are no longer offered, but still reported.
+    pub archived: bool,
+    /// The hours budgeted for the project in HH:MM, or empty for none.
+    #[serde(default)]
+    pub budget: String,
+}
+
+/// How much of a project's budget is used up.
+pub struct BudgetUse {
+    pub project: String,
+    pub used: isize,
+    pub budget: isize,
+}
+
+impl BudgetUse {
+    pub fn percent(&self) -> isize {
+        self.used * 100 / self.budget.max(1)
+    }
+
+    /// A warning once [`WARN_PERCENT`] of the budget is used.
+    pub fn warning(&self) -> Option<String> {
+        if self.used > self.budget {
+            Some(format!(
+                "{} is {} over its budget",
+                self.project,
+                s_to_hhmm(self.used - self.budget)
+            ))
+        } else if self.percent() >= WARN_PERCENT {
+            Some(format!(
+                "{} has used {}% of its budget",
+                self.project,
+                self.percent()
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// The share of a budget from which on it is warned about.
+pub const WARN_PERCENT: isize = 80;
+
+fn path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(data_dir()?.join(FILE_NAME))
+}
+
+/// Every known project by name.
+pub fn read() -> Result<BTreeMap<String, Project>, Box<dyn Error>> {
+    let path = path()?;
+    let contents = match fs::read(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(err) => Err(format!("{}: {err}", path.display()))?,
+    };
+    let mut rdr = csv::Reader::from_reader(contents.as_slice());
+    let mut projects = BTreeMap::new();
+    for project in rdr.deserialize::<Project>() {
+        let project = project.map_err(|err| format!("{}: {err}", path.display()))?;
+        projects.insert(project.name.clone(), project);
+    }
+    Ok(projects)
+}
+
+/// Replaces all projects, through a temporary file.
+fn write(projects: &BTreeMap<String, Project>) -> Result<(), Box<dyn Error>> {
+    let path = path()?;
+    let tmp_path = path.with_extension("csv.tmp");
+    let file = File::create(&tmp_path)?;
+    let mut wtr = csv::Writer::from_writer(&file);
+    for project in projects.values() {
+        wtr.serialize(project)?;
+    }
+    wtr.flush()?;
+    drop(wtr);
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Adds the project `name` for `client` with a `budget` in HH:MM, or moves
+/// it there, sets its budget and restores it if it is known already.
+pub fn add(
+    store: &dyn Store,
+    name: &str,
+    client: &str,
+    budget: &str,
+) -> Result<(), Box<dyn Error>> {
+    if name.trim().is_empty() {
+        Err("the project needs a name")?
+    }
+    if !budget.is_empty() {
+        hhmm_to_s(budget)?;
+    }
+    let _lock = store.lock()?;
+    let mut projects = read()?;
+    projects.insert(
+        name.to_owned(),
+        Project {
+            name: name.to_owned(),
+            client: client.to_owned(),
+            archived: false,
+            budget: budget.to_owned(),
+        },
+    );
+    write(&projects)
+}
+
+/// Archives the project `name`, which must be known.
+pub fn archive(store: &dyn Store, name: &str) -> Result<(), Box<dyn Error>> {
+    let _lock = store.lock()?;
+    let mut projects = read()?;
+    projects
+        .get_mut(name)
+        .ok_or_else(|| format!("unknown project '{name}', see 'azk project list'"))?
+        .archived = true;
+    write(&projects)
+}
+
+/// Sums per-project `totals` per client, with the projects of no client and
+/// the unknown ones under `None`.
+pub fn client_totals(
+    totals: &BTreeMap<Option<String>, isize>,
+    projects: &BTreeMap<String, Project>,
+) -> BTreeMap<Option<String>, Vec<(Option<String>, isize)>> {
+    let mut clients: BTreeMap<Option<String>, Vec<(Option<String>, isize)>> = BTreeMap::new();
+    for (project, total) in totals {
+        let client = project
+            .as_ref()
+            .and_then(|project| projects.get(project))
+            .map(|project| project.client.clone())
+            .filter(|client| !client.is_empty());
+        clients
+            .entry(client)
+            .or_default()
+            .push((project.clone(), *total));
+    }
+    clients
+}
+
+/// The use of the budget of every project that has one, over all recorded
+/// days. Only `today` counts an unfinished session up to `now`.
+pub fn budgets(
+    store: &dyn Store,
+    today: NaiveDate,
+    now: isize,
+) -> Result<Vec<BudgetUse>, Box<dyn Error>> {
+    let mut budgets = Vec::new();
+    for project in read()?.into_values() {
+        if !project.budget.is_empty() {
+            budgets.push(BudgetUse {
+                project: project.name,
+                used: 0,
+                budget: hhmm_to_s(&project.budget)?,
+            });
+        }
+    }
+    if budgets.is_empty() {
+        return Ok(budgets);
+    }
+    for date in store.list_days()? {
+        let Some(day) = read_work_time(store, date, (date == today).then_some(now))? else {
+            continue;
+        };
+        for interval in &day.intervals {
+            if let Some(budget) = budgets
+                .iter_mut()
+                .find(|budget| interval.project.as_ref() == Some(&budget.project))
+            {
+                budget.used += interval.duration();
+            }
+        }
+    }
+    Ok(budgets)
+}
@@ -0,0 +1,298 @@
+//! Removing the days older than the retention period, after archiving them
+//! as day files in a tar.zst and adding their monthly totals to
+//! `purged-totals.csv`, which the balance, stats and reports count the
+//! purged days by, so that long-term statistics survive.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, Config};
+use crate::off;
+use crate::report::{self, GroupBy};
+use crate::store::{self, data_dir, Store};
+
+/// The monthly totals of the purged days, in the data dir.
+pub const TOTALS_FILE: &str = "purged-totals.csv";
+
+/// The totals of the purged days of a month.
+#[derive(Deserialize, Serialize)]
+pub struct MonthTotal {
+    /// As in `2024-03`.
+    pub month: String,
+    /// The first and the last purged day with records. Days up to the last
+    /// are not counted again, as when a purge is repeated after failing.
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+    /// How many days had records.
+    pub days: usize,
+    /// The net work, in seconds.
+    pub seconds: isize,
+    /// The targets of those days by the schedule when they were purged, but
+    /// for days off, which are credited with theirs.
+    pub target: isize,
+}
+
+/// The monthly totals of the purged days, by month.
+pub fn read_totals() -> Result<BTreeMap<String, MonthTotal>, Box<dyn Error>> {
+    let path = data_dir()?.join(TOTALS_FILE);
+    let mut totals = BTreeMap::new();
+    match csv::Reader::from_path(&path) {
+        Ok(mut rdr) => {
+            for total in rdr.deserialize() {
+                let total: MonthTotal =
+                    total.map_err(|err| format!("{}: {err}", path.display()))?;
+                totals.insert(total.month.clone(), total);
+            }
+        }
+        Err(err) if matches!(err.kind(), csv::ErrorKind::Io(err) if err.kind() == ErrorKind::NotFound) =>
+            {}
+        Err(err) => Err(format!("{}: {err}", path.display()))?,
+    }
+    Ok(totals)
+}
+
+/// The monthly totals of the purged days, of the months whose purged days
+/// are all from `from` to `to`.
+pub fn totals_between(from: NaiveDate, to: NaiveDate) -> Result<Vec<MonthTotal>, Box<dyn Error>> {
+    Ok(read_totals()?
+        .into_values()
+        .filter(|total| from <= total.first && total.last <= to)
+        .collect())
+}
+
+/// The first day kept when keeping the days of the last `age`, as in `2y`,
+/// `18m`, `6w` or `90d`, counted back from `today`.
+pub fn cutoff(age: &str, today: NaiveDate) -> Result<NaiveDate, Box<dyn Error>> {
+    let invalid = || format!("invalid age '{age}', expected a number and y, m, w or d as in 2y");
+    let unit = age.chars().last().ok_or_else(invalid)?;
+    let count: u32 = age[..age.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+    let cutoff = match unit {
+        'y' => today.checked_sub_months(Months::new(count.saturating_mul(12))),
+        'm' => today.checked_sub_months(Months::new(count)),
+        'w' => today.checked_sub_days(chrono::Days::new(u64::from(count) * 7)),
+        'd' => today.checked_sub_days(chrono::Days::new(count.into())),
+        _ => Err(invalid())?,
+    };
+    Ok(cutoff.ok_or_else(invalid)?)
+}
+
+/// What was purged.
+pub struct Purged {
+    pub days: Vec<NaiveDate>,
+    /// Where the days were archived to, if they were.
+    pub archive: Option<PathBuf>,
+}
+
+/// Writes the records of `days` as day files of the csv store to a tar.zst
+/// in `dir`, named after the first and the last.
+fn archive(store: &dyn Store, days: &[NaiveDate], dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let (Some(first), Some(last)) = (days.first(), days.last()) else {
+        Err("no days to archive")?
+    };
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("azk-purged-{first}-{last}.tar.zst"));
+    let mut staging = path.clone().into_os_string();
+    staging.push(".tmp");
+    let staging = PathBuf::from(staging);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+    let result = archive_from(store, days, &staging, &path);
+    fs::remove_dir_all(&staging)?;
+    result?;
+    Ok(path)
+}
+
+fn archive_from(
+    store: &dyn Store,
+    days: &[NaiveDate],
+    staging: &Path,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let staged = store::open_backend_in("csv", staging.to_owned())?;
+    for date in days {
+        staged.write_day(*date, &store.read_day(*date)?.unwrap_or_default())?;
+    }
+    let file = File::create_new(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let mut archive = tar::Builder::new(zstd::Encoder::new(file, 0)?);
+    for date in days {
+        let file = staged.file(*date);
+        archive.append_path_with_name(&file, file.strip_prefix(staging)?)?;
+    }
+    archive.into_inner()?.finish()?.sync_all()?;
+    Ok(())
+}
+
+/// Adds the monthly totals of `days` to those in [`TOTALS_FILE`], but for
+/// those counted already.
+fn add_totals(
+    store: &dyn Store,
+    config: &Config,
+    days: &[NaiveDate],
+) -> Result<(), Box<dyn Error>> {
+    let (Some(first), Some(last)) = (days.first(), days.last()) else {
+        return Ok(());
+    };
+    let path = data_dir()?.join(TOTALS_FILE);
+    let mut totals = read_totals()?;
+
+    let rules = report::parse_break_rules(&config.break_rules)?;
+    let schedule = config::schedule(config)?;
+    let off = off::read_with_holidays(&config.holidays, *first, *last)?;
+    // The days purged are long past, so none is ongoing.
+    for day in report::days(store, *first, *last, Local::now().date_naive(), 0)? {
+        let Some(info) = &day.info else {
+            continue;
+        };
+        let month = report::label(day.date, GroupBy::Month);
+        let total = totals.entry(month.clone()).or_insert(MonthTotal {
+            month,
+            first: day.date,
+            last: day.date,
+            days: 0,
+            seconds: 0,
+            target: 0,
+        });
+        if total.days > 0 && day.date <= total.last {
+            continue;
+        }
+        total.first = total.first.min(day.date);
+        total.last = day.date;
+        total.days += 1;
+        total.seconds += report::net_duration(info, &rules);
+        if !off.contains_key(&day.date) {
+            total.target += schedule.target(day.date);
+        }
+    }
+
+    let mut tmp_path = path.clone().into_os_string();
+    tmp_path.push(format!(".{}.tmp", std::process::id()));
+    let mut wtr = csv::Writer::from_path(&tmp_path)?;
+    for total in totals.values() {
+        wtr.serialize(total)?;
+    }
+    wtr.flush()?;
+    drop(wtr);
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// The days of `store` before `cutoff`.
+pub fn days_before(store: &dyn Store, cutoff: NaiveDate) -> Result<Vec<NaiveDate>, Box<dyn Error>> {
+    Ok(store
+        .list_days()?
+        .into_iter()
+        .filter(|date| *date < cutoff)
+        .collect())
+}
+
+/// Removes the days of `store` before `cutoff`, after archiving them to a
+/// tar.zst in `archive_dir` if given, and adding up their monthly `totals`
+/// if asked to.
+pub fn purge(
+    store: &dyn Store,
+    config: &Config,
+    cutoff: NaiveDate,
+    archive_dir: Option<&Path>,
+    totals: bool,
+) -> Result<Purged, Box<dyn Error>> {
+    let _lock = store.lock()?;
+    let days = days_before(store, cutoff)?;
+    if days.is_empty() {
+        return Ok(Purged {
+            days,
+            archive: None,
+        });
+    }
+    let archive = archive_dir
+        .map(|dir| archive(store, &days, dir))
+        .transpose()?;
+    if totals {
+        add_totals(store, config, &days)?;
+    }
+    for date in &days {
+        store.write_day(*date, &[])?;
+    }
+    Ok(Purged { days, archive })
+}
+
+/// Purges as `retention` says, if `auto_purge` is on and it is set, see
+/// [`purge`]. Fails without `retention_archive`, as the days would be lost.
+pub fn apply(
+    store: &dyn Store,
+    config: &Config,
+    today: NaiveDate,
+) -> Result<Purged, Box<dyn Error>> {
+    if !config.auto_purge || config.retention.is_empty() {
+        return Ok(Purged {
+            days: Vec::new(),
+            archive: None,
+        });
+    }
+    if config.retention_archive.is_empty() {
+        Err("auto_purge needs retention_archive set, not purging")?
+    }
+    purge(
+        store,
+        config,
+        cutoff(&config.retention, today)?,
+        Some(Path::new(&config.retention_archive)),
+        config.retention_totals,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(input: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(input, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn counts_back_by_unit() {
+        let today = date("2024-05-15");
+        assert_eq!(cutoff("2y", today).unwrap(), date("2022-05-15"));
+        assert_eq!(cutoff("18m", today).unwrap(), date("2022-11-15"));
+        assert_eq!(cutoff("6w", today).unwrap(), date("2024-04-03"));
+        assert_eq!(cutoff("90d", today).unwrap(), date("2024-02-15"));
+        assert_eq!(cutoff("0d", today).unwrap(), today);
+    }
+
+    #[test]
+    fn clamps_to_the_end_of_shorter_months() {
+        assert_eq!(
+            cutoff("1y", date("2024-02-29")).unwrap(),
+            date("2023-02-28")
+        );
+        assert_eq!(
+            cutoff("1m", date("2024-03-31")).unwrap(),
+            date("2024-02-29")
+        );
+        assert_eq!(
+            cutoff("18m", date("2024-03-31")).unwrap(),
+            date("2022-09-30")
+        );
+        assert_eq!(
+            cutoff("1d", date("2024-01-01")).unwrap(),
+            date("2023-12-31")
+        );
+    }
+
+    #[test]
+    fn refuses_invalid_ages() {
+        let today = date("2024-05-15");
+        for age in ["", "y", "2", "2x", "-1d", "1.5y", "2 y"] {
+            assert!(cutoff(age, today).is_err(), "{age}");
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Ad-hoc SQL over the records, loaded into an in-memory SQLite database
+//! with the tables:
+//!
+//! - `intervals(date, start, end, seconds, project, note, ongoing)`
+//! - `breaks(date, start, end, seconds, category)`
+//! - `days(date, start, end, breaks, gross, seconds)`
+//!
+//! Dates are `YYYY-MM-DD`, the start and end of intervals and breaks are
+//! `YYYY-MM-DD HH:MM:SS` on the wall clock, and those of days are `HH:MM`.
+//! The `seconds` of a day are its work after the deduction for short breaks.
+
+use std::error::Error;
+
+use chrono::{DateTime, FixedOffset};
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection};
+
+use crate::report::{self, BreakRule, Day};
+use crate::time::s_to_hhmm;
+
+const SCHEMA: &str = "
+CREATE TABLE intervals (
+    date TEXT NOT NULL,
+    start TEXT NOT NULL,
+    end TEXT NOT NULL,
+    seconds INTEGER NOT NULL,
+    project TEXT,
+    note TEXT,
+    ongoing INTEGER NOT NULL
+);
+CREATE TABLE breaks (
+    date TEXT NOT NULL,
+    start TEXT NOT NULL,
+    end TEXT NOT NULL,
+    seconds INTEGER NOT NULL,
+    category TEXT
+);
+CREATE TABLE days (
+    date TEXT NOT NULL PRIMARY KEY,
+    start TEXT NOT NULL,
+    end TEXT NOT NULL,
+    breaks INTEGER NOT NULL,
+    gross INTEGER NOT NULL,
+    seconds INTEGER NOT NULL
+);
+";
+
+/// The result of a query, every value as text and NULL as empty.
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+fn wall(timestamp: &DateTime<FixedOffset>) -> String {
+    timestamp.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// A database of the days with work among `days`, with their net work after
+/// the deduction by `rules`.
+fn load(days: &[Day], rules: &[BreakRule]) -> Result<Connection, Box<dyn Error>> {
+    let mut conn = Connection::open_in_memory()?;
+    conn.execute_batch(SCHEMA)?;
+    let tx = conn.transaction()?;
+    {
+        let mut intervals = tx.prepare("INSERT INTO intervals VALUES (?, ?, ?, ?, ?, ?, ?)")?;
+        let mut breaks = tx.prepare("INSERT INTO breaks VALUES (?, ?, ?, ?, ?)")?;
+        let mut day_rows = tx.prepare("INSERT INTO days VALUES (?, ?, ?, ?, ?, ?)")?;
+        for day in days {
+            let Some(info) = day.info.as_ref().filter(|info| !info.intervals.is_empty()) else {
+                continue;
+            };
+            let date = day.date.format("%Y-%m-%d").to_string();
+            for interval in &info.intervals {
+                intervals.execute(params![
+                    date,
+                    wall(&interval.started_at),
+                    wall(&interval.ended_at),
+                    interval.duration() as i64,
+                    interval.project,
+                    interval.note,
+                    interval.ongoing,
+                ])?;
+            }
+            // A break lies between the intervals around it.
+            for (pause, around) in info.breaks.iter().zip(info.intervals.windows(2)) {
+                breaks.execute(params![
+                    date,
+                    wall(&around[0].ended_at),
+                    wall(&around[1].started_at),
+                    pause.duration() as i64,
+                    pause.category,
+                ])?;
+            }
+            day_rows.execute(params![
+                date,
+                s_to_hhmm(info.start()),
+                s_to_hhmm(info.end()),
+                info.break_duration() as i64,
+                info.duration() as i64,
+                report::net_duration(info, rules) as i64,
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(conn)
+}
+
+/// Runs `sql` over `days`, see the module documentation for the tables.
+pub fn run(days: &[Day], rules: &[BreakRule], sql: &str) -> Result<Table, Box<dyn Error>> {
+    let conn = load(days, rules)?;
+    let mut statement = conn.prepare(sql)?;
+    let columns: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(str::to_owned)
+        .collect();
+    let mut rows = Vec::new();
+    let mut result = statement.query([])?;
+    while let Some(row) = result.next()? {
+        let mut values = Vec::with_capacity(columns.len());
+        for index in 0..columns.len() {
+            values.push(match row.get_ref(index)? {
+                ValueRef::Null => String::new(),
+                ValueRef::Integer(value) => value.to_string(),
+                ValueRef::Real(value) => value.to_string(),
+                ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+                ValueRef::Blob(blob) => format!("<{} bytes>", blob.len()),
+            });
+        }
+        rows.push(values);
+    }
+    Ok(Table { columns, rows })
+}
@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use chrono::{Datelike, NaiveDate};
+
+use crate::model::DayInfo;
+use crate::purge::MonthTotal;
+use crate::store::{read_work_time, DayError, Store};
+use crate::time::{hhmm_to_s, s_to_hhmm};
+
+pub struct Day {
+    pub date: NaiveDate,
+    pub info: Option<DayInfo>,
+}
+
+/// Reads the days from `from` to `to` (inclusive). Days without records have no info.
+/// Only `today` counts an unfinished session up to `now`.
+pub fn days(
+    store: &dyn Store,
+    from: NaiveDate,
+    to: NaiveDate,
+    today: NaiveDate,
+    now: isize,
+) -> Result<Vec<Day>, Box<dyn Error>> {
+    let dates: Vec<NaiveDate> = from.iter_days().take_while(|date| *date <= to).collect();
+    let infos = read_days(store, &dates, today, now)?;
+    Ok(dates
+        .into_iter()
+        .zip(infos)
+        .map(|(date, info)| Day { date, info })
+        .collect())
+}
+
+/// How many days a thread reads at least, below which starting it costs
+/// more than it saves.
+const DAYS_PER_THREAD: usize = 32;
+
+/// Reads the work time of each of `dates`, see [`read_work_time`], spread
+/// over a thread per core for long ranges. Only `today` counts an
+/// unfinished session up to `now`.
+pub fn read_days(
+    store: &dyn Store,
+    dates: &[NaiveDate],
+    today: NaiveDate,
+    now: isize,
+) -> Result<Vec<Option<DayInfo>>, DayError> {
+    let read = |dates: &[NaiveDate]| {
+        dates
+            .iter()
+            .map(|date| read_work_time(store, *date, (*date == today).then_some(now)))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let threads = std::thread::available_parallelism()
+        .map_or(1, usize::from)
+        .min(dates.len() / DAYS_PER_THREAD);
+    if threads <= 1 {
+        return read(dates);
+    }
+    let chunk = dates.len().div_ceil(threads);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = dates
+            .chunks(chunk)
+            .map(|dates| scope.spawn(move || read(dates)))
+            .collect();
+        let mut infos = Vec::with_capacity(dates.len());
+        for handle in handles {
+            infos.extend(handle.join().expect("reading days does not panic")?);
+        }
+        Ok(infos)
+    })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    Day,
+    Week,
+    Month,
+}
+
+/// The day, week or month that `date` belongs to, as in `2024-03-01`,
+/// `2024-W09` or `2024-03`.
+pub fn label(date: NaiveDate, group_by: GroupBy) -> String {
+    match group_by {
+        GroupBy::Day => format!("{}", date.format("%Y-%m-%d")),
+        GroupBy::Week => {
+            let iso_week = date.iso_week();
+            format!("{}-W{:02}", iso_week.year(), iso_week.week())
+        }
+        GroupBy::Month => format!("{}", date.format("%Y-%m")),
+    }
+}
+
+/// Sums the net durations of consecutive days sharing the same day, week or
+/// month label, see [`net_duration`].
+pub fn totals(days: &[Day], group_by: GroupBy, rules: &[BreakRule]) -> Vec<(String, isize)> {
+    let mut totals: Vec<(String, isize)> = Vec::new();
+    for day in days {
+        let label = label(day.date, group_by);
+        let duration = day
+            .info
+            .as_ref()
+            .map_or(0, |info| net_duration(info, rules));
+        match totals.last_mut() {
+            Some((last, total)) if *last == label => *total += duration,
+            _ => totals.push((label, duration)),
+        }
+    }
+    totals
+}
+
+/// Sums the durations of the intervals of `infos` per project.
+pub fn project_totals<'a>(
+    infos: impl IntoIterator<Item = &'a DayInfo>,
+) -> BTreeMap<Option<String>, isize> {
+    let mut totals = BTreeMap::new();
+    for interval in infos.into_iter().flat_map(|info| &info.intervals) {
+        *totals.entry(interval.project.clone()).or_insert(0) += interval.duration();
+    }
+    totals
+}
+
+/// Sums the durations of the breaks of `infos` per kind of break.
+pub fn break_totals<'a>(
+    infos: impl IntoIterator<Item = &'a DayInfo>,
+) -> BTreeMap<Option<String>, isize> {
+    let mut totals = BTreeMap::new();
+    for b in infos.into_iter().flat_map(|info| &info.breaks) {
+        *totals.entry(b.category.clone()).or_insert(0) += b.duration();
+    }
+    totals
+}
+
+/// A break that is required once work exceeds a duration, as in "more than
+/// 06:00 of work needs 00:30 of breaks".
+#[derive(Clone, Copy, Debug)]
+pub struct BreakRule {
+    pub after: isize,
+    pub minimum: isize,
+}
+
+impl fmt::Display for BreakRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", s_to_hhmm(self.after), s_to_hhmm(self.minimum))
+    }
+}
+
+/// Parses rules written as `06:00=00:30, 09:00=00:45`, see [`BreakRule`].
+pub fn parse_break_rules(rules: &str) -> Result<Vec<BreakRule>, Box<dyn Error>> {
+    rules
+        .split(',')
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .map(|rule| {
+            let (after, minimum) = rule
+                .split_once('=')
+                .ok_or_else(|| format!("invalid break rule '{rule}', expected HH:MM=HH:MM"))?;
+            Ok(BreakRule {
+                after: hhmm_to_s(after.trim())?,
+                minimum: hhmm_to_s(minimum.trim())?,
+            })
+        })
+        .collect()
+}
+
+/// The breaks the strictest rule that applies to the day requires.
+pub fn required_break(info: &DayInfo, rules: &[BreakRule]) -> isize {
+    rules
+        .iter()
+        .filter(|rule| info.duration() > rule.after)
+        .map(|rule| rule.minimum)
+        .max()
+        .unwrap_or(0)
+}
+
+/// The work time taken off the day because its breaks fall short of the
+/// strictest rule that applies. Only as much is taken off as the work
+/// exceeds the rule by, so that the rule never pushes it below its limit.
+pub fn deduction(info: &DayInfo, rules: &[BreakRule]) -> isize {
+    let gross = info.duration();
+    let breaks = info.break_duration();
+    rules
+        .iter()
+        .filter(|rule| gross > rule.after)
+        .map(|rule| (rule.minimum - breaks).min(gross - rule.after).max(0))
+        .max()
+        .unwrap_or(0)
+}
+
+/// The work time of the day after the [`deduction`].
+pub fn net_duration(info: &DayInfo, rules: &[BreakRule]) -> isize {
+    info.duration() - deduction(info, rules)
+}
+
+/// Figures about the tracked days of a range, see [`stats`].
+pub struct Stats {
+    /// The days with any work.
+    pub days: usize,
+    pub total: isize,
+    pub average: isize,
+    pub median: isize,
+    /// The average times of the first start and the last stop.
+    pub average_start: isize,
+    pub average_end: isize,
+    pub longest: (NaiveDate, isize),
+    /// The most days with work in a row, and the last of them.
+    pub streak: (usize, NaiveDate),
+    pub breaks: isize,
+    /// How many of the days were purged, of which only the monthly totals
+    /// are kept, see [`Stats::add_purged`].
+    pub purged: usize,
+}
+
+impl Stats {
+    /// Counts the days of the `purged` monthly totals towards the days, the
+    /// total and the average, which are all they are known for.
+    pub fn add_purged(&mut self, purged: &[MonthTotal]) {
+        for total in purged {
+            self.days += total.days;
+            self.total += total.seconds;
+            self.purged += total.days;
+        }
+        self.average = self.total / self.days as isize;
+    }
+}
+
+/// Computes the [`Stats`] of the days with work among `days`, with the net
+/// durations after the [`deduction`], or nothing if no day had work.
+pub fn stats(days: &[Day], rules: &[BreakRule]) -> Option<Stats> {
+    let tracked: Vec<(NaiveDate, &DayInfo)> = days
+        .iter()
+        .filter_map(|day| Some((day.date, day.info.as_ref()?)))
+        .filter(|(_, info)| !info.intervals.is_empty())
+        .collect();
+    let count = tracked.len() as isize;
+    if count == 0 {
+        return None;
+    }
+
+    let mut durations: Vec<isize> = tracked
+        .iter()
+        .map(|(_, info)| net_duration(info, rules))
+        .collect();
+    let total = durations.iter().sum();
+    let longest = tracked
+        .iter()
+        .zip(&durations)
+        .map(|((date, _), duration)| (*date, *duration))
+        .max_by_key(|(date, duration)| (*duration, std::cmp::Reverse(*date)))?;
+    durations.sort_unstable();
+    let middle = durations.len() / 2;
+    let median = if durations.len().is_multiple_of(2) {
+        (durations[middle - 1] + durations[middle]) / 2
+    } else {
+        durations[middle]
+    };
+
+    let mut streak = (0, tracked[0].0);
+    let mut current = 0;
+    for (index, (date, _)) in tracked.iter().enumerate() {
+        let follows = index > 0 && tracked[index - 1].0.succ_opt() == Some(*date);
+        current = if follows { current + 1 } else { 1 };
+        if current > streak.0 {
+            streak = (current, *date);
+        }
+    }
+
+    Some(Stats {
+        days: tracked.len(),
+        total,
+        average: total / count,
+        median,
+        average_start: tracked.iter().map(|(_, info)| info.start()).sum::<isize>() / count,
+        average_end: tracked.iter().map(|(_, info)| info.end()).sum::<isize>() / count,
+        longest,
+        streak,
+        breaks: tracked.iter().map(|(_, info)| info.break_duration()).sum(),
+        purged: 0,
+    })
+}
+
+/// The average of the counted days among the `window` days up to each of
+/// `durations`, where days that do not count are `None`. Windows without any
+/// counted day have no average.
+pub fn rolling_average(durations: &[Option<isize>], window: usize) -> Vec<Option<isize>> {
+    (0..durations.len())
+        .map(|end| {
+            let counted: Vec<isize> = durations[(end + 1).saturating_sub(window)..=end]
+                .iter()
+                .flatten()
+                .copied()
+                .collect();
+            (!counted.is_empty()).then(|| counted.iter().sum::<isize>() / counted.len() as isize)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures;
+
+    fn rules() -> Vec<BreakRule> {
+        parse_break_rules("06:00=00:30, 09:00=00:45").unwrap()
+    }
+
+    #[test]
+    fn parses_break_rules() {
+        let rules = rules();
+        assert_eq!(rules.len(), 2);
+        assert_eq!((rules[1].after, rules[1].minimum), (9 * 3600, 45 * 60));
+        assert!(parse_break_rules("06:00").is_err());
+        assert!(parse_break_rules("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn deducts_nothing_up_to_the_limit_or_with_enough_breaks() {
+        assert_eq!(
+            deduction(&fixtures::info("2024-03-01", &["09:00", "15:00"]), &rules()),
+            0
+        );
+        assert_eq!(
+            deduction(
+                &fixtures::info("2024-03-01", &["08:00", "12:00", "12:30", "15:00"]),
+                &rules()
+            ),
+            0
+        );
+        assert_eq!(
+            deduction(&fixtures::info("2024-03-01", &["08:00", "18:00"]), &[]),
+            0
+        );
+    }
+
+    #[test]
+    fn deducts_no_more_than_the_work_exceeds_the_limit_by() {
+        let info = fixtures::info("2024-03-01", &["08:00", "14:10"]);
+        assert_eq!(deduction(&info, &rules()), 10 * 60);
+        assert_eq!(net_duration(&info, &rules()), 6 * 3600);
+    }
+
+    #[test]
+    fn deducts_the_missing_break_of_the_strictest_rule() {
+        // 7h with a 15m break.
+        let info = fixtures::info("2024-03-01", &["08:00", "12:00", "12:15", "15:15"]);
+        assert_eq!(deduction(&info, &rules()), 15 * 60);
+        // 10h with a 30m break, short of the 45m after 9h.
+        let info = fixtures::info("2024-03-01", &["07:00", "12:00", "12:30", "17:30"]);
+        assert_eq!(deduction(&info, &rules()), 15 * 60);
+        assert_eq!(required_break(&info, &rules()), 45 * 60);
+    }
+}
@@ -0,0 +1,202 @@
+//! Rounding of durations for reports and exports, as in billing by the
+//! quarter hour. The records themselves are never rounded.
+
+use std::error::Error;
+
+use chrono::Duration;
+
+use crate::model::Interval;
+use crate::report::Day;
+
+/// What gets rounded, as in `config::Config::rounding_per`.
+pub const SCOPES: [&str; 2] = ["interval", "day"];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Nearest,
+    Up,
+    Down,
+}
+
+#[derive(Clone, Copy)]
+pub struct Rounding {
+    mode: Mode,
+    /// The step rounded to, in seconds.
+    step: isize,
+    /// Whether the total of a day is rounded rather than each interval.
+    per_day: bool,
+}
+
+impl Rounding {
+    /// Parses a policy like `nearest:15`, `up:6` or `down:5`, in minutes, to
+    /// apply per interval or per day as in [`SCOPES`]. An empty policy or
+    /// `none` is no rounding.
+    pub fn parse(policy: &str, per: &str) -> Result<Option<Rounding>, Box<dyn Error>> {
+        let policy = policy.trim();
+        if policy.is_empty() || policy == "none" {
+            return Ok(None);
+        }
+        let invalid =
+            || format!("invalid rounding '{policy}', expected e.g. nearest:15, up:6 or down:5");
+        let (mode, minutes) = policy.split_once(':').ok_or_else(invalid)?;
+        let mode = match mode {
+            "nearest" => Mode::Nearest,
+            "up" => Mode::Up,
+            "down" => Mode::Down,
+            _ => Err(invalid())?,
+        };
+        let minutes: isize = minutes.parse().map_err(|_| invalid())?;
+        if !(1..=60).contains(&minutes) {
+            Err(invalid())?
+        }
+        let per_day = match per {
+            "interval" => false,
+            "day" => true,
+            _ => Err(format!(
+                "unknown rounding scope '{per}', expected one of {}",
+                SCOPES.join(", ")
+            ))?,
+        };
+        Ok(Some(Rounding {
+            mode,
+            step: minutes * 60,
+            per_day,
+        }))
+    }
+
+    /// Rounds `seconds` to the step.
+    pub fn round(&self, seconds: isize) -> isize {
+        let down = seconds - seconds.rem_euclid(self.step);
+        match self.mode {
+            Mode::Down => down,
+            Mode::Up if down == seconds => down,
+            Mode::Up => down + self.step,
+            Mode::Nearest if (seconds - down) * 2 >= self.step => down + self.step,
+            Mode::Nearest => down,
+        }
+    }
+
+    /// Rounds the intervals of `days`, moving their ends, so everything
+    /// reported or exported from them is rounded. Rounding per day changes
+    /// the last intervals of the day by what the day's total is rounded by.
+    pub fn apply(&self, days: &mut [Day]) {
+        for info in days.iter_mut().filter_map(|day| day.info.as_mut()) {
+            if !self.per_day {
+                for interval in &mut info.intervals {
+                    let change = self.round(interval.elapsed) - interval.elapsed;
+                    lengthen(interval, change);
+                }
+                continue;
+            }
+            let total = info.duration();
+            let mut change = self.round(total) - total;
+            // Shortening takes from the intervals before once the last is
+            // used up.
+            for interval in info.intervals.iter_mut().rev() {
+                let applied = change.max(-interval.elapsed);
+                lengthen(interval, applied);
+                change -= applied;
+                if change == 0 {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn lengthen(interval: &mut Interval, seconds: isize) {
+    interval.elapsed += seconds;
+    interval.end += seconds;
+    interval.ended_at += Duration::seconds(seconds as i64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures::day;
+
+    fn elapsed(day: &Day) -> Vec<isize> {
+        let info = day.info.as_ref().unwrap();
+        info.intervals
+            .iter()
+            .map(|interval| interval.elapsed)
+            .collect()
+    }
+
+    #[test]
+    fn parses_policies() {
+        assert!(Rounding::parse("", "interval").unwrap().is_none());
+        assert!(Rounding::parse("none", "day").unwrap().is_none());
+        let rounding = Rounding::parse("up:6", "day").unwrap().unwrap();
+        assert_eq!((rounding.step, rounding.per_day), (360, true));
+        for policy in ["nearest", "nearest:0", "nearest:61", "sideways:15", "up:x"] {
+            assert!(Rounding::parse(policy, "interval").is_err(), "{policy}");
+        }
+        assert!(Rounding::parse("up:15", "week").is_err());
+    }
+
+    #[test]
+    fn rounds_to_the_step() {
+        let nearest = Rounding::parse("nearest:15", "interval").unwrap().unwrap();
+        assert_eq!(nearest.round(7 * 60 + 29), 0);
+        assert_eq!(nearest.round(7 * 60 + 30), 15 * 60);
+        assert_eq!(nearest.round(15 * 60), 15 * 60);
+        let up = Rounding::parse("up:6", "interval").unwrap().unwrap();
+        assert_eq!(up.round(1), 6 * 60);
+        assert_eq!(up.round(6 * 60), 6 * 60);
+        assert_eq!(up.round(0), 0);
+        let down = Rounding::parse("down:5", "interval").unwrap().unwrap();
+        assert_eq!(down.round(5 * 60 - 1), 0);
+        assert_eq!(down.round(61 * 60), 60 * 60);
+    }
+
+    #[test]
+    fn rounds_each_interval() {
+        let mut days = [day(
+            "2024-03-01",
+            &["08:00:00", "08:07:00", "09:00:00", "09:08:00"],
+        )];
+        Rounding::parse("nearest:15", "interval")
+            .unwrap()
+            .unwrap()
+            .apply(&mut days);
+        assert_eq!(elapsed(&days[0]), [0, 15 * 60]);
+        let info = days[0].info.as_ref().unwrap();
+        assert_eq!(info.intervals[1].end, 9 * 3600 + 15 * 60);
+    }
+
+    #[test]
+    fn rounds_the_day_from_its_last_intervals() {
+        // 22 minutes, rounded down to 15, taken from the last interval.
+        let mut days = [day(
+            "2024-03-01",
+            &["08:00:00", "08:10:00", "09:00:00", "09:12:00"],
+        )];
+        Rounding::parse("down:15", "day")
+            .unwrap()
+            .unwrap()
+            .apply(&mut days);
+        assert_eq!(elapsed(&days[0]), [10 * 60, 5 * 60]);
+
+        // 8 minutes, rounded down to nothing, taken from both.
+        let mut days = [day(
+            "2024-03-01",
+            &["08:00:00", "08:03:00", "09:00:00", "09:05:00"],
+        )];
+        Rounding::parse("down:15", "day")
+            .unwrap()
+            .unwrap()
+            .apply(&mut days);
+        assert_eq!(elapsed(&days[0]), [0, 0]);
+
+        let mut days = [day(
+            "2024-03-01",
+            &["08:00:00", "08:10:00", "09:00:00", "09:12:00"],
+        )];
+        Rounding::parse("up:15", "day")
+            .unwrap()
+            .unwrap()
+            .apply(&mut days);
+        assert_eq!(elapsed(&days[0]), [10 * 60, 20 * 60]);
+    }
+}
@@ -0,0 +1,76 @@
+use std::error::Error;
+use std::fmt;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+
+use crate::time::{hhmm_to_s, s_to_hhmm};
+
+/// The target work duration of each weekday, in seconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+    /// Indexed by the days from Monday.
+    targets: [isize; 7],
+}
+
+fn parse_weekday(input: &str) -> Result<Weekday, Box<dyn Error>> {
+    Ok(input
+        .trim()
+        .parse::<Weekday>()
+        .map_err(|_| format!("invalid weekday '{}', expected e.g. mon", input.trim()))?)
+}
+
+impl Schedule {
+    /// Parses targets written as `mon-thu=08:00, fri=04:00, sat-sun=00:00`.
+    /// Weekdays that are left out keep `default`, weekends nothing.
+    pub fn parse(schedule: &str, default: isize) -> Result<Schedule, Box<dyn Error>> {
+        let mut targets = [default, default, default, default, default, 0, 0];
+        for entry in schedule.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (days, target) = entry.split_once('=').ok_or_else(|| {
+                format!("invalid schedule '{entry}', expected e.g. mon-thu=08:00")
+            })?;
+            let target = hhmm_to_s(target.trim())?;
+            let (first, last) = match days.split_once('-') {
+                Some((first, last)) => (parse_weekday(first)?, parse_weekday(last)?),
+                None => (parse_weekday(days)?, parse_weekday(days)?),
+            };
+            let mut day = first;
+            loop {
+                targets[day.num_days_from_monday() as usize] = target;
+                if day == last {
+                    break;
+                }
+                day = day.succ();
+            }
+        }
+        Ok(Schedule { targets })
+    }
+
+    /// The target of `date`.
+    pub fn target(&self, date: NaiveDate) -> isize {
+        self.targets[date.weekday().num_days_from_monday() as usize]
+    }
+
+    /// The sum of the targets of a week.
+    pub fn weekly(&self) -> isize {
+        self.targets.iter().sum()
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut day = Weekday::Mon;
+        for (index, target) in self.targets.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(
+                f,
+                "{}={}",
+                day.to_string().to_lowercase(),
+                s_to_hhmm(*target)
+            )?;
+            day = day.succ();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+//! A user script, `azk.rhai` in the config dir, run by the embedded Rhai
+//! engine (https://rhai.rs). It may define any of these functions:
+//!
+//! - `before_write(date, records)` is called before a day is written with
+//!   its date as in `2024-03-01` and its records, and returns the records to
+//!   write instead. A record is a map of `kind`, `timestamp` in RFC 3339,
+//!   `project` and `note`, the last two `()` when there are none.
+//! - `report_fields(days)` is called by `azk report` with the days reported
+//!   and returns a map of more fields to print, by name.
+//! - `check_day(day)` is called by the compliance checks for each day worked
+//!   and returns an array of findings, either messages, which are warnings,
+//!   or maps of `severity`, `warning` or `violation`, and `message`.
+//!
+//! A day is a map of `date`, `start` and `end` in seconds since midnight,
+//! `worked` and `breaks` in seconds, whether it is `ongoing`, and its
+//! `intervals`, each a map of `start`, `end`, `project` and `note`.
+
+use std::error::Error;
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+use serde::Serialize;
+
+use crate::compliance::Severity;
+use crate::config;
+use crate::model::{DayInfo, Record, RecordError};
+use crate::store::{DayError, Lock, Store};
+
+/// The script, in the config dir.
+pub const FILE_NAME: &str = "azk.rhai";
+
+/// How many operations a call may take before it is stopped, so that a
+/// script stuck in a loop does not hang azk.
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+#[derive(Serialize)]
+struct ScriptInterval<'a> {
+    start: isize,
+    end: isize,
+    project: Option<&'a str>,
+    note: Option<&'a str>,
+}
+
+/// A day as scripts see it, see the module docs.
+#[derive(Serialize)]
+struct ScriptDay<'a> {
+    date: String,
+    start: isize,
+    end: isize,
+    worked: isize,
+    breaks: isize,
+    ongoing: bool,
+    intervals: Vec<ScriptInterval<'a>>,
+}
+
+impl<'a> ScriptDay<'a> {
+    fn new(date: NaiveDate, info: &'a DayInfo) -> ScriptDay<'a> {
+        ScriptDay {
+            date: date.to_string(),
+            start: info.start(),
+            end: info.end(),
+            worked: info.duration(),
+            breaks: info.break_duration(),
+            ongoing: info.ongoing(),
+            intervals: info
+                .intervals
+                .iter()
+                .map(|interval| ScriptInterval {
+                    start: interval.start,
+                    end: interval.end,
+                    project: interval.project.as_deref(),
+                    note: interval.note.as_deref(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The compiled user script.
+pub struct Script {
+    path: PathBuf,
+    engine: Engine,
+    ast: AST,
+}
+
+impl Script {
+    /// The script in the config dir, or `None` if there is none.
+    pub fn load() -> Result<Option<Script>, Box<dyn Error>> {
+        let path = config::dir()?.join(FILE_NAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let ast = engine
+            .compile_file(path.clone())
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        Ok(Some(Script { path, engine, ast }))
+    }
+
+    fn defines(&self, name: &str) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|function| function.name == name)
+    }
+
+    /// Calls the function `name` with `args`, if the script defines it.
+    fn call(&self, name: &str, args: Vec<Dynamic>) -> Result<Option<Dynamic>, Box<dyn Error>> {
+        if !self.defines(name) {
+            return Ok(None);
+        }
+        let result = self
+            .engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, name, args)
+            .map_err(|err| format!("{}: {name}: {err}", self.path.display()))?;
+        Ok(Some(result))
+    }
+
+    /// The records to write for the day instead of `records`, see
+    /// `before_write`.
+    pub fn before_write(
+        &self,
+        date: NaiveDate,
+        records: &[Record],
+    ) -> Result<Option<Vec<Record>>, Box<dyn Error>> {
+        let args = vec![date.to_string().into(), rhai::serde::to_dynamic(records)?];
+        let Some(result) = self.call("before_write", args)? else {
+            return Ok(None);
+        };
+        let records = rhai::serde::from_dynamic(&result).map_err(|err| {
+            format!(
+                "{}: before_write did not return records: {err}",
+                self.path.display()
+            )
+        })?;
+        Ok(Some(records))
+    }
+
+    /// More fields of a report over `days`, by name, see `report_fields`.
+    pub fn report_fields(
+        &self,
+        days: &[(NaiveDate, &DayInfo)],
+    ) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let days: Vec<ScriptDay> = days
+            .iter()
+            .map(|(date, info)| ScriptDay::new(*date, info))
+            .collect();
+        let Some(result) = self.call("report_fields", vec![rhai::serde::to_dynamic(days)?])? else {
+            return Ok(Vec::new());
+        };
+        let fields = result.try_cast::<Map>().ok_or_else(|| {
+            format!(
+                "{}: report_fields did not return a map",
+                self.path.display()
+            )
+        })?;
+        Ok(fields
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect())
+    }
+
+    /// The findings of the script for the day, see `check_day`.
+    pub fn check_day(
+        &self,
+        date: NaiveDate,
+        info: &DayInfo,
+    ) -> Result<Vec<(Severity, String)>, Box<dyn Error>> {
+        let day = rhai::serde::to_dynamic(ScriptDay::new(date, info))?;
+        let Some(result) = self.call("check_day", vec![day])? else {
+            return Ok(Vec::new());
+        };
+        let invalid = || {
+            format!(
+                "{}: check_day did not return an array of messages or maps of severity and message",
+                self.path.display()
+            )
+        };
+        let mut findings = Vec::new();
+        for finding in result.try_cast::<Array>().ok_or_else(invalid)? {
+            if finding.is_string() {
+                findings.push((Severity::Warning, finding.to_string()));
+                continue;
+            }
+            let finding = finding.try_cast::<Map>().ok_or_else(invalid)?;
+            let severity = match finding.get("severity").map(Dynamic::to_string).as_deref() {
+                None | Some("warning") => Severity::Warning,
+                Some("violation") => Severity::Violation,
+                Some(_) => Err(invalid())?,
+            };
+            let message = finding.get("message").ok_or_else(invalid)?.to_string();
+            findings.push((severity, message));
+        }
+        Ok(findings)
+    }
+}
+
+/// A store passing the records written through it to `before_write` first.
+pub struct Scripted {
+    inner: Box<dyn Store>,
+    script: Script,
+}
+
+impl Scripted {
+    pub fn new(inner: Box<dyn Store>, script: Script) -> Scripted {
+        Scripted { inner, script }
+    }
+}
+
+impl Store for Scripted {
+    fn location(&self, date: NaiveDate) -> String {
+        self.inner.location(date)
+    }
+
+    fn file(&self, date: NaiveDate) -> PathBuf {
+        self.inner.file(date)
+    }
+
+    fn locate(&self, date: NaiveDate, index: usize) -> String {
+        self.inner.locate(date, index)
+    }
+
+    fn read_day(&self, date: NaiveDate) -> Result<Option<Vec<Record>>, DayError> {
+        self.inner.read_day(date)
+    }
+
+    fn visit_day(
+        &self,
+        date: NaiveDate,
+        visit: &mut dyn FnMut(&Record) -> Result<(), RecordError>,
+    ) -> Result<bool, DayError> {
+        self.inner.visit_day(date, visit)
+    }
+
+    /// The script sees the whole day, so with it the day is rewritten.
+    fn append_record(&self, date: NaiveDate, record: Record) -> Result<(), Box<dyn Error>> {
+        if !self.script.defines("before_write") {
+            return self.inner.append_record(date, record);
+        }
+        let mut records = self.inner.read_day(date)?.unwrap_or_default();
+        records.push(record);
+        self.write_day(date, &records)
+    }
+
+    fn write_day(&self, date: NaiveDate, records: &[Record]) -> Result<(), Box<dyn Error>> {
+        match self.script.before_write(date, records)? {
+            Some(records) => self.inner.write_day(date, &records),
+            None => self.inner.write_day(date, records),
+        }
+    }
+
+    fn schema(&self, date: NaiveDate) -> Result<Option<u32>, DayError> {
+        self.inner.schema(date)
+    }
+
+    fn list_days(&self) -> Result<Vec<NaiveDate>, Box<dyn Error>> {
+        self.inner.list_days()
+    }
+
+    fn modified(&self, date: NaiveDate) -> Result<u64, Box<dyn Error>> {
+        self.inner.modified(date)
+    }
+
+    fn lock(&self) -> Result<Lock, Box<dyn Error>> {
+        self.inner.lock()
+    }
+}
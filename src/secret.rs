@@ -0,0 +1,123 @@
+//! Secrets like access tokens, kept in the system keyring rather than the
+//! config: the Secret Service through `secret-tool` on Linux and the login
+//! keychain through `security` on macOS. There is none on other platforms,
+//! Windows included, where the secrets that can also be given in the
+//! environment, like `$GITLAB_TOKEN`, have to be.
+
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// The service the secrets of azk are filed under.
+const SERVICE: &str = "azk";
+
+/// The entries azk reads, with what they hold, for `azk secret`.
+pub const NAMES: [(&str, &str); 9] = [
+    ("slack", "the Slack user token"),
+    ("mqtt", "the password for mqtt_username"),
+    ("influx", "the InfluxDB token"),
+    ("serve", "the token of azk serve"),
+    ("sync", "the password or secret key for sync_username"),
+    ("encryption", "the passphrase of the encrypted days"),
+    (
+        "gitlab",
+        "the GitLab access token, instead of $GITLAB_TOKEN",
+    ),
+    ("webhook", "the bearer token sent to the webhooks"),
+    (
+        "audit",
+        "the key the audit log is chained with, made when it starts",
+    ),
+];
+
+/// The keyring's command line tool, with `macos` or `linux` arguments.
+fn command(macos: &[&str], linux: &[&str]) -> Result<Command, Box<dyn Error>> {
+    if cfg!(target_os = "macos") {
+        let mut command = Command::new("security");
+        command.args(macos);
+        Ok(command)
+    } else if cfg!(unix) {
+        let mut command = Command::new("secret-tool");
+        command.args(linux);
+        Ok(command)
+    } else {
+        Err("unsupported platform, the keyring is only supported on Linux and macOS")?
+    }
+}
+
+fn unreadable(command: &Command, err: std::io::Error) -> String {
+    format!(
+        "cannot read the keyring, {}: {err}",
+        command.get_program().to_string_lossy()
+    )
+}
+
+/// The secret called `name`, e.g. `slack`, if there is one.
+pub fn get(name: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let mut command = command(
+        &["find-generic-password", "-s", SERVICE, "-a", name, "-w"],
+        &["lookup", "service", SERVICE, "account", name],
+    )?;
+    let output = command.output().map_err(|err| unreadable(&command, err))?;
+    let secret = String::from_utf8(output.stdout)?;
+    let secret = secret.trim_end_matches('\n');
+    Ok((output.status.success() && !secret.is_empty()).then(|| secret.to_owned()))
+}
+
+/// Keeps `secret` as the one called `name`, replacing any there was.
+pub fn set(name: &str, secret: &str) -> Result<(), Box<dyn Error>> {
+    // `secret-tool` reads the secret from stdin. `security` only takes it
+    // as an argument, where others could see it, or from the terminal, so
+    // it is given the whole command on stdin instead.
+    let label = format!("{SERVICE} {name}");
+    let mut command = command(
+        &["-i"],
+        &[
+            "store", "--label", &label, "service", SERVICE, "account", name,
+        ],
+    )?;
+    let input = if cfg!(target_os = "macos") {
+        let quoted = secret.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("add-generic-password -U -s {SERVICE} -a {name} -w \"{quoted}\"\n")
+    } else {
+        secret.to_owned()
+    };
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| unreadable(&command, err))?;
+    child
+        .stdin
+        .take()
+        .expect("the keyring's stdin is piped")
+        .write_all(input.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        Err(format!(
+            "cannot write to the keyring: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))?
+    }
+    Ok(())
+}
+
+/// Removes the secret called `name`, returning whether there was one.
+pub fn remove(name: &str) -> Result<bool, Box<dyn Error>> {
+    if get(name)?.is_none() {
+        return Ok(false);
+    }
+    let mut command = command(
+        &["delete-generic-password", "-s", SERVICE, "-a", name],
+        &["clear", "service", SERVICE, "account", name],
+    )?;
+    let output = command.output().map_err(|err| unreadable(&command, err))?;
+    if !output.status.success() {
+        Err(format!(
+            "cannot remove from the keyring: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))?
+    }
+    Ok(true)
+}
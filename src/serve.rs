@@ -0,0 +1,200 @@
+use std::error::Error;
+
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use azk::store::{read_work_time, update_time, Store};
+use azk::time::{hhmmss_to_s, s_to_hhmm};
+use azk::{config, input, report, secret};
+
+use crate::run_hooks;
+
+/// The keyring entry holding the token that requests must carry, as in
+/// `Authorization: Bearer TOKEN`.
+const SECRET: &str = "serve";
+
+/// The environment variable holding the token if the keyring does not.
+const TOKEN_VAR: &str = "AZK_SERVE_TOKEN";
+
+/// The dashboard served at `/`, which asks for the token itself.
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+/// Compares without giving away through timing how much of `given` matched.
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn authorized(request: &Request, token: &str) -> bool {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .and_then(|header| header.value.as_str().strip_prefix("Bearer "))
+        .is_some_and(|given| same_token(given, token))
+}
+
+/// The value of `name` in the query of `url`, decoded.
+fn query(url: &str, name: &str) -> Option<String> {
+    form_urlencoded::parse(url.split_once('?')?.1.as_bytes())
+        .find_map(|(key, value)| (key == name).then(|| value.into_owned()))
+}
+
+/// `GET /status`: the state and work of today, with its intervals.
+fn status(store: &dyn Store) -> Result<Value, Box<dyn Error>> {
+    let now = chrono::Local::now();
+    let now_s = hhmmss_to_s(&format!("{}", now.format("%H:%M:%S")));
+    let day = read_work_time(store, now.date_naive(), Some(now_s))?;
+    let state = match &day {
+        Some(day) if day.ongoing() => "working",
+        Some(day) if !day.intervals.is_empty() => "break",
+        _ => "idle",
+    };
+    let duration = day.as_ref().map_or(0, |day| day.duration());
+    let intervals: Vec<_> = day
+        .iter()
+        .flat_map(|day| &day.intervals)
+        .map(|interval| {
+            json!({
+                "start": s_to_hhmm(interval.start),
+                "end": s_to_hhmm(interval.end),
+                "ongoing": interval.ongoing,
+                "project": interval.project,
+            })
+        })
+        .collect();
+    Ok(json!({
+        "date": now.date_naive().to_string(),
+        "state": state,
+        "today_s": duration,
+        "today_hm": s_to_hhmm(duration),
+        "session_s": day.map_or(0, |day| now_s - day.last),
+        "intervals": intervals,
+    }))
+}
+
+/// `POST /stamp`: toggles between work and break.
+fn stamp(store: &dyn Store) -> Result<Value, Box<dyn Error>> {
+    let now = chrono::Local::now();
+    let today = now.date_naive();
+    let time = format!("{}", now.format("%H:%M:%S"));
+    let record =
+        update_time(store, today, &time, None, None, None)?.ok_or("nothing was stamped")?;
+    run_hooks(store, today, &record);
+    Ok(json!({
+        "date": today.to_string(),
+        "time": time,
+        "state": if record.kind == "strt" { "working" } else { "break" },
+    }))
+}
+
+/// `GET /report?from=DAY&to=DAY`: the net work of every day in the range,
+/// both defaulting to today.
+fn report(store: &dyn Store, url: &str) -> Result<Value, Box<dyn Error>> {
+    let now = chrono::Local::now();
+    let today = now.date_naive();
+    let day = |name| query(url, name).map_or(Ok(today), |day| input::parse_date(&day, today));
+    let (from, to) = (day("from")?, day("to")?);
+    if to < from {
+        Err(format!("to {to} is before from {from}"))?
+    }
+    let now_s = hhmmss_to_s(&format!("{}", now.format("%H:%M:%S")));
+    let days = report::days(store, from, to, today, now_s)?;
+    let rules = report::parse_break_rules(&config::load()?.break_rules)?;
+    let totals = report::totals(&days, report::GroupBy::Day, &rules);
+    let total: isize = totals.iter().map(|(_, duration)| duration).sum();
+    Ok(json!({
+        "from": from.to_string(),
+        "to": to.to_string(),
+        "total_s": total,
+        "total_hm": s_to_hhmm(total),
+        "days": totals
+            .iter()
+            .map(|(date, duration)| json!({ "date": date, "duration_s": duration }))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+fn respond(request: Request, code: u16, body: &Value) -> Result<(), Box<dyn Error>> {
+    let content_type =
+        Header::from_bytes("Content-Type", "application/json").expect("the header is valid");
+    request.respond(
+        Response::from_string(body.to_string())
+            .with_status_code(code)
+            .with_header(content_type),
+    )?;
+    Ok(())
+}
+
+/// Answers `request`, failing only if the answer cannot be sent.
+fn handle(store: &dyn Store, request: Request, token: &str) -> Result<(), Box<dyn Error>> {
+    if request.method() == &Method::Get && request.url() == "/" {
+        let content_type = Header::from_bytes("Content-Type", "text/html; charset=utf-8")
+            .expect("the header is valid");
+        request.respond(Response::from_string(DASHBOARD).with_header(content_type))?;
+        return Ok(());
+    }
+    if !authorized(&request, token) {
+        return respond(request, 401, &json!({ "error": "unauthorized" }));
+    }
+    let url = request.url().to_owned();
+    let path = url.split('?').next().unwrap_or("");
+    let result = match (request.method(), path) {
+        (Method::Get, "/status") => status(store),
+        (Method::Post, "/stamp") => stamp(store),
+        (Method::Get, "/report") => report(store, &url),
+        _ => return respond(request, 404, &json!({ "error": "not found" })),
+    };
+    match result {
+        Ok(body) => respond(request, 200, &body),
+        Err(err) => respond(request, 400, &json!({ "error": err.to_string() })),
+    }
+}
+
+/// Answers requests on `listen`, as in `127.0.0.1:7878`, that carry the
+/// token from the keyring or `$AZK_SERVE_TOKEN`. The dashboard at `/` is
+/// served to anyone, as it holds no data. A client that goes away before
+/// its answer is sent is only reported.
+pub fn serve(store: &dyn Store, listen: &str) -> Result<(), Box<dyn Error>> {
+    let token = match secret::get(SECRET).ok().flatten() {
+        Some(token) => token,
+        None => std::env::var(TOKEN_VAR).map_err(|_| {
+            format!("there is no '{SECRET}' token in the keyring and ${TOKEN_VAR} is not set")
+        })?,
+    };
+    if token.trim().is_empty() {
+        Err(format!("${TOKEN_VAR} is empty, which would let anyone in"))?
+    }
+    let server = Server::http(listen).map_err(|err| format!("{listen}: {err}"))?;
+    println!("Listening on http://{listen}");
+
+    for request in server.incoming_requests() {
+        let client = request.remote_addr().copied();
+        if let Err(err) = handle(store, request, &token) {
+            match client {
+                Some(client) => eprintln!("Cannot answer {client}: {err}"),
+                None => eprintln!("Cannot answer a request: {err}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_query_values() {
+        let url = "/report?from=2024-03-01&to=last%2Dfriday&note=a+b";
+        assert_eq!(query(url, "from").as_deref(), Some("2024-03-01"));
+        assert_eq!(query(url, "to").as_deref(), Some("last-friday"));
+        assert_eq!(query(url, "note").as_deref(), Some("a b"));
+        assert_eq!(query(url, "fro"), None);
+        assert_eq!(query("/report", "from"), None);
+    }
+}
@@ -0,0 +1,62 @@
+//! The Slack status, set from stamps.
+
+use std::error::Error;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::model::Record;
+use crate::secret;
+
+/// The keyring entry holding the user token, with the `users.profile:write`
+/// scope.
+pub const SECRET: &str = "slack";
+
+const ENDPOINT: &str = "https://slack.com/api/users.profile.set";
+
+/// Splits a status as in `:hammer: working` into its emoji and text.
+fn split_status(status: &str) -> (&str, &str) {
+    match status.split_once(' ') {
+        Some((emoji, text)) if emoji.starts_with(':') && emoji.ends_with(':') => {
+            (emoji, text.trim())
+        }
+        _ if status.starts_with(':') && status.ends_with(':') => (status, ""),
+        _ => ("", status),
+    }
+}
+
+/// Sets the status for `record`: `slack_working` on a start, `slack_break`
+/// on a stop naming a kind of break, and none on any other stop. Does
+/// nothing unless `slack_working` is set.
+pub fn set_status(config: &Config, record: &Record) -> Result<(), Box<dyn Error>> {
+    if config.slack_working.is_empty() {
+        return Ok(());
+    }
+    let status = if record.kind == "strt" {
+        config.slack_working.as_str()
+    } else if record.break_category().is_some() {
+        config.slack_break.as_str()
+    } else {
+        ""
+    };
+    let (emoji, text) = split_status(status);
+    let token = secret::get(SECRET)?
+        .ok_or_else(|| format!("there is no '{SECRET}' token in the keyring"))?;
+    let body = serde_json::json!({
+        "profile": {
+            "status_text": text,
+            "status_emoji": emoji,
+            "status_expiration": 0,
+        }
+    });
+    let response: serde_json::Value = ureq::post(ENDPOINT)
+        .timeout(Duration::from_secs(5))
+        .set("Authorization", &format!("Bearer {token}"))
+        .set("Content-Type", "application/json; charset=utf-8")
+        .send_string(&body.to_string())?
+        .into_string()
+        .map(|text| serde_json::from_str(&text))??;
+    if response["ok"] != true {
+        Err(format!("Slack refused the status: {}", response["error"]))?
+    }
+    Ok(())
+}
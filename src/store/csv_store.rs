@@ -0,0 +1,552 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Cursor, ErrorKind, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+
+use super::encryption::Encryption;
+use super::{ends_with_line_break, torn_start, DayError, Lock, Store, SCHEMA_VERSION};
+use crate::model::{Record, RecordError};
+use crate::time::{local_timestamp, parse_hhmmss};
+
+/// The first line of a file since schema 3, followed by the version.
+/// Older files are told apart by their header.
+const MARKER: &str = "# azk schema ";
+
+/// Reads the schema of the file at `path` and whether it is marked, or `None`
+/// if there is no such file.
+fn read_schema(path: &Path) -> io::Result<Option<(u32, bool)>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let mut first_line = String::new();
+    BufReader::new(file).read_line(&mut first_line)?;
+    schema_of(&first_line).map(Some)
+}
+
+/// The schema of a file starting with `first_line` and whether it is marked.
+fn schema_of(first_line: &str) -> io::Result<(u32, bool)> {
+    if let Some(version) = first_line.strip_prefix(MARKER) {
+        let version = version.trim().parse().map_err(|_| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid schema marker '{}'", first_line.trim()),
+            )
+        })?;
+        return Ok((version, true));
+    }
+    let columns: Vec<&str> = first_line.trim().split(',').collect();
+    let schema = if first_line.is_empty() {
+        SCHEMA_VERSION
+    } else if columns.contains(&"timestamp") {
+        3
+    } else if columns.contains(&"project") {
+        2
+    } else {
+        1
+    };
+    Ok((schema, false))
+}
+
+/// A record of the files written before timestamps were stored, with only
+/// the wall-clock time. The UTC offset is taken to be the local one.
+#[derive(Deserialize)]
+struct LegacyRecord {
+    kind: String,
+    time: String,
+    project: Option<String>,
+    note: Option<String>,
+}
+
+impl LegacyRecord {
+    fn into_record(self, date: NaiveDate) -> Result<Record, RecordError> {
+        let timestamp = parse_hhmmss(&self.time)
+            .and_then(|s| local_timestamp(date, s))
+            .ok_or(RecordError::InvalidTime(self.time))?;
+        Ok(Record {
+            kind: self.kind,
+            timestamp,
+            project: self.project,
+            note: self.note,
+        })
+    }
+}
+
+/// The name and the contents of each day file in an archive.
+type Archived = Arc<Vec<(String, Vec<u8>)>>;
+
+/// One CSV file per day, named `YYYY-MM-DD.csv`, or `YYYY-MM-DD.csv.enc`
+/// when the days are encrypted, see [`Encryption`]. Encrypted days are
+/// always rewritten whole. The days of a month may be rolled into an
+/// archive, `YYYY-MM.tar.zst`, which is read as if they were not and
+/// unpacked again before one of them is changed, see [`CsvStore::archive`].
+pub struct CsvStore {
+    dir: PathBuf,
+    encryption: Option<Encryption>,
+    /// The archives read, by month, with their modification time and size
+    /// then, so that each is only decompressed again once it changed.
+    archives: Mutex<HashMap<NaiveDate, (SystemTime, u64, Archived)>>,
+}
+
+impl CsvStore {
+    /// The store in `dir`, encrypted if it says so.
+    pub fn new(dir: PathBuf) -> Result<Self, Box<dyn Error>> {
+        let encryption = Encryption::load(&dir)?;
+        Ok(CsvStore::with_encryption(dir, encryption))
+    }
+
+    pub fn with_encryption(dir: PathBuf, encryption: Option<Encryption>) -> Self {
+        CsvStore {
+            dir,
+            encryption,
+            archives: Mutex::default(),
+        }
+    }
+
+    pub fn encryption(&self) -> Option<&Encryption> {
+        self.encryption.as_ref()
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.encryption.is_some()
+    }
+
+    /// The ending of the names of day files.
+    fn suffix(&self) -> &'static str {
+        if self.is_encrypted() {
+            ".csv.enc"
+        } else {
+            ".csv"
+        }
+    }
+
+    pub fn file_path(&self, date: NaiveDate) -> PathBuf {
+        self.dir
+            .join(format!("{}{}", date.format("%Y-%m-%d"), self.suffix()))
+    }
+
+    /// The archive of the days of the month of `date`.
+    fn archive_path(&self, date: NaiveDate) -> PathBuf {
+        self.dir.join(format!("{}.tar.zst", date.format("%Y-%m")))
+    }
+
+    /// The months with an archive, as their first days.
+    fn archived_months(&self) -> io::Result<Vec<NaiveDate>> {
+        let mut months = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name();
+            if let Some(stem) = name.to_string_lossy().strip_suffix(".tar.zst") {
+                if let Ok(month) = NaiveDate::parse_from_str(&format!("{stem}-01"), "%Y-%m-%d") {
+                    months.push(month);
+                }
+            }
+        }
+        Ok(months)
+    }
+
+    /// The name and the contents of each day file in the archive of the
+    /// month of `date`, none if there is no archive.
+    fn read_archive(&self, date: NaiveDate) -> io::Result<Archived> {
+        let month = date.with_day(1).expect("every month has a first day");
+        let mut archives = self.archives.lock().unwrap_or_else(|err| err.into_inner());
+        let file = match File::open(self.archive_path(date)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                archives.remove(&month);
+                return Ok(Archived::default());
+            }
+            Err(err) => return Err(err),
+        };
+        let metadata = file.metadata()?;
+        let (modified, len) = (metadata.modified()?, metadata.len());
+        if let Some((cached_modified, cached_len, files)) = archives.get(&month) {
+            if (*cached_modified, *cached_len) == (modified, len) {
+                return Ok(Arc::clone(files));
+            }
+        }
+        let mut files = Vec::new();
+        let mut archive = tar::Archive::new(zstd::Decoder::new(file)?);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().into_owned();
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            files.push((name, contents));
+        }
+        let files = Arc::new(files);
+        archives.insert(month, (modified, len, Arc::clone(&files)));
+        Ok(files)
+    }
+
+    /// The contents of the day, decrypted, from its file or else from the
+    /// archive of its month, or `None` if there are none.
+    fn contents(&self, date: NaiveDate) -> io::Result<Option<Vec<u8>>> {
+        let file_path = self.file_path(date);
+        let contents = match fs::read(&file_path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                let name = file_path.file_name().unwrap_or_default().to_string_lossy();
+                let archived = self.read_archive(date)?;
+                match archived.iter().find(|(archived, _)| *archived == name) {
+                    Some((_, contents)) => contents.clone(),
+                    None => return Ok(None),
+                }
+            }
+            Err(err) => return Err(err),
+        };
+        match &self.encryption {
+            Some(encryption) => encryption.open(&contents).map(Some),
+            None => Ok(Some(contents)),
+        }
+    }
+
+    /// Whether the day is read as a whole through [`CsvStore::contents`]
+    /// rather than from its file as it is.
+    fn read_whole(&self, date: NaiveDate) -> bool {
+        self.is_encrypted() || !self.file_path(date).exists()
+    }
+
+    /// Unpacks the archive of the month of `date` back into day files, if
+    /// there is one, so that they can be changed.
+    fn unarchive(&self, date: NaiveDate) -> Result<(), Box<dyn Error>> {
+        let archive_path = self.archive_path(date);
+        for (name, contents) in self.read_archive(date)?.iter() {
+            let path = self.dir.join(name);
+            // Days written since are newer.
+            if !path.exists() {
+                let mut tmp_path = path.clone().into_os_string();
+                tmp_path.push(".tmp");
+                let mut file = File::create(&tmp_path)?;
+                file.write_all(contents)?;
+                file.sync_all()?;
+                fs::rename(&tmp_path, path)?;
+            }
+        }
+        if archive_path.exists() {
+            fs::remove_file(archive_path)?;
+        }
+        Ok(())
+    }
+
+    /// Rolls the day files of the month starting on `month` into its
+    /// archive and returns how many there were.
+    pub fn archive(&self, month: NaiveDate) -> Result<usize, Box<dyn Error>> {
+        self.unarchive(month)?;
+        let mut days: Vec<NaiveDate> = self
+            .list_days()?
+            .into_iter()
+            .filter(|date| date.year() == month.year() && date.month() == month.month())
+            .collect();
+        days.sort();
+        if days.is_empty() {
+            return Ok(0);
+        }
+        let archive_path = self.archive_path(month);
+        let mut tmp_path = archive_path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let file = File::create(&tmp_path)?;
+        let mut archive = tar::Builder::new(zstd::Encoder::new(file, 19)?);
+        for date in &days {
+            let file_path = self.file_path(*date);
+            let name = file_path.file_name().unwrap_or_default();
+            archive.append_path_with_name(&file_path, name)?;
+        }
+        archive.into_inner()?.finish()?.sync_all()?;
+        fs::rename(&tmp_path, archive_path)?;
+        for date in &days {
+            fs::remove_file(self.file_path(*date))?;
+        }
+        Ok(days.len())
+    }
+
+    /// Removes the archives of all months, once their days are written
+    /// elsewhere.
+    pub fn remove_archives(&self) -> Result<(), Box<dyn Error>> {
+        for month in self.archived_months()? {
+            fs::remove_file(self.archive_path(month))?;
+        }
+        Ok(())
+    }
+
+    /// The schema of the day's file and whether it is marked, or `None` if
+    /// there is no such file.
+    fn read_schema(&self, date: NaiveDate) -> io::Result<Option<(u32, bool)>> {
+        if !self.read_whole(date) {
+            return read_schema(&self.file_path(date));
+        }
+        let Some(contents) = self.contents(date)? else {
+            return Ok(None);
+        };
+        let mut first_line = String::new();
+        Cursor::new(contents).read_line(&mut first_line)?;
+        schema_of(&first_line).map(Some)
+    }
+
+    /// Reads the day's records one by one into `visit`, failing at the
+    /// first one it rejects, and tells whether there is a file for the day.
+    /// A last record that was cut short by a crash is left out.
+    fn read_records(
+        &self,
+        date: NaiveDate,
+        visit: &mut dyn FnMut(Record) -> Result<(), RecordError>,
+    ) -> Result<bool, DayError> {
+        let location = self.location(date);
+        let io_error = |source| DayError::Io {
+            location: location.clone(),
+            source,
+        };
+        // Encrypted and archived days are written whole, so none is ever cut
+        // short.
+        let (schema, torn, reader): (u32, Option<u64>, Box<dyn Read>) = if self.read_whole(date) {
+            let Some(contents) = self.contents(date).map_err(io_error)? else {
+                return Ok(false);
+            };
+            let mut first_line = String::new();
+            let mut contents = Cursor::new(contents);
+            contents.read_line(&mut first_line).map_err(io_error)?;
+            let (schema, _) = schema_of(&first_line).map_err(io_error)?;
+            if schema > SCHEMA_VERSION {
+                return Err(DayError::Schema { location, schema });
+            }
+            contents.rewind().map_err(io_error)?;
+            (schema, None, Box::new(contents))
+        } else {
+            let Some(schema) = self.schema(date)? else {
+                return Ok(false);
+            };
+            let mut file = File::open(self.file_path(date)).map_err(io_error)?;
+            let torn = torn_start(&mut file).map_err(io_error)?;
+            file.rewind().map_err(io_error)?;
+            (schema, torn, Box::new(BufReader::new(file)))
+        };
+        let mut rdr = csv::ReaderBuilder::new()
+            .comment(Some(b'#'))
+            .from_reader(reader);
+        let headers = rdr.headers().map_err(|source| DayError::Csv {
+            location: format!("{location}:1"),
+            source,
+        })?;
+        let headers = headers.clone();
+        let legacy = schema < 3;
+
+        for row in rdr.records() {
+            let (position, line, record) = match row {
+                Ok(row) => {
+                    let position = row.position().cloned().unwrap_or_else(csv::Position::new);
+                    let line = format!("{location}:{}", position.line());
+                    let record = if legacy {
+                        row.deserialize::<LegacyRecord>(Some(&headers))
+                            .map_err(|source| DayError::Csv {
+                                location: line.clone(),
+                                source,
+                            })
+                            .and_then(|record| {
+                                record.into_record(date).map_err(|source| DayError::Record {
+                                    location: line.clone(),
+                                    source,
+                                })
+                            })
+                    } else {
+                        row.deserialize(Some(&headers))
+                            .map_err(|source| DayError::Csv {
+                                location: line.clone(),
+                                source,
+                            })
+                    };
+                    (position, line, record)
+                }
+                Err(source) => {
+                    let position = source
+                        .position()
+                        .cloned()
+                        .unwrap_or_else(csv::Position::new);
+                    let line = format!("{location}:{}", position.line());
+                    let err = DayError::Csv {
+                        location: line.clone(),
+                        source,
+                    };
+                    (position, line, Err(err))
+                }
+            };
+            match record {
+                Ok(record) => visit(record).map_err(|source| DayError::Record {
+                    location: line,
+                    source,
+                })?,
+                Err(_) if torn.is_some_and(|torn| position.byte() >= torn) => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Writes the record, and the marker and header into an empty file, with
+    /// a single call and waits for it to reach the disk.
+    fn write_record(mut file: &File, record: Record) -> Result<(), Box<dyn Error>> {
+        let write_headers = file.metadata()?.len() == 0;
+        let mut buffer = Vec::new();
+        if write_headers {
+            writeln!(buffer, "{MARKER}{SCHEMA_VERSION}")?;
+        }
+        let mut wtr = csv::WriterBuilder::new()
+            .has_headers(write_headers)
+            .from_writer(&mut buffer);
+        wtr.serialize(record)?;
+        wtr.flush()?;
+        drop(wtr);
+        file.write_all(&buffer)?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+impl Store for CsvStore {
+    fn location(&self, date: NaiveDate) -> String {
+        self.file_path(date).display().to_string()
+    }
+
+    fn file(&self, date: NaiveDate) -> PathBuf {
+        self.file_path(date)
+    }
+
+    /// Records are written one per line after the marker and the header.
+    fn locate(&self, date: NaiveDate, index: usize) -> String {
+        let marked = self
+            .read_schema(date)
+            .ok()
+            .flatten()
+            .is_some_and(|(_, marked)| marked);
+        let first = if marked { 3 } else { 2 };
+        format!("{}:{}", self.location(date), index + first)
+    }
+
+    fn read_day(&self, date: NaiveDate) -> Result<Option<Vec<Record>>, DayError> {
+        let mut records = Vec::new();
+        let found = self.read_records(date, &mut |record| {
+            records.push(record);
+            Ok(())
+        })?;
+        Ok(found.then_some(records))
+    }
+
+    fn visit_day(
+        &self,
+        date: NaiveDate,
+        visit: &mut dyn FnMut(&Record) -> Result<(), RecordError>,
+    ) -> Result<bool, DayError> {
+        self.read_records(date, &mut |record| visit(&record))
+    }
+
+    /// A file in an older schema is rewritten in the current one first, as
+    /// is one whose last record was cut short, and any encrypted one.
+    fn append_record(&self, date: NaiveDate, record: Record) -> Result<(), Box<dyn Error>> {
+        self.unarchive(date)?;
+        let file_path = self.file_path(date);
+        if self.is_encrypted()
+            || read_schema(&file_path)?.is_some_and(|(schema, _)| schema < SCHEMA_VERSION)
+            || !ends_with_line_break(&file_path)?
+        {
+            let mut records = self.read_day(date)?.unwrap_or_default();
+            records.push(record);
+            return self.write_day(date, &records);
+        }
+
+        let file = File::options()
+            .read(true)
+            .create(true)
+            .append(true)
+            .open(file_path)?;
+        Self::write_record(&file, record)
+    }
+
+    /// Goes through a temporary file so that readers never see a half-written
+    /// day, not even after a crash.
+    fn write_day(&self, date: NaiveDate, records: &[Record]) -> Result<(), Box<dyn Error>> {
+        self.unarchive(date)?;
+        let file_path = self.file_path(date);
+        if records.is_empty() {
+            if file_path.exists() {
+                fs::remove_file(file_path)?;
+            }
+            return Ok(());
+        }
+
+        let mut contents = Vec::new();
+        writeln!(contents, "{MARKER}{SCHEMA_VERSION}")?;
+        let mut wtr = csv::Writer::from_writer(&mut contents);
+        for record in records {
+            wtr.serialize(record)?;
+        }
+        wtr.flush()?;
+        drop(wtr);
+        if let Some(encryption) = &self.encryption {
+            contents = encryption.seal(&contents)?;
+        }
+        let mut tmp_path = file_path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, file_path)?;
+        Ok(())
+    }
+
+    fn schema(&self, date: NaiveDate) -> Result<Option<u32>, DayError> {
+        let location = self.location(date);
+        match self.read_schema(date) {
+            Ok(Some((schema, _))) if schema > SCHEMA_VERSION => {
+                Err(DayError::Schema { location, schema })
+            }
+            Ok(schema) => Ok(schema.map(|(schema, _)| schema)),
+            Err(source) => Err(DayError::Io { location, source }),
+        }
+    }
+
+    fn list_days(&self) -> Result<Vec<NaiveDate>, Box<dyn Error>> {
+        let mut dates = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name();
+            if let Some(stem) = name.to_string_lossy().strip_suffix(self.suffix()) {
+                if let Ok(date) = NaiveDate::parse_from_str(stem, "%Y-%m-%d") {
+                    dates.push(date);
+                }
+            }
+        }
+        for month in self.archived_months()? {
+            for (name, _) in self.read_archive(month)?.iter() {
+                if let Some(stem) = name.strip_suffix(self.suffix()) {
+                    if let Ok(date) = NaiveDate::parse_from_str(stem, "%Y-%m-%d") {
+                        dates.push(date);
+                    }
+                }
+            }
+        }
+        dates.sort();
+        dates.dedup();
+        Ok(dates)
+    }
+
+    /// One lock for all days, as `azk.lock` next to them.
+    fn lock(&self) -> Result<Lock, Box<dyn Error>> {
+        Lock::acquire(&self.dir.join("azk.lock"))
+    }
+
+    /// That of the archive for archived days.
+    fn modified(&self, date: NaiveDate) -> Result<u64, Box<dyn Error>> {
+        let file_path = self.file_path(date);
+        let path = if file_path.exists() {
+            file_path
+        } else {
+            self.archive_path(date)
+        };
+        let mtime = fs::metadata(path)?.modified()?;
+        Ok(mtime.duration_since(UNIX_EPOCH)?.as_secs())
+    }
+}
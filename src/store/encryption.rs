@@ -0,0 +1,179 @@
+use std::error::Error;
+use std::fs;
+use std::io::{self, ErrorKind, IsTerminal};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+use crate::secret;
+
+/// The file in the data dir telling that the days are encrypted, and how
+/// their key is derived from the passphrase.
+pub const FILE_NAME: &str = "encryption.json";
+
+/// The keyring entry holding the passphrase.
+pub const SECRET: &str = "encryption";
+
+/// The environment variable holding the passphrase, if the keyring does not.
+pub const PASSPHRASE_VAR: &str = "AZK_PASSPHRASE";
+
+/// What encrypted files start with, followed by the nonce.
+const MAGIC: &[u8] = b"azk-enc1";
+const NONCE_LEN: usize = 24;
+
+/// What is sealed with the key, to tell a wrong passphrase from a changed
+/// file.
+const CHECK: &[u8] = b"azk";
+
+#[derive(Deserialize, Serialize)]
+struct Header {
+    version: u32,
+    /// The salt of Argon2id, in base64.
+    salt: String,
+    /// [`CHECK`] sealed with the key, in base64.
+    check: String,
+}
+
+/// The key the days in a data dir are encrypted with, by XChaCha20-Poly1305.
+/// It is derived from the passphrase by Argon2id when first needed, so that
+/// commands not reading any day do not ask for it.
+pub struct Encryption {
+    header: Header,
+    cipher: OnceLock<Result<XChaCha20Poly1305, String>>,
+}
+
+/// The passphrase from the keyring, `$AZK_PASSPHRASE` or else asked for,
+/// twice to `confirm` a new one.
+pub fn passphrase(confirm: bool) -> Result<String, Box<dyn Error>> {
+    if let Some(passphrase) = secret::get(SECRET).ok().flatten() {
+        return Ok(passphrase);
+    }
+    if let Some(passphrase) = std::env::var(PASSPHRASE_VAR).ok().filter(|p| !p.is_empty()) {
+        return Ok(passphrase);
+    }
+    if !io::stdin().is_terminal() {
+        Err(format!(
+            "the days are encrypted, put the passphrase in the keyring as '{SECRET}' or in ${PASSPHRASE_VAR}"
+        ))?
+    }
+    let passphrase = rpassword::prompt_password("Passphrase: ")?;
+    if passphrase.is_empty() {
+        Err("the passphrase is empty")?
+    }
+    if confirm && rpassword::prompt_password("Repeat it: ")? != passphrase {
+        Err("the passphrases differ")?
+    }
+    Ok(passphrase)
+}
+
+fn derive(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305, String> {
+    let mut key = [0; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| format!("cannot derive the key: {err}"))?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+fn seal_with(cipher: &XChaCha20Poly1305, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let mut nonce = [0; NONCE_LEN];
+    getrandom::fill(&mut nonce).map_err(|err| format!("no randomness for a nonce: {err}"))?;
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), plaintext)
+        .map_err(|_| "cannot encrypt")?;
+    Ok([MAGIC, &nonce, &ciphertext].concat())
+}
+
+fn open_with(cipher: &XChaCha20Poly1305, sealed: &[u8]) -> Result<Vec<u8>, String> {
+    let rest = sealed
+        .strip_prefix(MAGIC)
+        .filter(|rest| rest.len() >= NONCE_LEN)
+        .ok_or("not encrypted by azk")?;
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    cipher
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "cannot decrypt, it was changed or encrypted with another key".to_owned())
+}
+
+impl Encryption {
+    /// A new key derived from `passphrase` with a random salt.
+    pub fn new(passphrase: &str) -> Result<Encryption, Box<dyn Error>> {
+        let mut salt = [0; 16];
+        getrandom::fill(&mut salt).map_err(|err| format!("no randomness for a salt: {err}"))?;
+        let cipher = derive(passphrase, &salt)?;
+        let header = Header {
+            version: 1,
+            salt: STANDARD.encode(salt),
+            check: STANDARD.encode(seal_with(&cipher, CHECK)?),
+        };
+        Ok(Encryption {
+            header,
+            cipher: OnceLock::from(Ok(cipher)),
+        })
+    }
+
+    /// The encryption of the days in `dir`, or `None` if they are not.
+    pub fn load(dir: &Path) -> Result<Option<Encryption>, Box<dyn Error>> {
+        let path = dir.join(FILE_NAME);
+        let contents = match fs::read(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => Err(format!("{}: {err}", path.display()))?,
+        };
+        let header: Header = serde_json::from_slice(&contents)
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        if header.version > 1 {
+            Err(format!(
+                "{}: written by a newer version of azk",
+                path.display()
+            ))?
+        }
+        Ok(Some(Encryption {
+            header,
+            cipher: OnceLock::new(),
+        }))
+    }
+
+    /// Saves how the key is derived in `dir`, which marks its days as
+    /// encrypted.
+    pub fn save(&self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        let path = dir.join(FILE_NAME);
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(&self.header)?)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    fn cipher(&self) -> io::Result<&XChaCha20Poly1305> {
+        self.cipher
+            .get_or_init(|| {
+                let passphrase = passphrase(false).map_err(|err| err.to_string())?;
+                let salt = STANDARD
+                    .decode(&self.header.salt)
+                    .map_err(|err| format!("invalid salt: {err}"))?;
+                let cipher = derive(&passphrase, &salt)?;
+                let check = STANDARD
+                    .decode(&self.header.check)
+                    .map_err(|err| format!("invalid check: {err}"))?;
+                match open_with(&cipher, &check) {
+                    Ok(check) if check == CHECK => Ok(cipher),
+                    _ => Err("wrong passphrase".to_owned()),
+                }
+            })
+            .as_ref()
+            .map_err(|err| io::Error::other(err.clone()))
+    }
+
+    pub fn seal(&self, plaintext: &[u8]) -> io::Result<Vec<u8>> {
+        seal_with(self.cipher()?, plaintext).map_err(io::Error::other)
+    }
+
+    pub fn open(&self, sealed: &[u8]) -> io::Result<Vec<u8>> {
+        open_with(self.cipher()?, sealed).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, ErrorKind, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::{ends_with_line_break, torn_start, DayError, Lock, Store, SCHEMA_VERSION};
+use crate::model::{Record, RecordError};
+
+/// The file holding all records, in the data dir.
+pub const FILE_NAME: &str = "azk.jsonl";
+
+/// The schema of files without a [`Marker`], which were written before it was
+/// introduced.
+const UNMARKED_SCHEMA: u32 = 3;
+
+/// The first line of the file, telling which schema the records follow.
+#[derive(Deserialize, Serialize)]
+struct Marker {
+    schema: u32,
+}
+
+/// The day a record belongs to, on its own wall clock.
+fn date_of(record: &Record) -> NaiveDate {
+    record.timestamp.date_naive()
+}
+
+/// Where the records of each day are in the file, as it was when it was
+/// read, so that a day is read without going through the others.
+struct Index {
+    modified: SystemTime,
+    len: u64,
+    schema: u32,
+    /// The offset and the line number of each record, by day.
+    days: BTreeMap<NaiveDate, Vec<(u64, usize)>>,
+}
+
+/// All days in a single JSON Lines file, one stamp per line. Stamps are only
+/// ever appended; editing a day rewrites the file.
+pub struct JsonlStore {
+    path: PathBuf,
+    /// Built once and again only when the file changed.
+    index: Mutex<Option<Arc<Index>>>,
+}
+
+impl JsonlStore {
+    pub fn new(dir: PathBuf) -> Self {
+        JsonlStore {
+            path: dir.join(FILE_NAME),
+            index: Mutex::default(),
+        }
+    }
+
+    fn io_error(&self, source: std::io::Error) -> DayError {
+        DayError::Io {
+            location: self.path.display().to_string(),
+            source,
+        }
+    }
+
+    /// The file, or `None` if it is missing.
+    fn open(&self) -> Result<Option<File>, DayError> {
+        match File::open(&self.path) {
+            Ok(file) => Ok(Some(file)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(source) => Err(self.io_error(source)),
+        }
+    }
+
+    /// Reads `file` from the start, passing every record to `visit` with its
+    /// offset and line number, and returns the file, its schema and how much
+    /// was read. A last record that was cut short by a crash is left out.
+    fn scan(
+        &self,
+        mut file: File,
+        visit: &mut dyn FnMut(u64, usize, Record),
+    ) -> Result<(File, u32, u64), DayError> {
+        let location = self.path.display().to_string();
+        let torn = torn_start(&mut file).map_err(|source| self.io_error(source))?;
+        file.rewind().map_err(|source| self.io_error(source))?;
+        let mut reader = BufReader::new(file);
+        let mut schema = SCHEMA_VERSION;
+        let mut len = 0;
+        let mut text = String::new();
+        for number in 1.. {
+            let start = len;
+            text.clear();
+            let read = reader
+                .read_line(&mut text)
+                .map_err(|source| self.io_error(source))?;
+            if read == 0 {
+                break;
+            }
+            len += read as u64;
+            if number == 1 {
+                match serde_json::from_str(&text) {
+                    Ok(Marker { schema: marked }) if marked > SCHEMA_VERSION => {
+                        return Err(DayError::Schema {
+                            location,
+                            schema: marked,
+                        })
+                    }
+                    Ok(Marker { schema: marked }) => {
+                        schema = marked;
+                        continue;
+                    }
+                    Err(_) => schema = UNMARKED_SCHEMA,
+                }
+            }
+            if text.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&text) {
+                Ok(record) => visit(start, number, record),
+                Err(_) if torn == Some(start) => break,
+                Err(source) => {
+                    return Err(DayError::Json {
+                        location: format!("{location}:{number}"),
+                        source,
+                    })
+                }
+            }
+        }
+        Ok((reader.into_inner(), schema, len))
+    }
+
+    /// The index of the file, opened as `file`, or `None` if it is missing.
+    fn index(&self) -> Result<Option<(File, Arc<Index>)>, DayError> {
+        let Some(file) = self.open()? else {
+            return Ok(None);
+        };
+        let metadata = file.metadata().map_err(|source| self.io_error(source))?;
+        let modified = metadata
+            .modified()
+            .map_err(|source| self.io_error(source))?;
+        let mut cached = self.index.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(index) = cached.as_ref() {
+            if (index.modified, index.len) == (modified, metadata.len()) {
+                return Ok(Some((file, Arc::clone(index))));
+            }
+        }
+
+        let mut days: BTreeMap<NaiveDate, Vec<(u64, usize)>> = BTreeMap::new();
+        let (file, schema, len) = self.scan(file, &mut |start, number, record| {
+            days.entry(date_of(&record))
+                .or_default()
+                .push((start, number));
+        })?;
+        let index = Arc::new(Index {
+            modified,
+            len,
+            schema,
+            days,
+        });
+        *cached = Some(Arc::clone(&index));
+        Ok(Some((file, index)))
+    }
+
+    fn marker_line() -> Result<String, serde_json::Error> {
+        serde_json::to_string(&Marker {
+            schema: SCHEMA_VERSION,
+        })
+    }
+
+    /// Every record of the file, in order.
+    fn read_lines(&self) -> Result<Vec<Record>, DayError> {
+        let mut lines = Vec::new();
+        if let Some(file) = self.open()? {
+            self.scan(file, &mut |_, _, record| lines.push(record))?;
+        }
+        Ok(lines)
+    }
+
+    fn write_lines(&self, lines: &[Record]) -> Result<(), Box<dyn Error>> {
+        let tmp_path = self.path.with_extension("jsonl.tmp");
+        let mut file = File::create(&tmp_path)?;
+        writeln!(file, "{}", Self::marker_line()?)?;
+        for line in lines {
+            writeln!(file, "{}", serde_json::to_string(line)?)?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+impl Store for JsonlStore {
+    fn location(&self, _date: NaiveDate) -> String {
+        self.path.display().to_string()
+    }
+
+    fn file(&self, _date: NaiveDate) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn locate(&self, date: NaiveDate, index: usize) -> String {
+        let line = self.index().ok().flatten().and_then(|(_, days)| {
+            days.days
+                .get(&date)
+                .and_then(|lines| lines.get(index))
+                .map(|(_, number)| *number)
+        });
+        match line {
+            Some(number) => format!("{}:{number}", self.path.display()),
+            None => format!("{} #{index}", self.path.display()),
+        }
+    }
+
+    /// Reads only the lines of the day, as found in the index.
+    fn read_day(&self, date: NaiveDate) -> Result<Option<Vec<Record>>, DayError> {
+        let mut records = Vec::new();
+        self.visit_day(date, &mut |record| {
+            records.push(record.clone());
+            Ok(())
+        })?;
+        Ok((!records.is_empty()).then_some(records))
+    }
+
+    /// Reads only the lines of the day, as found in the index.
+    fn visit_day(
+        &self,
+        date: NaiveDate,
+        visit: &mut dyn FnMut(&Record) -> Result<(), RecordError>,
+    ) -> Result<bool, DayError> {
+        let Some((file, index)) = self.index()? else {
+            return Ok(false);
+        };
+        let Some(lines) = index.days.get(&date) else {
+            return Ok(false);
+        };
+        let location = self.path.display().to_string();
+        let mut reader = BufReader::new(file);
+        let mut text = String::new();
+        for (offset, number) in lines {
+            reader
+                .seek(SeekFrom::Start(*offset))
+                .map_err(|source| self.io_error(source))?;
+            text.clear();
+            reader
+                .read_line(&mut text)
+                .map_err(|source| self.io_error(source))?;
+            let record: Record = serde_json::from_str(&text).map_err(|source| DayError::Json {
+                location: format!("{location}:{number}"),
+                source,
+            })?;
+            visit(&record).map_err(|source| DayError::Record {
+                location: format!("{location}:{number}"),
+                source,
+            })?;
+        }
+        Ok(true)
+    }
+
+    /// Written with a single call, so that the line is never interleaved with
+    /// another one, and waited for to reach the disk. If the last line was cut
+    /// short, the file is rewritten without it instead, as is a file in an
+    /// older schema.
+    fn append_record(&self, _date: NaiveDate, record: Record) -> Result<(), Box<dyn Error>> {
+        let outdated = self
+            .index()?
+            .is_some_and(|(_, index)| index.schema < SCHEMA_VERSION);
+        if !ends_with_line_break(&self.path)? || outdated {
+            let mut lines = self.read_lines()?;
+            lines.push(record);
+            return self.write_lines(&lines);
+        }
+
+        let mut text = format!("{}\n", serde_json::to_string(&record)?);
+        let mut file = File::options().create(true).append(true).open(&self.path)?;
+        if file.metadata()?.len() == 0 {
+            text.insert_str(0, &format!("{}\n", Self::marker_line()?));
+        }
+        file.write_all(text.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Days stay in order, so the replaced day ends up where it was.
+    fn write_day(&self, date: NaiveDate, records: &[Record]) -> Result<(), Box<dyn Error>> {
+        let mut lines: Vec<Record> = self
+            .read_lines()?
+            .into_iter()
+            .filter(|line| date_of(line) != date)
+            .collect();
+        lines.extend(records.iter().cloned());
+        lines.sort_by_key(date_of);
+        self.write_lines(&lines)
+    }
+
+    fn schema(&self, date: NaiveDate) -> Result<Option<u32>, DayError> {
+        Ok(self
+            .index()?
+            .filter(|(_, index)| index.days.contains_key(&date))
+            .map(|(_, index)| index.schema))
+    }
+
+    fn list_days(&self) -> Result<Vec<NaiveDate>, Box<dyn Error>> {
+        Ok(self
+            .index()?
+            .map(|(_, index)| index.days.keys().copied().collect())
+            .unwrap_or_default())
+    }
+
+    fn lock(&self) -> Result<Lock, Box<dyn Error>> {
+        Lock::acquire(&self.path.with_extension("jsonl.lock"))
+    }
+
+    /// There is only one file, so any change counts for every day.
+    fn modified(&self, _date: NaiveDate) -> Result<u64, Box<dyn Error>> {
+        let mtime = fs::metadata(&self.path)?.modified()?;
+        Ok(mtime.duration_since(UNIX_EPOCH)?.as_secs())
+    }
+}
@@ -0,0 +1,504 @@
+mod csv_store;
+mod encryption;
+mod jsonl_store;
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate};
+
+use crate::model::{DayFold, DayInfo, Record, RecordError};
+use crate::script::{Script, Scripted};
+use crate::time::{hhmmss_to_s, local_timestamp};
+use crate::{audit, config};
+
+pub use csv_store::CsvStore;
+pub use encryption::{passphrase, Encryption, FILE_NAME as ENCRYPTION_FILE};
+pub use jsonl_store::{JsonlStore, FILE_NAME as JSONL_FILE};
+
+/// The version of the data files written by this version of azk.
+///
+/// 1. `kind,time` with the wall-clock time.
+/// 2. `kind,time,project,note`.
+/// 3. RFC3339 timestamps instead of the time, with a marker for the version.
+/// 4. Stops may name the kind of break, as in `stop:lunch`.
+pub const SCHEMA_VERSION: u32 = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DayError {
+    #[error("{location}: {source}")]
+    Io { location: String, source: io::Error },
+    #[error("{location}: {source}")]
+    Csv {
+        location: String,
+        source: csv::Error,
+    },
+    #[error("{location}: {source}")]
+    Json {
+        location: String,
+        source: serde_json::Error,
+    },
+    #[error("{location}: {source}")]
+    Record {
+        location: String,
+        source: RecordError,
+    },
+    #[error("{location}: written in schema {schema}, which needs a newer version of azk")]
+    Schema { location: String, schema: u32 },
+}
+
+/// Where the records of each day are kept. Commands only go through this
+/// trait, so that backends can be swapped without touching them. Days may be
+/// read from several threads at once.
+pub trait Store: Sync {
+    /// Describes where the day is kept, for messages.
+    fn location(&self, date: NaiveDate) -> String;
+
+    /// The file the day is kept in, which may hold other days too.
+    fn file(&self, date: NaiveDate) -> PathBuf;
+
+    /// Describes where the day's `index`th record is kept, for error messages.
+    fn locate(&self, date: NaiveDate, index: usize) -> String {
+        format!("{} #{index}", self.location(date))
+    }
+
+    /// The day's records, or `None` if nothing was recorded that day.
+    fn read_day(&self, date: NaiveDate) -> Result<Option<Vec<Record>>, DayError>;
+
+    /// Passes the day's records to `visit` in order, which backends may do
+    /// without holding them all at once, and tells whether anything was
+    /// recorded that day. Fails at the first record `visit` rejects.
+    fn visit_day(
+        &self,
+        date: NaiveDate,
+        visit: &mut dyn FnMut(&Record) -> Result<(), RecordError>,
+    ) -> Result<bool, DayError> {
+        let Some(records) = self.read_day(date)? else {
+            return Ok(false);
+        };
+        for (index, record) in records.iter().enumerate() {
+            visit(record).map_err(|source| DayError::Record {
+                location: self.locate(date, index),
+                source,
+            })?;
+        }
+        Ok(true)
+    }
+
+    /// Adds `record` after the day's other records.
+    fn append_record(&self, date: NaiveDate, record: Record) -> Result<(), Box<dyn Error>>;
+
+    /// Replaces all of the day's records. Without records the day is removed.
+    fn write_day(&self, date: NaiveDate, records: &[Record]) -> Result<(), Box<dyn Error>>;
+
+    /// The schema version the day is stored in, see [`SCHEMA_VERSION`], or
+    /// `None` if nothing was recorded that day. Fails if it is newer than the
+    /// current one.
+    fn schema(&self, date: NaiveDate) -> Result<Option<u32>, DayError>;
+
+    /// The days that have records, in order.
+    fn list_days(&self) -> Result<Vec<NaiveDate>, Box<dyn Error>>;
+
+    /// Seconds since the epoch of the day's last change, for cache invalidation.
+    fn modified(&self, date: NaiveDate) -> Result<u64, Box<dyn Error>>;
+
+    /// Waits until no other process holds the store's lock and takes it, so
+    /// that reading records and writing them back isn't interleaved with
+    /// another write.
+    fn lock(&self) -> Result<Lock, Box<dyn Error>>;
+}
+
+/// An exclusive advisory lock on a file, released when dropped.
+pub struct Lock {
+    _file: File,
+}
+
+impl Lock {
+    pub(crate) fn acquire(path: &Path) -> Result<Lock, Box<dyn Error>> {
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        file.lock()
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        Ok(Lock { _file: file })
+    }
+}
+
+/// Where the last line of `file` starts if it was cut short, by a crash while
+/// appending it, reading only as much of its end as it takes. Every line
+/// written ends with a line break.
+fn torn_start(file: &mut File) -> io::Result<Option<u64>> {
+    let mut end = file.metadata()?.len();
+    let mut buffer = [0; 4096];
+    let mut last = true;
+    while end > 0 {
+        let start = end.saturating_sub(buffer.len() as u64);
+        let chunk = &mut buffer[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+        if last && chunk.ends_with(b"\n") {
+            return Ok(None);
+        }
+        last = false;
+        if let Some(i) = chunk.iter().rposition(|&b| b == b'\n') {
+            return Ok(Some(start + i as u64 + 1));
+        }
+        end = start;
+    }
+    Ok((!last).then_some(0))
+}
+
+/// Whether the file at `path` is missing, empty or ends with a line break,
+/// so that a line can be appended to it.
+fn ends_with_line_break(path: &Path) -> io::Result<bool> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(true),
+        Err(err) => return Err(err),
+    };
+    if file.metadata()?.len() == 0 {
+        return Ok(true);
+    }
+    file.seek(SeekFrom::End(-1))?;
+    let mut last = [0];
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
+
+/// The environment variable naming the data directory to use instead of the
+/// platform's or the profile's, which `--data-dir` sets too.
+pub const DATA_DIR_VAR: &str = "AZK_DATA_DIR";
+
+/// Where the records and everything else azk keeps are, created if missing:
+/// `$AZK_DATA_DIR`, else the `data_dir` key, else the platform's.
+pub fn data_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let dir = std::env::var_os(DATA_DIR_VAR).filter(|dir| !dir.is_empty());
+    if let Some(data_dir) = dir.map(PathBuf::from).or(config::data_dir()?) {
+        fs::create_dir_all(&data_dir).map_err(|err| format!("{}: {err}", data_dir.display()))?;
+        return Ok(data_dir);
+    }
+    if let Some(proj_dirs) = directories::ProjectDirs::from("com", "hylo", "azk") {
+        let mut data_dir = proj_dirs.data_dir().to_path_buf();
+        if let Some(profile) = config::profile()? {
+            data_dir = data_dir.join("profiles").join(profile);
+        }
+        fs::create_dir_all(&data_dir)?;
+        return Ok(data_dir);
+    }
+    Err("path error")?
+}
+
+/// The names of the backends, as set in the `store` config key.
+pub const BACKENDS: [&str; 2] = ["csv", "jsonl"];
+
+/// Opens the store of the backend named `backend`, see [`BACKENDS`].
+pub fn open_backend(backend: &str) -> Result<Box<dyn Store>, Box<dyn Error>> {
+    open_backend_in(backend, data_dir()?)
+}
+
+/// Opens the store of the backend named `backend` kept in `dir` rather than
+/// the data dir.
+pub fn open_backend_in(backend: &str, dir: PathBuf) -> Result<Box<dyn Store>, Box<dyn Error>> {
+    match backend {
+        "csv" => Ok(Box::new(CsvStore::new(dir)?)),
+        "jsonl" => Ok(Box::new(JsonlStore::new(dir))),
+        _ => Err(format!(
+            "unknown store '{backend}', expected one of {}",
+            BACKENDS.join(", ")
+        ))?,
+    }
+}
+
+/// Encrypts the days of the csv store in the data dir with a key derived
+/// from `passphrase`, or decrypts them if it is `None`, and returns how many
+/// there are. The new files are written before the old ones are removed.
+pub fn encrypt(backend: &str, passphrase: Option<&str>) -> Result<usize, Box<dyn Error>> {
+    if backend != "csv" {
+        Err("only the days of the csv store can be encrypted, see 'azk convert'")?
+    }
+    let dir = data_dir()?;
+    let from = CsvStore::new(dir.clone())?;
+    let _lock = from.lock()?;
+    if from.is_encrypted() == passphrase.is_some() {
+        Err(if from.is_encrypted() {
+            "the days are encrypted already"
+        } else {
+            "the days are not encrypted"
+        })?
+    }
+    let days = from.list_days()?;
+    let mut records = Vec::with_capacity(days.len());
+    for date in &days {
+        records.push(from.read_day(*date)?.unwrap_or_default());
+    }
+
+    let encryption = passphrase.map(Encryption::new).transpose()?;
+    let to = CsvStore::with_encryption(dir.clone(), encryption);
+    for (date, records) in days.iter().zip(&records) {
+        to.write_day(*date, records)?;
+    }
+    // Only now do the new files count.
+    match &to.encryption() {
+        Some(encryption) => encryption.save(&dir)?,
+        None => fs::remove_file(dir.join(ENCRYPTION_FILE))?,
+    }
+    for date in &days {
+        match fs::remove_file(from.file_path(*date)) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err)?,
+            _ => {}
+        }
+    }
+    // The archived days were written out as day files too.
+    from.remove_archives()?;
+    if passphrase.is_some() {
+        crate::totals::remove_caches(&dir)?;
+    }
+    Ok(days.len())
+}
+
+/// Rolls the days of the csv store in the data dir before the month
+/// starting on `before` into an archive per month, see
+/// [`CsvStore::archive`]. Returns the months archived, with how many days
+/// each has.
+pub fn archive(
+    backend: &str,
+    before: NaiveDate,
+) -> Result<Vec<(NaiveDate, usize)>, Box<dyn Error>> {
+    if backend != "csv" {
+        Err("only the days of the csv store can be archived, see 'azk convert'")?
+    }
+    let store = CsvStore::new(data_dir()?)?;
+    let _lock = store.lock()?;
+    let mut months: Vec<NaiveDate> = store
+        .list_days()?
+        .into_iter()
+        .filter(|date| *date < before && store.file_path(*date).exists())
+        .filter_map(|date| date.with_day(1))
+        .collect();
+    months.dedup();
+    let mut archived = Vec::new();
+    for month in months {
+        archived.push((month, store.archive(month)?));
+    }
+    Ok(archived)
+}
+
+/// Opens the store configured with the `store` key, logging its changes if
+/// `audit` is on, and passing what is written through the user script if
+/// there is one, see [`crate::script`].
+pub fn open() -> Result<Box<dyn Store>, Box<dyn Error>> {
+    let config = config::load()?;
+    let mut store = open_backend(&config.store)?;
+    if config.audit {
+        store = Box::new(audit::Audited::new(store, &data_dir()?));
+    }
+    // The audit log holds what was written in the end.
+    if let Some(script) = Script::load()? {
+        store = Box::new(Scripted::new(store, script));
+    }
+    Ok(store)
+}
+
+/// Rewrites the days stored in an older schema in the current one. Returns
+/// the days that were upgraded, with their previous schema.
+pub fn migrate(store: &dyn Store) -> Result<Vec<(NaiveDate, u32)>, Box<dyn Error>> {
+    let mut migrated = Vec::new();
+    for date in store.list_days()? {
+        let Some(schema) = store.schema(date)? else {
+            continue;
+        };
+        if schema < SCHEMA_VERSION {
+            let _lock = store.lock()?;
+            let records = store.read_day(date)?.unwrap_or_default();
+            store.write_day(date, &records)?;
+            migrated.push((date, schema));
+        }
+    }
+    Ok(migrated)
+}
+
+/// Pairs the day's records into work intervals as they are read, see
+/// [`DayFold`]. Returns `None` if nothing was recorded that day.
+pub fn read_work_time(
+    store: &dyn Store,
+    date: NaiveDate,
+    now: Option<isize>,
+) -> Result<Option<DayInfo>, DayError> {
+    let mut fold = DayFold::default();
+    if !store.visit_day(date, &mut |record| fold.push(record))? {
+        return Ok(None);
+    }
+    let now = now.and_then(|now| local_timestamp(date, now));
+    Ok(Some(fold.finish(now)))
+}
+
+/// Like [`DayInfo::from_records`], locating the failing record in `store`.
+fn pair_records(
+    store: &dyn Store,
+    date: NaiveDate,
+    records: &[Record],
+    now: Option<DateTime<FixedOffset>>,
+) -> Result<DayInfo, DayError> {
+    DayInfo::from_records(records, now).map_err(|(index, source)| DayError::Record {
+        location: store.locate(date, index),
+        source,
+    })
+}
+
+/// Inserts a stretch of work from `start` to `end` among the day's records,
+/// as a start and a stop. It must not overlap work already recorded.
+pub fn add_interval(
+    store: &dyn Store,
+    date: NaiveDate,
+    start: &str,
+    end: &str,
+    project: Option<&String>,
+    note: Option<&String>,
+) -> Result<(), Box<dyn Error>> {
+    let timestamp = |time: &str| {
+        local_timestamp(date, hhmmss_to_s(time))
+            .ok_or_else(|| format!("{date} {time} does not exist in the local time zone"))
+    };
+    let (start_timestamp, end_timestamp) = (timestamp(start)?, timestamp(end)?);
+    if end_timestamp <= start_timestamp {
+        Err(format!("{end} is not after {start}"))?
+    }
+
+    let _lock = store.lock()?;
+    let mut records = store.read_day(date)?.unwrap_or_default();
+    pair_records(store, date, &records, None)?;
+
+    let index = records.partition_point(|record| record.timestamp <= start_timestamp);
+    let stamp = |kind: &str, timestamp| Record {
+        kind: kind.to_owned(),
+        timestamp,
+        project: project.cloned(),
+        note: None,
+    };
+    records.insert(index, stamp("stop", end_timestamp));
+    records.insert(
+        index,
+        Record {
+            note: note.cloned(),
+            ..stamp("strt", start_timestamp)
+        },
+    );
+    if DayInfo::from_records(&records, None).is_err() {
+        Err(format!(
+            "{start} - {end} overlaps work recorded in {}",
+            store.location(date)
+        ))?
+    }
+
+    store.write_day(date, &records)
+}
+
+/// Moves the day's last stamp to `time`, keeping the original time in its
+/// note. Returns the original time.
+pub fn amend_last(
+    store: &dyn Store,
+    date: NaiveDate,
+    time: &str,
+) -> Result<String, Box<dyn Error>> {
+    let timestamp = local_timestamp(date, hhmmss_to_s(time))
+        .ok_or_else(|| format!("{date} {time} does not exist in the local time zone"))?;
+
+    let _lock = store.lock()?;
+    let mut records = store.read_day(date)?.unwrap_or_default();
+    let Some(last) = records.pop() else {
+        Err(format!("no stamps in {}", store.location(date)))?
+    };
+    if let Some(previous) = records
+        .last()
+        .filter(|previous| timestamp < previous.timestamp)
+    {
+        Err(format!(
+            "cannot amend to {time}, it is before the previous stamp ({} at {}) in {}",
+            previous.kind,
+            previous.time(),
+            store.locate(date, records.len() - 1)
+        ))?
+    }
+
+    let original = last.time();
+    let audit = format!("amended from {original}");
+    records.push(Record {
+        timestamp,
+        note: Some(match last.note {
+            Some(note) => format!("{note} ({audit})"),
+            None => audit,
+        }),
+        ..last
+    });
+    store.write_day(date, &records)?;
+    Ok(original)
+}
+
+/// Seconds that passed from the day's last stamp until `time`, or `None` if
+/// there are no stamps yet.
+pub fn since_last_stamp(
+    store: &dyn Store,
+    date: NaiveDate,
+    time: &str,
+) -> Result<Option<isize>, DayError> {
+    let records = store.read_day(date)?.unwrap_or_default();
+    let Some(last) = records.last() else {
+        return Ok(None);
+    };
+    Ok(local_timestamp(date, hhmmss_to_s(time))
+        .map(|timestamp| (timestamp - last.timestamp).num_seconds() as isize))
+}
+
+/// Appends a stamp toggling between work and break and returns it. If `kind`
+/// is given, only a stamp of that kind is appended, and nothing is returned
+/// if it would toggle the other way. A stop can name the kind of break, as
+/// in `stop:lunch`.
+pub fn update_time(
+    store: &dyn Store,
+    date: NaiveDate,
+    time: &str,
+    kind: Option<&str>,
+    project: Option<&String>,
+    note: Option<&String>,
+) -> Result<Option<Record>, Box<dyn Error>> {
+    let _lock = store.lock()?;
+    let timestamp = local_timestamp(date, hhmmss_to_s(time))
+        .ok_or_else(|| format!("{date} {time} does not exist in the local time zone"))?;
+    let records = store.read_day(date)?.unwrap_or_default();
+    let day = pair_records(store, date, &records, Some(timestamp))?;
+
+    if let Some(last) = records.last().filter(|last| timestamp < last.timestamp) {
+        Err(format!(
+            "cannot stamp at {time}, it is before the last stamp ({} at {}) in {}",
+            last.kind,
+            last.time(),
+            store.locate(date, records.len() - 1)
+        ))?
+    }
+
+    let new_kind = if day.ongoing() { "stop" } else { "strt" };
+    if kind.is_some_and(|kind| kind.split(':').next() != Some(new_kind)) {
+        return Ok(None);
+    }
+
+    let record = Record {
+        timestamp,
+        kind: kind.unwrap_or(new_kind).to_owned(),
+        project: project.cloned(),
+        note: note.cloned(),
+    };
+
+    // Nothing inconsistent is written, even if the checks above miss a case.
+    let mut checked = records;
+    checked.push(record.clone());
+    if let Err((_, err)) = DayInfo::from_records(&checked, None) {
+        Err(format!("cannot stamp {new_kind} at {time}: {err}"))?
+    }
+
+    store.append_record(date, record.clone())?;
+    Ok(Some(record))
+}
@@ -0,0 +1,293 @@
+//! Syncing through a git remote. The data dir is a repository of its own,
+//! committed to and merged with `sync_remote`. Days changed on both sides
+//! are [`merge`]d against their merge base.
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use super::{check, merge_sides, remote, staging, Pulled, Status, BASE, STATE};
+use crate::audit;
+use crate::config::Config;
+use crate::store::{data_dir, Store};
+
+/// What is left out of the repository: locks and temporary files, caches,
+/// which are rebuilt, and the other profiles.
+const GITIGNORE: &str = "*.lock\n*.tmp\n*-cache.csv\n*.restore/\n/profiles/\n";
+
+/// Days keep the lines of both sides, so that git does not stop at them, and
+/// are merged stamp by stamp after, see [`merge_days`]. Only the plain days
+/// are merged so: encrypted days and archives would be corrupted, so that
+/// they conflict instead, as do the other files.
+const GITATTRIBUTES: &str = "\
+[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9].csv merge=union
+azk.jsonl merge=union
+*.csv.enc binary
+*.tar.zst binary
+";
+
+/// Runs git with `args` in `dir` and returns what it printed.
+fn git(dir: &Path, args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|err| format!("cannot run git: {err}"))?;
+    if !output.status.success() {
+        Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))?
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The file names git printed one per line, but for those azk keeps to
+/// itself, like the `.gitignore`.
+fn names(output: &str) -> BTreeSet<String> {
+    output
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('.'))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Makes `dir` a repository with `remote` as `origin`, if it is not yet.
+fn init(dir: &Path, remote: &str) -> Result<(), Box<dyn Error>> {
+    if !dir.join(".git").exists() {
+        git(dir, &["init", "--quiet"])?;
+    }
+    // The state of the other backends and the audit log are left out too.
+    fs::write(
+        dir.join(".gitignore"),
+        format!("{GITIGNORE}/{STATE}\n/{BASE}/\n/{}\n", audit::FILE_NAME),
+    )?;
+    fs::write(dir.join(".gitattributes"), GITATTRIBUTES)?;
+    match git(dir, &["remote", "get-url", "origin"]) {
+        Ok(url) if url.trim() == remote => {}
+        Ok(_) => {
+            git(dir, &["remote", "set-url", "origin", remote])?;
+        }
+        Err(_) => {
+            git(dir, &["remote", "add", "origin", remote])?;
+        }
+    }
+    Ok(())
+}
+
+/// The arguments to commit as azk, unless git knows who the user is.
+fn identity(dir: &Path) -> Vec<&'static str> {
+    if git(dir, &["config", "user.email"]).is_ok() {
+        return Vec::new();
+    }
+    vec!["-c", "user.name=azk", "-c", "user.email=azk@localhost"]
+}
+
+/// Commits all changes in `dir` and returns whether there were any. The
+/// first commit has at least the `.gitignore`.
+fn commit(dir: &Path, message: &str) -> Result<bool, Box<dyn Error>> {
+    git(dir, &["add", "--all"])?;
+    if git(dir, &["status", "--porcelain"])?.trim().is_empty() {
+        return Ok(false);
+    }
+    let mut args = identity(dir);
+    args.extend(["commit", "--quiet", "--message", message]);
+    git(dir, &args)?;
+    Ok(true)
+}
+
+/// Makes the data dir a repository syncing with `sync_remote` if needed and
+/// fetches the remote. Returns the remote's branch, if it has one yet.
+fn fetch(dir: &Path, config: &Config) -> Result<Option<String>, Box<dyn Error>> {
+    init(dir, remote(config)?)?;
+    git(dir, &["fetch", "--quiet", "origin"])?;
+    let remote_branch = format!("origin/{}", config.sync_branch);
+    Ok(
+        git(dir, &["rev-parse", "--verify", "--quiet", &remote_branch])
+            .is_ok()
+            .then_some(remote_branch),
+    )
+}
+
+/// The files changed on `to` since it parted from `from`, all of its files
+/// if they have nothing in common.
+fn changed(dir: &Path, from: &str, to: &str) -> Result<BTreeSet<String>, Box<dyn Error>> {
+    match git(dir, &["merge-base", from, to]) {
+        Ok(base) => Ok(names(&git(dir, &["diff", "--name-only", base.trim(), to])?)),
+        Err(_) => Ok(names(&git(dir, &["ls-tree", "-r", "--name-only", to])?)),
+    }
+}
+
+/// Merges the stamps of the days in the files `both`, which git merged line
+/// by line, as changed since the commit `base`, if the sides have one, on
+/// the commits `ours` and `theirs`. Notes the days merged in `pulled`.
+fn merge_days(
+    store: &dyn Store,
+    config: &Config,
+    dir: &Path,
+    [base, ours, theirs]: [Option<&str>; 3],
+    both: &BTreeSet<String>,
+    pulled: &mut Pulled,
+) -> Result<(), Box<dyn Error>> {
+    if both.is_empty() {
+        return Ok(());
+    }
+    let staging = staging(dir)?;
+    let result = (|| {
+        for (side, commit) in [("base", base), ("ours", ours), ("theirs", theirs)] {
+            fs::create_dir_all(staging.join(side))?;
+            let Some(commit) = commit else {
+                continue;
+            };
+            for name in both {
+                let object = format!("{commit}:{name}");
+                if git(dir, &["cat-file", "-e", &object]).is_ok() {
+                    fs::write(staging.join(side).join(name), show(dir, &object)?)?;
+                }
+            }
+        }
+        merge_sides(store, config, dir, &staging, pulled)
+    })();
+    fs::remove_dir_all(&staging)?;
+    result
+}
+
+/// The contents of the git `object`, as in `HEAD:2024-03-01.csv`.
+fn show(dir: &Path, object: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["show", object])
+        .output()
+        .map_err(|err| format!("cannot run git: {err}"))?;
+    if !output.status.success() {
+        Err(format!(
+            "git show {object} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))?
+    }
+    Ok(output.stdout)
+}
+
+/// Commits the changes here with `message` and merges those of the remote.
+pub fn pull(store: &dyn Store, config: &Config, message: &str) -> Result<Pulled, Box<dyn Error>> {
+    let dir = data_dir()?;
+    let _lock = store.lock()?;
+    let remote_branch = fetch(&dir, config)?;
+    commit(&dir, message)?;
+    let Some(remote_branch) = remote_branch else {
+        return Ok(Pulled::default());
+    };
+    let files = changed(&dir, "HEAD", &remote_branch)?;
+    let both = changed(&dir, &remote_branch, "HEAD")?
+        .intersection(&files)
+        .cloned()
+        .collect();
+    let ours = git(&dir, &["rev-parse", "HEAD"])?.trim().to_owned();
+    let base = git(&dir, &["merge-base", "HEAD", &remote_branch])
+        .ok()
+        .map(|base| base.trim().to_owned());
+    let mut args = identity(&dir);
+    args.extend([
+        "merge",
+        "--quiet",
+        "--no-edit",
+        "--allow-unrelated-histories",
+        &remote_branch,
+    ]);
+    if let Err(err) = git(&dir, &args) {
+        // Leave the records as they were rather than half merged.
+        let _ = git(&dir, &["merge", "--abort"]);
+        Err(format!(
+            "cannot merge the records of {remote_branch}: {err}"
+        ))?
+    }
+    let mut pulled = Pulled {
+        files: files.into_iter().collect(),
+        ..Pulled::default()
+    };
+    let commits = [
+        base.as_deref(),
+        Some(ours.as_str()),
+        Some(remote_branch.as_str()),
+    ];
+    merge_days(store, config, &dir, commits, &both, &mut pulled)?;
+    commit(&dir, "Merge the stamps of days changed on both sides")?;
+    check(store, &mut pulled);
+    Ok(pulled)
+}
+
+/// Commits the changes here with `message` and pushes them, returning the
+/// files changed. Fails if the remote has changes not pulled yet.
+pub fn push(
+    store: &dyn Store,
+    config: &Config,
+    message: &str,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let dir = data_dir()?;
+    let _lock = store.lock()?;
+    let remote_branch = fetch(&dir, config)?;
+    commit(&dir, message)?;
+    let pushed = match remote_branch {
+        Some(remote_branch) => {
+            if !git(&dir, &["rev-list", &format!("HEAD..{remote_branch}")])?.is_empty() {
+                Err(format!(
+                    "{} has changes not pulled yet, run 'azk sync pull' first",
+                    config.sync_remote
+                ))?
+            }
+            changed(&dir, &remote_branch, "HEAD")?
+        }
+        None => names(&git(&dir, &["ls-tree", "-r", "--name-only", "HEAD"])?),
+    };
+    git(
+        &dir,
+        &[
+            "push",
+            "--quiet",
+            "origin",
+            &format!("HEAD:{}", config.sync_branch),
+        ],
+    )?;
+    Ok(pushed.into_iter().collect())
+}
+
+/// The files changed here and on the remote since they were last in step,
+/// without committing anything.
+pub fn status(store: &dyn Store, config: &Config) -> Result<Status, Box<dyn Error>> {
+    let dir = data_dir()?;
+    let _lock = store.lock()?;
+    let remote_branch = fetch(&dir, config)?;
+    let porcelain = git(&dir, &["status", "--porcelain", "--untracked-files=all"])?;
+    let mut local = names(
+        &porcelain
+            .lines()
+            .filter_map(|line| line.get(3..))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    );
+    let head = git(&dir, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok();
+    let mut remote = BTreeSet::new();
+    match (head, remote_branch) {
+        (true, Some(remote_branch)) => {
+            local.extend(changed(&dir, &remote_branch, "HEAD")?);
+            remote = changed(&dir, "HEAD", &remote_branch)?;
+        }
+        (true, None) => local.extend(names(&git(
+            &dir,
+            &["ls-tree", "-r", "--name-only", "HEAD"],
+        )?)),
+        (false, Some(remote_branch)) => {
+            remote = names(&git(
+                &dir,
+                &["ls-tree", "-r", "--name-only", &remote_branch],
+            )?);
+        }
+        (false, None) => {}
+    }
+    Ok(Status::new(local, remote))
+}
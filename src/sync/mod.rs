@@ -0,0 +1,846 @@
+//! Keeping the records of several machines in step, by pushing the files of
+//! the data dir to a remote and pulling those the others pushed there. The
+//! remote is a git repository, see [`git`], or else a [`SyncBackend`] holding
+//! copies of the files, as set by `sync_backend`. Days changed on both sides
+//! are merged stamp by stamp against how they were when last in step, see
+//! [`merge`].
+
+pub mod git;
+mod rsync;
+mod s3;
+mod webdav;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Component, Path};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::model::Record;
+use crate::store::{self, data_dir, read_work_time, Store};
+use crate::{audit, backup, holidays, off, projects, purge, secret};
+
+pub use rsync::Rsync;
+pub use s3::S3;
+pub use webdav::WebDav;
+
+/// The names of the backends, as set in the `sync_backend` config key.
+pub const BACKENDS: [&str; 4] = ["git", "rsync", "webdav", "s3"];
+
+/// The keyring entry holding the password for `sync_username`, or with S3
+/// the secret access key.
+pub const SECRET: &str = "sync";
+
+/// The file on the remote listing the files pushed there, as a [`Manifest`].
+const MANIFEST: &str = "azk-sync.json";
+
+/// The file in the data dir keeping the [`Manifest`] of the last push or
+/// pull, which tells what changed since on either side.
+pub const STATE: &str = "sync-state.json";
+
+/// The directory in the data dir holding the files synced as they were after
+/// the last push or pull, which the days changed on both sides since are
+/// merged against.
+pub const BASE: &str = "sync-base";
+
+/// The files of the data dir synced besides the days and their archives.
+const DATA_FILES: [&str; 6] = [
+    store::JSONL_FILE,
+    store::ENCRYPTION_FILE,
+    projects::FILE_NAME,
+    off::FILE_NAME,
+    holidays::FILE_NAME,
+    purge::TOTALS_FILE,
+];
+
+/// Whether `name` is that of a file synced: a day, an archive of a month,
+/// or one of [`DATA_FILES`], right in the data dir.
+fn synced(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        return false;
+    }
+    let is_date = |stem: &str, format: &str| NaiveDate::parse_from_str(stem, format).is_ok();
+    DATA_FILES.contains(&name)
+        || name
+            .strip_suffix(".csv.enc")
+            .or_else(|| name.strip_suffix(".csv"))
+            .is_some_and(|stem| is_date(stem, "%Y-%m-%d"))
+        || name
+            .strip_suffix(".tar.zst")
+            .is_some_and(|stem| is_date(&format!("{stem}-01"), "%Y-%m-%d"))
+}
+
+/// Fails unless `name`, as given by the remote at `location`, is that of a
+/// file synced, so that a remote cannot have files outside the data dir
+/// written or removed.
+fn check_name(name: &str, location: &str) -> Result<(), Box<dyn Error>> {
+    if !synced(name) {
+        Err(format!(
+            "{location} lists the file '{name}', which azk does not sync"
+        ))?
+    }
+    Ok(())
+}
+
+/// The SHA-256 of every file synced, by name.
+#[derive(Default, Deserialize, Serialize)]
+struct Manifest {
+    files: BTreeMap<String, String>,
+}
+
+/// Where `azk sync` keeps copies of the files of the data dir, for backends
+/// other than git. Files are named by their path in the data dir, with `/`
+/// between directories.
+pub trait SyncBackend {
+    /// Describes the remote, for messages.
+    fn location(&self) -> String;
+
+    /// The contents of the file `name`, or `None` if there is none.
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>>;
+
+    /// Writes the file `name`, replacing the one there.
+    fn put(&self, name: &str, contents: &[u8]) -> Result<(), Box<dyn Error>>;
+
+    /// Removes the file `name`, if there is one.
+    fn remove(&self, name: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Copies the files `names` in `dir` to the remote.
+    fn upload(&self, dir: &Path, names: &[String]) -> Result<(), Box<dyn Error>> {
+        for name in names {
+            self.put(name, &fs::read(dir.join(name))?)?;
+        }
+        Ok(())
+    }
+
+    /// Copies the files `names` of the remote into `dir`, replacing those
+    /// there.
+    fn download(&self, dir: &Path, names: &[String]) -> Result<(), Box<dyn Error>> {
+        for name in names {
+            check_name(name, &self.location())?;
+            let contents = self
+                .get(name)?
+                .ok_or_else(|| format!("{} has no {name}", self.location()))?;
+            write(&dir.join(name), &contents)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes `path` through a temporary file, so it is never read half written.
+fn write(path: &Path, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut tmp_path = path.to_owned().into_os_string();
+    tmp_path.push(".tmp");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub fn sha256(contents: &[u8]) -> String {
+    hex(&Sha256::digest(contents))
+}
+
+/// The files changed here, on the remote, and on both sides since they were
+/// last in step.
+#[derive(Default)]
+pub struct Status {
+    pub local: Vec<String>,
+    pub remote: Vec<String>,
+    pub both: Vec<String>,
+}
+
+impl Status {
+    /// Sorts the files changed `local`ly and on the `remote`, with those in
+    /// both apart.
+    fn new(local: BTreeSet<String>, remote: BTreeSet<String>) -> Status {
+        Status {
+            local: local.difference(&remote).cloned().collect(),
+            remote: remote.difference(&local).cloned().collect(),
+            both: local.intersection(&remote).cloned().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.local.is_empty() && self.remote.is_empty() && self.both.is_empty()
+    }
+}
+
+/// What a pull changed here.
+#[derive(Default)]
+pub struct Pulled {
+    /// The files changed on the remote.
+    pub files: Vec<String>,
+    /// The days changed on both sides, whose stamps were merged.
+    pub merged: Vec<NaiveDate>,
+    /// The merged days where both sides changed the same stamps, which keep
+    /// what both made of them, see [`Merge`].
+    pub conflicts: Vec<NaiveDate>,
+    /// The merged days that do not add up, as when both sides were started,
+    /// with why.
+    pub problems: Vec<(NaiveDate, String)>,
+}
+
+/// What tells stamps apart: all but the UTC offset they were taken in.
+type Key<'a> = (DateTime<Utc>, &'a str, Option<&'a str>, Option<&'a str>);
+
+fn key(record: &Record) -> Key<'_> {
+    (
+        record.timestamp.with_timezone(&Utc),
+        &record.kind,
+        record.project.as_deref(),
+        record.note.as_deref(),
+    )
+}
+
+/// The stamps of a day merged from both sides.
+pub struct Merge {
+    pub records: Vec<Record>,
+    /// Whether both sides removed the same stamps and added others, as when
+    /// they changed a stamp each their own way. Both ways are kept then.
+    pub conflict: bool,
+}
+
+fn keys(records: &[Record]) -> BTreeSet<Key<'_>> {
+    records.iter().map(key).collect()
+}
+
+/// Merges the stamps of a day as changed since `base` here, in `ours`, and
+/// on the other side, in `theirs`. A day changed on one side only is taken
+/// from that side. Otherwise the stamps added on either side are kept and
+/// those removed on either are dropped, so that a stamp changed is one
+/// removed and one added. They are ordered by time, then kind, project,
+/// note and UTC offset, so that either side merges to the same.
+pub fn merge(base: &[Record], ours: &[Record], theirs: &[Record]) -> Merge {
+    if same(ours, base) || same(ours, theirs) {
+        return Merge {
+            records: theirs.to_vec(),
+            conflict: false,
+        };
+    }
+    if same(theirs, base) {
+        return Merge {
+            records: ours.to_vec(),
+            conflict: false,
+        };
+    }
+    let (base_keys, our_keys, their_keys) = (keys(base), keys(ours), keys(theirs));
+    let mut records: Vec<Record> = ours
+        .iter()
+        .chain(theirs)
+        .filter(|record| {
+            let key = key(record);
+            !base_keys.contains(&key) || our_keys.contains(&key) && their_keys.contains(&key)
+        })
+        .cloned()
+        .collect();
+    records.sort_by(|a, b| {
+        key(a).cmp(&key(b)).then_with(|| {
+            let offset = |record: &Record| record.timestamp.offset().local_minus_utc();
+            offset(a).cmp(&offset(b))
+        })
+    });
+    records.dedup_by(|a, b| key(a) == key(b));
+    let removed_on_both = base_keys
+        .iter()
+        .any(|key| !our_keys.contains(key) && !their_keys.contains(key));
+    let added = |keys: &BTreeSet<_>| !keys.is_subset(&base_keys);
+    Merge {
+        records,
+        conflict: removed_on_both && added(&our_keys) && added(&their_keys),
+    }
+}
+
+/// Whether `a` and `b` are the same stamps in the same order.
+fn same(a: &[Record], b: &[Record]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| key(a) == key(b) && a.timestamp.offset() == b.timestamp.offset())
+}
+
+/// An empty directory next to the data dir `dir` to stage a merge in.
+fn staging(dir: &Path) -> Result<std::path::PathBuf, Box<dyn Error>> {
+    let mut staging = dir.to_owned().into_os_string();
+    staging.push(".sync");
+    let staging = std::path::PathBuf::from(staging);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+    Ok(staging)
+}
+
+/// Opens the `side` of a merge in `staging` as a store like those of the
+/// data dir `dir`, whose days are encrypted like those of `dir`, if at all.
+fn open_side(
+    config: &Config,
+    dir: &Path,
+    staging: &Path,
+    side: &str,
+) -> Result<Box<dyn Store>, Box<dyn Error>> {
+    let side = staging.join(side);
+    fs::create_dir_all(&side)?;
+    let encryption = dir.join(store::ENCRYPTION_FILE);
+    if encryption.exists() {
+        fs::copy(&encryption, side.join(store::ENCRYPTION_FILE))?;
+    }
+    store::open_backend_in(&config.store, side)
+}
+
+/// Merges the days staged in `staging` as they were when last in step, in
+/// `base`, as changed here since, in `ours`, and as changed on the remote,
+/// in `theirs`, into `store` in `dir`. Notes those changed on both sides in
+/// `pulled`.
+fn merge_sides(
+    store: &dyn Store,
+    config: &Config,
+    dir: &Path,
+    staging: &Path,
+    pulled: &mut Pulled,
+) -> Result<(), Box<dyn Error>> {
+    let base = open_side(config, dir, staging, "base")?;
+    let ours = open_side(config, dir, staging, "ours")?;
+    let theirs = open_side(config, dir, staging, "theirs")?;
+    let mut dates = BTreeSet::new();
+    for side in [&base, &ours, &theirs] {
+        dates.extend(side.list_days()?);
+    }
+    for date in dates {
+        let read = |side: &dyn Store| -> Result<Vec<Record>, Box<dyn Error>> {
+            Ok(side.read_day(date)?.unwrap_or_default())
+        };
+        let (base, ours, theirs) = (
+            read(base.as_ref())?,
+            read(ours.as_ref())?,
+            read(theirs.as_ref())?,
+        );
+        let merge = merge(&base, &ours, &theirs);
+        if !same(&merge.records, &read(store)?) {
+            store.write_day(date, &merge.records)?;
+        }
+        if !same(&ours, &base) && !same(&theirs, &base) && !same(&ours, &theirs) {
+            pulled.merged.push(date);
+        }
+        if merge.conflict {
+            pulled.conflicts.push(date);
+        }
+    }
+    Ok(())
+}
+
+/// Keeps the file `name` as it is in `from`, or its removal if `None`, as
+/// the one of the last sync in `dir`, see [`BASE`].
+fn set_base(dir: &Path, name: &str, from: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let base = dir.join(BASE).join(name);
+    match from {
+        Some(from) => write(&base, &fs::read(from.join(name))?),
+        None => match fs::remove_file(&base) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err)?,
+            _ => Ok(()),
+        },
+    }
+}
+
+/// Notes the merged days of `pulled` that do not add up.
+fn check(store: &dyn Store, pulled: &mut Pulled) {
+    for date in &pulled.merged {
+        if let Err(err) = read_work_time(store, *date, None) {
+            pulled.problems.push((*date, err.to_string()));
+        }
+    }
+}
+
+/// The name of the file at `relative` in the data dir.
+fn name(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The remote set by `sync_remote`.
+fn remote(config: &Config) -> Result<&str, Box<dyn Error>> {
+    if config.sync_remote.is_empty() {
+        Err("sync_remote is not set, see 'azk config set sync_remote'")?
+    }
+    Ok(&config.sync_remote)
+}
+
+/// The secret of `sync_username` in the keyring.
+fn password() -> Result<String, Box<dyn Error>> {
+    Ok(secret::get(SECRET)?
+        .ok_or_else(|| format!("there is no '{SECRET}' password in the keyring"))?)
+}
+
+/// Opens the backend set by `sync_backend`, which must not be git.
+pub fn open(config: &Config) -> Result<Box<dyn SyncBackend>, Box<dyn Error>> {
+    let remote = remote(config)?;
+    match config.sync_backend.as_str() {
+        "rsync" => Ok(Box::new(Rsync::new(remote))),
+        "webdav" => {
+            let login = if config.sync_username.is_empty() {
+                None
+            } else {
+                Some((config.sync_username.as_str(), password()?))
+            };
+            Ok(Box::new(WebDav::new(remote, login)))
+        }
+        "s3" => Ok(Box::new(S3::new(
+            remote,
+            &config.sync_region,
+            &config.sync_username,
+            &password()?,
+        )?)),
+        backend => Err(format!(
+            "unknown sync backend '{backend}', expected one of {}",
+            BACKENDS.join(", ")
+        ))?,
+    }
+}
+
+/// The files of the data dir `dir` that are synced: days, archives and
+/// [`DATA_FILES`], so not those backups leave out, nor hidden ones like
+/// those of git.
+fn local(dir: &Path) -> Result<Manifest, Box<dyn Error>> {
+    let mut files = BTreeMap::new();
+    for relative in backup::files(dir)? {
+        let hidden = relative.components().any(|component| {
+            matches!(component, Component::Normal(name) if name.to_string_lossy().starts_with('.'))
+        });
+        let name = name(&relative);
+        if hidden || backup::skipped(&relative) || !synced(&name) {
+            continue;
+        }
+        files.insert(name, sha256(&fs::read(dir.join(&relative))?));
+    }
+    Ok(Manifest { files })
+}
+
+/// The manifests of the data dir, of the remote and of the last sync.
+struct Sides {
+    local: Manifest,
+    remote: Manifest,
+    base: Manifest,
+}
+
+impl Sides {
+    fn read(backend: &dyn SyncBackend, dir: &Path) -> Result<Sides, Box<dyn Error>> {
+        let remote: Manifest = match backend.get(MANIFEST)? {
+            Some(contents) => serde_json::from_slice(&contents)
+                .map_err(|err| format!("{}: {MANIFEST}: {err}", backend.location()))?,
+            None => Manifest::default(),
+        };
+        for name in remote.files.keys() {
+            check_name(name, &format!("{}: {MANIFEST}", backend.location()))?;
+        }
+        let base = match fs::read(dir.join(STATE)) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .map_err(|err| format!("{}: {err}", dir.join(STATE).display()))?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Manifest::default(),
+            Err(err) => Err(err)?,
+        };
+        Ok(Sides {
+            local: local(dir)?,
+            remote,
+            base,
+        })
+    }
+
+    /// The files changed on each side since the last sync. Files changed
+    /// the same way on both count as unchanged.
+    fn status(&self) -> Status {
+        let changed = |side: &Manifest| -> BTreeSet<String> {
+            side.files
+                .keys()
+                .chain(self.base.files.keys())
+                .filter(|name| side.files.get(*name) != self.base.files.get(*name))
+                .filter(|name| self.local.files.get(*name) != self.remote.files.get(*name))
+                .cloned()
+                .collect()
+        };
+        Status::new(changed(&self.local), changed(&self.remote))
+    }
+}
+
+/// Saves `manifest` as the state of the last sync in `dir`.
+fn save_state(dir: &Path, manifest: &Manifest) -> Result<(), Box<dyn Error>> {
+    write(&dir.join(STATE), &serde_json::to_vec_pretty(manifest)?)
+}
+
+/// The files changed here and on the remote since the last sync.
+pub fn status(store: &dyn Store, config: &Config) -> Result<Status, Box<dyn Error>> {
+    if config.sync_backend == "git" {
+        return git::status(store, config);
+    }
+    let backend = open(config)?;
+    let dir = data_dir()?;
+    let _lock = store.lock()?;
+    Ok(Sides::read(backend.as_ref(), &dir)?.status())
+}
+
+/// Sends the files changed here to the remote and returns them. Fails if the
+/// remote has changes not pulled yet. Git commits them with `message`.
+pub fn push(
+    store: &dyn Store,
+    config: &Config,
+    message: &str,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    if config.sync_backend == "git" {
+        return git::push(store, config, message);
+    }
+    let backend = open(config)?;
+    let dir = data_dir()?;
+    let _lock = store.lock()?;
+    let sides = Sides::read(backend.as_ref(), &dir)?;
+    let status = sides.status();
+    if !status.remote.is_empty() || !status.both.is_empty() {
+        Err(format!(
+            "{} has changes not pulled yet, run 'azk sync pull' first",
+            backend.location()
+        ))?
+    }
+    if status.local.is_empty() {
+        save_state(&dir, &sides.local)?;
+        return Ok(Vec::new());
+    }
+    let (sent, removed): (Vec<String>, Vec<String>) = status
+        .local
+        .iter()
+        .cloned()
+        .partition(|name| sides.local.files.contains_key(name));
+    backend.upload(&dir, &sent)?;
+    for name in &removed {
+        backend.remove(name)?;
+    }
+    backend.put(MANIFEST, &serde_json::to_vec_pretty(&sides.local)?)?;
+    save_state(&dir, &sides.local)?;
+    for name in &sent {
+        set_base(&dir, name, Some(&dir))?;
+    }
+    for name in &removed {
+        set_base(&dir, name, None)?;
+    }
+    Ok(status.local)
+}
+
+/// Merges the stamps in the files `both` of the remote, changed here too,
+/// into those of `store` in `dir`, noting the days merged in `pulled`.
+/// Fails if any of them holds something else than days.
+fn merge_files(
+    store: &dyn Store,
+    backend: &dyn SyncBackend,
+    config: &Config,
+    dir: &Path,
+    both: &[String],
+    pulled: &mut Pulled,
+) -> Result<(), Box<dyn Error>> {
+    if both.is_empty() {
+        return Ok(());
+    }
+    let staging = staging(dir)?;
+    let result = merge_staged(store, backend, config, dir, &staging, both, pulled);
+    fs::remove_dir_all(&staging)?;
+    result
+}
+
+fn merge_staged(
+    store: &dyn Store,
+    backend: &dyn SyncBackend,
+    config: &Config,
+    dir: &Path,
+    staging: &Path,
+    both: &[String],
+    pulled: &mut Pulled,
+) -> Result<(), Box<dyn Error>> {
+    let theirs_dir = staging.join("theirs");
+    backend.download(&theirs_dir, both)?;
+    let theirs = open_side(config, dir, staging, "theirs")?;
+    let day_files: BTreeSet<String> = theirs
+        .list_days()?
+        .iter()
+        .filter_map(|date| Some(name(theirs.file(*date).strip_prefix(&theirs_dir).ok()?)))
+        .collect();
+    let others: Vec<&str> = both
+        .iter()
+        .filter(|name| !day_files.contains(*name))
+        .map(String::as_str)
+        .collect();
+    if !others.is_empty() {
+        Err(format!(
+            "changed here and on {}, not pulling them: {}",
+            backend.location(),
+            others.join(", ")
+        ))?
+    }
+
+    for (side, from) in [("ours", dir.to_owned()), ("base", dir.join(BASE))] {
+        for name in both {
+            if from.join(name).exists() {
+                write(&staging.join(side).join(name), &fs::read(from.join(name))?)?;
+            }
+        }
+    }
+    merge_sides(store, config, dir, staging, pulled)?;
+    for name in both {
+        set_base(dir, name, Some(&theirs_dir))?;
+    }
+    Ok(())
+}
+
+/// Fetches the files changed on the remote, merging the days changed here
+/// too. Fails if other files changed on both sides. Git commits the changes
+/// here with `message` and merges instead. The days pulled are logged with
+/// `audit` on.
+pub fn pull(store: &dyn Store, config: &Config, message: &str) -> Result<Pulled, Box<dyn Error>> {
+    let pulled = if config.sync_backend == "git" {
+        git::pull(store, config, message)?
+    } else {
+        pull_files(store, config)?
+    };
+    if config.audit {
+        audit::note(store, &data_dir()?, "pull", Some(&pulled.files))?;
+    }
+    Ok(pulled)
+}
+
+/// Downloads the files `names` of the remote into `dir`, once all of them
+/// are as listed in its `manifest`, so that none is replaced by a version
+/// that changed while pulling.
+fn fetch(
+    backend: &dyn SyncBackend,
+    dir: &Path,
+    names: &[String],
+    manifest: &Manifest,
+) -> Result<(), Box<dyn Error>> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    let staging = staging(dir)?;
+    let result = fetch_staged(backend, dir, &staging, names, manifest);
+    fs::remove_dir_all(&staging)?;
+    result
+}
+
+fn fetch_staged(
+    backend: &dyn SyncBackend,
+    dir: &Path,
+    staging: &Path,
+    names: &[String],
+    manifest: &Manifest,
+) -> Result<(), Box<dyn Error>> {
+    backend.download(staging, names)?;
+    for name in names {
+        if sha256(&fs::read(staging.join(name))?) != manifest.files[name] {
+            Err(format!(
+                "{name} changed on {} while pulling it, pull again",
+                backend.location()
+            ))?
+        }
+    }
+    for name in names {
+        let path = dir.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(staging.join(name), path)?;
+    }
+    Ok(())
+}
+
+fn pull_files(store: &dyn Store, config: &Config) -> Result<Pulled, Box<dyn Error>> {
+    let backend = open(config)?;
+    let dir = data_dir()?;
+    let _lock = store.lock()?;
+    let sides = Sides::read(backend.as_ref(), &dir)?;
+    let status = sides.status();
+    // Files removed on the remote but changed here are kept as they are.
+    let both: Vec<String> = status
+        .both
+        .iter()
+        .filter(|name| sides.remote.files.contains_key(*name))
+        .cloned()
+        .collect();
+    let mut pulled = Pulled::default();
+    merge_files(store, backend.as_ref(), config, &dir, &both, &mut pulled)?;
+    let (fetched, removed): (Vec<String>, Vec<String>) = status
+        .remote
+        .iter()
+        .cloned()
+        .partition(|name| sides.remote.files.contains_key(name));
+    fetch(backend.as_ref(), &dir, &fetched, &sides.remote)?;
+    for name in &removed {
+        match fs::remove_file(dir.join(name)) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err)?,
+            _ => {}
+        }
+    }
+    for name in &fetched {
+        set_base(&dir, name, Some(&dir))?;
+    }
+    for name in &removed {
+        set_base(&dir, name, None)?;
+    }
+    // What is only changed here, or merged, stays a change against the
+    // remote.
+    save_state(&dir, &sides.remote)?;
+    pulled.files = status.remote.into_iter().chain(both).collect();
+    check(store, &mut pulled);
+    Ok(pulled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures::record;
+
+    fn stamps(records: &[Record]) -> Vec<(String, String)> {
+        records
+            .iter()
+            .map(|record| (record.kind.clone(), record.time()))
+            .collect()
+    }
+
+    #[test]
+    fn takes_a_day_changed_on_one_side() {
+        let base = [
+            record("2024-03-01", "strt", "09:00"),
+            record("2024-03-01", "stop", "12:00"),
+        ];
+        let edited = [
+            record("2024-03-01", "strt", "09:00"),
+            record("2024-03-01", "stop", "12:30"),
+        ];
+        let merge = merge(&base, &base, &edited);
+        assert!(same(&merge.records, &edited) && !merge.conflict);
+        assert!(same(&super::merge(&base, &edited, &base).records, &edited));
+
+        let deleted = [record("2024-03-01", "strt", "09:00")];
+        assert!(same(
+            &super::merge(&base, &deleted, &base).records,
+            &deleted
+        ));
+        assert!(super::merge(&base, &base, &[]).records.is_empty());
+    }
+
+    #[test]
+    fn merges_changes_to_different_stamps() {
+        let base = [
+            record("2024-03-01", "strt", "09:00"),
+            record("2024-03-01", "stop", "12:00"),
+        ];
+        let ours = [
+            record("2024-03-01", "strt", "08:45"),
+            record("2024-03-01", "stop", "12:00"),
+        ];
+        let theirs = [
+            record("2024-03-01", "strt", "09:00"),
+            record("2024-03-01", "stop", "12:00"),
+            record("2024-03-01", "strt", "13:00"),
+            record("2024-03-01", "stop", "17:00"),
+        ];
+        let merge = merge(&base, &ours, &theirs);
+        assert_eq!(
+            stamps(&merge.records),
+            [
+                ("strt".to_owned(), "08:45:00".to_owned()),
+                ("stop".to_owned(), "12:00:00".to_owned()),
+                ("strt".to_owned(), "13:00:00".to_owned()),
+                ("stop".to_owned(), "17:00:00".to_owned()),
+            ]
+        );
+        assert!(!merge.conflict);
+        assert!(same(
+            &merge.records,
+            &super::merge(&base, &theirs, &ours).records
+        ));
+    }
+
+    #[test]
+    fn drops_a_stamp_deleted_on_one_side_while_the_other_adds() {
+        let base = [
+            record("2024-03-01", "strt", "09:00"),
+            record("2024-03-01", "stop", "12:00"),
+        ];
+        let ours = [record("2024-03-01", "strt", "09:00")];
+        let theirs = [
+            record("2024-03-01", "strt", "09:00"),
+            record("2024-03-01", "stop", "12:00"),
+            record("2024-03-01", "strt", "13:00"),
+        ];
+        let merge = merge(&base, &ours, &theirs);
+        assert_eq!(
+            stamps(&merge.records),
+            [
+                ("strt".to_owned(), "09:00:00".to_owned()),
+                ("strt".to_owned(), "13:00:00".to_owned()),
+            ]
+        );
+        assert!(!merge.conflict);
+    }
+
+    #[test]
+    fn reports_a_stamp_changed_on_both_sides() {
+        let base = [
+            record("2024-03-01", "strt", "09:00"),
+            record("2024-03-01", "stop", "12:00"),
+        ];
+        let ours = [
+            record("2024-03-01", "strt", "09:00"),
+            record("2024-03-01", "stop", "12:15"),
+        ];
+        let theirs = [
+            record("2024-03-01", "strt", "09:00"),
+            record("2024-03-01", "stop", "12:30"),
+        ];
+        let merge = merge(&base, &ours, &theirs);
+        assert_eq!(merge.records.len(), 3);
+        assert!(merge.conflict);
+
+        let noted = [
+            Record {
+                note: Some("standup".to_owned()),
+                ..record("2024-03-01", "strt", "09:00")
+            },
+            base[1].clone(),
+        ];
+        assert!(same(&super::merge(&base, &noted, &noted).records, &noted));
+    }
+
+    #[test]
+    fn keeps_a_stamp_taken_in_two_offsets_once() {
+        let ours = [record("2024-03-01", "strt", "09:00")];
+        let mut theirs = ours.clone();
+        theirs[0].timestamp = theirs[0]
+            .timestamp
+            .with_timezone(&chrono::FixedOffset::east_opt(7200).unwrap());
+        assert_eq!(merge(&[], &ours, &theirs).records.len(), 1);
+    }
+
+    #[test]
+    fn refuses_names_outside_the_data_dir() {
+        assert!(synced("2024-03-01.csv"));
+        assert!(synced("2024-03-01.csv.enc"));
+        assert!(synced("2024-03.tar.zst"));
+        assert!(synced("projects.csv"));
+        assert!(!synced("../2024-03-01.csv"));
+        assert!(!synced("/etc/2024-03-01.csv"));
+        assert!(!synced("profiles/work/2024-03-01.csv"));
+        assert!(!synced("hooks"));
+    }
+}
@@ -0,0 +1,167 @@
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use super::{check_name, SyncBackend};
+
+/// The exit status of rsync when some files were not transferred, as when
+/// they are missing.
+const PARTIAL: i32 = 23;
+
+/// What rsync says about a file that is missing, among other errors that
+/// end in [`PARTIAL`], with its messages in English.
+const MISSING: &str = "No such file or directory";
+
+/// A directory copied to with rsync, over ssh for remotes like
+/// `me@example.com:azk`, or on this machine for a plain path.
+pub struct Rsync {
+    remote: String,
+}
+
+impl Rsync {
+    pub fn new(remote: &str) -> Rsync {
+        Rsync {
+            remote: remote.trim_end_matches('/').to_owned(),
+        }
+    }
+
+    /// Runs rsync with `args`, passing `input` to it, and fails unless it
+    /// succeeded or only missed files.
+    fn run(&self, args: &[&str], input: &str) -> Result<Output, Box<dyn Error>> {
+        let mut child = Command::new("rsync")
+            .env("LC_ALL", "C")
+            .arg("--quiet")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("cannot run rsync: {err}"))?;
+        child
+            .stdin
+            .take()
+            .expect("rsync's stdin is piped")
+            .write_all(input.as_bytes())?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() && output.status.code() != Some(PARTIAL) {
+            Err(format!(
+                "rsync with {} failed: {}",
+                self.remote,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))?
+        }
+        Ok(output)
+    }
+
+    /// A path for a temporary file or directory of this process.
+    fn scratch(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("azk-rsync-{}-{name}", std::process::id()))
+    }
+
+    fn path(&self, name: &str) -> String {
+        format!("{}/{name}", self.remote)
+    }
+}
+
+impl SyncBackend for Rsync {
+    fn location(&self) -> String {
+        self.remote.clone()
+    }
+
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        let tmp_path = Rsync::scratch("get");
+        let output = self.run(&[&self.path(name), &tmp_path.to_string_lossy()], "")?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.contains(MISSING) {
+                return Ok(None);
+            }
+            Err(format!(
+                "rsync could not copy {name} from {}: {}",
+                self.remote,
+                stderr.trim()
+            ))?
+        }
+        let contents = fs::read(&tmp_path)?;
+        fs::remove_file(&tmp_path)?;
+        Ok(Some(contents))
+    }
+
+    fn put(&self, name: &str, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+        let tmp_path = Rsync::scratch("put");
+        fs::write(&tmp_path, contents)?;
+        let result = self.run(
+            &["--mkpath", &tmp_path.to_string_lossy(), &self.path(name)],
+            "",
+        );
+        fs::remove_file(&tmp_path)?;
+        if !result?.status.success() {
+            Err(format!("rsync could not copy {name} to {}", self.remote))?
+        }
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        // Syncing an empty directory with deletion, limited to the file and
+        // the directories leading to it.
+        let empty = Rsync::scratch("empty");
+        fs::create_dir_all(&empty)?;
+        let mut args = vec!["--recursive".to_owned(), "--delete".to_owned()];
+        let mut prefix = String::new();
+        for part in name.split('/') {
+            prefix.push('/');
+            prefix.push_str(part);
+            args.push(format!("--include={prefix}"));
+        }
+        args.push("--exclude=*".to_owned());
+        args.push(format!("{}/", empty.display()));
+        args.push(format!("{}/", self.remote));
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let result = self.run(&args, "");
+        fs::remove_dir(&empty)?;
+        result?;
+        Ok(())
+    }
+
+    fn upload(&self, dir: &Path, names: &[String]) -> Result<(), Box<dyn Error>> {
+        let output = self.run(
+            &[
+                "--mkpath",
+                "--files-from=-",
+                &format!("{}/", dir.display()),
+                &format!("{}/", self.remote),
+            ],
+            &names.join("\n"),
+        )?;
+        if !output.status.success() {
+            Err(format!(
+                "rsync could not copy every file to {}",
+                self.remote
+            ))?
+        }
+        Ok(())
+    }
+
+    fn download(&self, dir: &Path, names: &[String]) -> Result<(), Box<dyn Error>> {
+        for name in names {
+            check_name(name, &self.remote)?;
+        }
+        let output = self.run(
+            &[
+                "--files-from=-",
+                &format!("{}/", self.remote),
+                &format!("{}/", dir.display()),
+            ],
+            &names.join("\n"),
+        )?;
+        if !output.status.success() {
+            Err(format!(
+                "rsync could not copy every file from {}",
+                self.remote
+            ))?
+        }
+        Ok(())
+    }
+}
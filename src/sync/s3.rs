@@ -0,0 +1,140 @@
+use std::error::Error;
+use std::io::Read;
+use std::time::Duration;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use super::{hex, sha256, SyncBackend};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Objects in a bucket of S3 or compatible storage like MinIO, addressed by
+/// path as in `https://s3.example.com/bucket/prefix`, and signed with
+/// Signature Version 4.
+pub struct S3 {
+    /// The scheme and host, as in `https://s3.example.com`.
+    endpoint: String,
+    host: String,
+    /// The bucket and the prefix of the objects, as in `bucket/prefix`.
+    path: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encodes `name` for the path of a request, except for `/`.
+fn encode(name: &str) -> String {
+    name.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+impl S3 {
+    pub fn new(
+        url: &str,
+        region: &str,
+        access_key: &str,
+        secret_key: &str,
+    ) -> Result<S3, Box<dyn Error>> {
+        let invalid =
+            || format!("invalid S3 remote '{url}', expected https://HOST/BUCKET[/PREFIX]");
+        let (scheme, rest) = url
+            .split_once("://")
+            .filter(|(scheme, _)| matches!(*scheme, "http" | "https"))
+            .ok_or_else(invalid)?;
+        let (host, path) = rest.split_once('/').ok_or_else(invalid)?;
+        let path = path.trim_matches('/');
+        if host.is_empty() || path.is_empty() {
+            Err(invalid())?
+        }
+        if access_key.is_empty() {
+            Err("sync_username is not set to the access key")?
+        }
+        Ok(S3 {
+            endpoint: format!("{scheme}://{host}"),
+            host: host.to_owned(),
+            path: path.to_owned(),
+            region: region.to_owned(),
+            access_key: access_key.to_owned(),
+            secret_key: secret_key.to_owned(),
+        })
+    }
+
+    /// A request for the object `name`, signed for sending `payload`.
+    fn request(&self, method: &str, name: &str, payload: &[u8]) -> ureq::Request {
+        let now = Utc::now();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let path = format!("/{}/{}", encode(&self.path), encode(name));
+        let payload_hash = sha256(payload);
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{method}\n{path}\n\nhost:{}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{timestamp}\n\n{signed_headers}\n{payload_hash}",
+            self.host
+        );
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+            sha256(canonical_request.as_bytes())
+        );
+        let key = [date.as_str(), &self.region, "s3", "aws4_request"]
+            .iter()
+            .fold(
+                format!("AWS4{}", self.secret_key).into_bytes(),
+                |key, part| hmac(&key, part),
+            );
+        let signature = hex(&hmac(&key, &string_to_sign));
+        ureq::request(method, &format!("{}{path}", self.endpoint))
+            .timeout(TIMEOUT)
+            .set("x-amz-content-sha256", &payload_hash)
+            .set("x-amz-date", &timestamp)
+            .set(
+                "Authorization",
+                &format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+                    self.access_key
+                ),
+            )
+    }
+}
+
+impl SyncBackend for S3 {
+    fn location(&self) -> String {
+        format!("{}/{}", self.endpoint, self.path)
+    }
+
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        match self.request("GET", name, b"").call() {
+            Ok(response) => {
+                let mut contents = Vec::new();
+                response.into_reader().read_to_end(&mut contents)?;
+                Ok(Some(contents))
+            }
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(err) => Err(err)?,
+        }
+    }
+
+    fn put(&self, name: &str, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.request("PUT", name, contents).send_bytes(contents)?;
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        self.request("DELETE", name, b"").call()?;
+        Ok(())
+    }
+}
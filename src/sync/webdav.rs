@@ -0,0 +1,102 @@
+use std::error::Error;
+use std::io::Read;
+use std::time::Duration;
+
+use base64::Engine;
+
+use super::SyncBackend;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A folder on a WebDAV server, like one of Nextcloud at
+/// `https://cloud.example.com/remote.php/dav/files/me/azk`.
+pub struct WebDav {
+    url: String,
+    authorization: Option<String>,
+}
+
+impl WebDav {
+    /// The folder at `url`, logging in with a username and password if
+    /// given.
+    pub fn new(url: &str, login: Option<(&str, String)>) -> WebDav {
+        WebDav {
+            url: url.trim_end_matches('/').to_owned(),
+            authorization: login.map(|(username, password)| {
+                let credentials = format!("{username}:{password}");
+                format!(
+                    "Basic {}",
+                    base64::engine::general_purpose::STANDARD.encode(credentials)
+                )
+            }),
+        }
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let request = ureq::request(method, url).timeout(TIMEOUT);
+        match &self.authorization {
+            Some(authorization) => request.set("Authorization", authorization),
+            None => request,
+        }
+    }
+
+    fn url(&self, name: &str) -> String {
+        format!("{}/{name}", self.url)
+    }
+
+    /// Creates the folder and those within it leading to the file `name`,
+    /// where missing.
+    fn create_folders(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        let mut url = self.url.clone();
+        let parts: Vec<&str> = name.split('/').collect();
+        for part in std::iter::once("").chain(parts[..parts.len() - 1].iter().copied()) {
+            if !part.is_empty() {
+                url = format!("{url}/{part}");
+            }
+            match self.request("MKCOL", &url).call() {
+                // 405 is a folder that is already there.
+                Ok(_) | Err(ureq::Error::Status(405, _)) => {}
+                Err(err) => Err(err)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SyncBackend for WebDav {
+    fn location(&self) -> String {
+        self.url.clone()
+    }
+
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        let url = self.url(name);
+        match self.request("GET", &url).call() {
+            Ok(response) => {
+                let mut contents = Vec::new();
+                response.into_reader().read_to_end(&mut contents)?;
+                Ok(Some(contents))
+            }
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(err) => Err(err)?,
+        }
+    }
+
+    fn put(&self, name: &str, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+        let url = self.url(name);
+        match self.request("PUT", &url).send_bytes(contents) {
+            Ok(_) => return Ok(()),
+            // 409 is a folder that is missing.
+            Err(ureq::Error::Status(409, _)) => self.create_folders(name)?,
+            Err(err) => Err(err)?,
+        }
+        self.request("PUT", &url).send_bytes(contents)?;
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        let url = self.url(name);
+        match self.request("DELETE", &url).call() {
+            Ok(_) | Err(ureq::Error::Status(404, _)) => Ok(()),
+            Err(err) => Err(err)?,
+        }
+    }
+}
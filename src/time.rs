@@ -0,0 +1,102 @@
+use std::error::Error;
+
+use chrono::{Datelike, Offset, TimeZone, Timelike};
+
+pub const SECONDS_PER_MINUTE: isize = 60;
+pub const SECONDS_PER_HOUR: isize = 60 * 60;
+
+/// Parses a stored `HH:MM:SS` time into seconds since midnight.
+pub fn parse_hhmmss(hhmmss: &str) -> Option<isize> {
+    let time = chrono::NaiveTime::parse_from_str(hhmmss, "%H:%M:%S").ok()?;
+    Some(time.num_seconds_from_midnight() as isize)
+}
+
+/// Like [`parse_hhmmss`], for times azk formatted itself.
+pub fn hhmmss_to_s(hhmmss: &str) -> isize {
+    parse_hhmmss(hhmmss).expect("times are formatted as HH:MM:SS")
+}
+
+/// The instant `s` seconds after midnight on the wall clock of `date` refers to
+/// in the local time zone. In the hour repeated when the clocks go back, the
+/// earlier one is taken; in the hour skipped when they go forward, there is none.
+pub fn local_timestamp(
+    date: chrono::NaiveDate,
+    s: isize,
+) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    let time = chrono::NaiveTime::from_num_seconds_from_midnight_opt(s as u32, 0)?;
+    let timestamp = chrono::Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()?;
+    Some(timestamp.with_timezone(&timestamp.offset().fix()))
+}
+
+pub fn s_to_hhmm(s: isize) -> String {
+    let hours = s / SECONDS_PER_HOUR;
+    let minutes = (s % SECONDS_PER_HOUR) / SECONDS_PER_MINUTE;
+
+    format!("{:02}:{:02}", hours, minutes)
+}
+
+/// Normalizes a user-supplied `HH:MM` or `HH:MM:SS` to the stored `HH:MM:SS` form.
+pub fn parse_hhmm(input: &str) -> Result<String, Box<dyn Error>> {
+    let time = chrono::NaiveTime::parse_from_str(input, "%H:%M:%S")
+        .or_else(|_| chrono::NaiveTime::parse_from_str(input, "%H:%M"))
+        .map_err(|_| format!("invalid time '{input}', expected HH:MM"))?;
+    Ok(format!("{}", time.format("%H:%M:%S")))
+}
+
+/// Parses a user-supplied `YYYY-MM-DD` day.
+pub fn parse_day(input: &str) -> Result<chrono::NaiveDate, Box<dyn Error>> {
+    Ok(chrono::NaiveDate::parse_from_str(input, "%Y-%m-%d")
+        .map_err(|_| format!("invalid day '{input}', expected YYYY-MM-DD"))?)
+}
+
+/// Parses a duration like `40:00`, where hours may exceed a day.
+pub fn hhmm_to_s(hhmm: &str) -> Result<isize, Box<dyn Error>> {
+    let invalid = || format!("invalid duration '{hhmm}', expected HH:MM");
+    let (h, m) = hhmm.split_once(':').ok_or_else(invalid)?;
+    let h = h.parse::<isize>().map_err(|_| invalid())?;
+    let m = m.parse::<isize>().map_err(|_| invalid())?;
+    Ok((h * SECONDS_PER_HOUR) + (m * SECONDS_PER_MINUTE))
+}
+
+pub fn s_to_signed_hhmm(s: isize) -> String {
+    let sign = if s < 0 { '-' } else { '+' };
+    format!("{sign}{}", s_to_hhmm(s.abs()))
+}
+
+/// Formats a duration in decimal hours, as in `7.75 h`.
+pub fn s_to_decimal(s: isize) -> String {
+    format!("{:.2} h", s as f64 / SECONDS_PER_HOUR as f64)
+}
+
+pub fn s_to_signed_decimal(s: isize) -> String {
+    let sign = if s < 0 { '-' } else { '+' };
+    format!("{sign}{}", s_to_decimal(s.abs()))
+}
+
+/// Parses an ISO week like `2024-W10` into its Monday.
+pub fn parse_iso_week(input: &str) -> Result<chrono::NaiveDate, Box<dyn Error>> {
+    let invalid = || format!("invalid week '{input}', expected YYYY-Www");
+    let (year, week) = input.split_once("-W").ok_or_else(invalid)?;
+    let year = year.parse::<i32>().map_err(|_| invalid())?;
+    let week = week.parse::<u32>().map_err(|_| invalid())?;
+    Ok(chrono::NaiveDate::from_isoywd_opt(year, week, chrono::Weekday::Mon).ok_or_else(invalid)?)
+}
+
+/// Parses a month like `2024-03` into its first day.
+pub fn parse_month(input: &str) -> Result<chrono::NaiveDate, Box<dyn Error>> {
+    Ok(
+        chrono::NaiveDate::parse_from_str(&format!("{input}-01"), "%Y-%m-%d")
+            .map_err(|_| format!("invalid month '{input}', expected YYYY-MM"))?,
+    )
+}
+
+pub fn last_day_of_month(first: chrono::NaiveDate) -> chrono::NaiveDate {
+    let (year, month) = if first.month() == 12 {
+        (first.year() + 1, 1)
+    } else {
+        (first.year(), first.month() + 1)
+    };
+    chrono::NaiveDate::from_ymd_opt(year, month, 1).unwrap() - chrono::Duration::days(1)
+}
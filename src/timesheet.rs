@@ -0,0 +1,101 @@
+//! A printable timesheet of a month, one row per day with its start, end,
+//! breaks and work, and lines for signatures.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use chrono::NaiveDate;
+
+use crate::config::Config;
+use crate::format;
+use crate::pdf::{self, Page};
+use crate::report::{self, BreakRule, Day};
+use crate::time::s_to_hhmm;
+
+/// The placeholders of `timesheet_title` for the month starting on `first`.
+pub fn title_vars(config: &Config, first: NaiveDate) -> Vec<(&'static str, String)> {
+    vec![
+        ("month", first.format("%Y-%m").to_string()),
+        ("month_name", first.format("%B %Y").to_string()),
+        ("name", config.timesheet_name.clone()),
+    ]
+}
+
+const LEFT: f32 = 50.0;
+const RIGHT: f32 = pdf::WIDTH - 50.0;
+const ROW: f32 = 16.0;
+const SIZE: f32 = 9.5;
+
+/// The timesheet of the month starting on `first` as a PDF, from its `days`
+/// with the `off` days noted, with durations formatted by `hours`.
+pub fn pdf(
+    config: &Config,
+    first: NaiveDate,
+    days: &[Day],
+    off: &BTreeMap<NaiveDate, String>,
+    rules: &[BreakRule],
+    hours: fn(isize) -> String,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut page = Page::default();
+    let title = format::render(&config.timesheet_title, &title_vars(config, first))?;
+    let mut y = pdf::HEIGHT - 60.0;
+    page.text(LEFT, y, 16.0, true, &title);
+    if !config.timesheet_name.is_empty() {
+        y -= 20.0;
+        page.text(LEFT, y, 11.0, false, &config.timesheet_name);
+    }
+
+    y -= 34.0;
+    page.text(LEFT, y, SIZE, true, "Date");
+    page.text_right(215.0, y, SIZE, true, "Start");
+    page.text_right(270.0, y, SIZE, true, "End");
+    page.text_right(335.0, y, SIZE, true, "Breaks");
+    page.text_right(400.0, y, SIZE, true, "Work");
+    page.text(420.0, y, SIZE, true, "Note");
+    page.line(LEFT, y - 5.0, RIGHT, y - 5.0, 0.8);
+
+    let mut total = 0;
+    for day in days {
+        y -= ROW;
+        page.text(
+            LEFT,
+            y,
+            SIZE,
+            false,
+            &day.date.format("%a %Y-%m-%d").to_string(),
+        );
+        if let Some(info) = day.info.as_ref().filter(|info| !info.intervals.is_empty()) {
+            let net = report::net_duration(info, rules);
+            total += net;
+            page.text_right(215.0, y, SIZE, false, &s_to_hhmm(info.start()));
+            page.text_right(270.0, y, SIZE, false, &s_to_hhmm(info.end()));
+            page.text_right(335.0, y, SIZE, false, &hours(info.break_duration()));
+            page.text_right(400.0, y, SIZE, false, &hours(net));
+        }
+        if let Some(kind) = off.get(&day.date) {
+            page.text(420.0, y, SIZE, false, kind);
+        }
+        page.line(LEFT, y - 5.0, RIGHT, y - 5.0, 0.2);
+    }
+
+    y -= ROW + 2.0;
+    page.text(LEFT, y, SIZE, true, "Total");
+    page.text_right(400.0, y, SIZE, true, &hours(total));
+
+    let signatures: Vec<&str> = config
+        .timesheet_signatures
+        .split(',')
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .collect();
+    if !signatures.is_empty() {
+        let gap = 30.0;
+        let width = (RIGHT - LEFT - gap * (signatures.len() - 1) as f32) / signatures.len() as f32;
+        for (index, label) in signatures.iter().enumerate() {
+            let x = LEFT + index as f32 * (width + gap);
+            page.line(x, 90.0, x + width, 90.0, 0.6);
+            page.text(x, 78.0, 8.0, false, &format!("Date, signature {label}"));
+        }
+    }
+    Ok(pdf::document(&[page]))
+}
@@ -0,0 +1,243 @@
+//! A cache of the work of every recorded day, so reports over many months
+//! only read the days that changed since. Like the balance cache, it is
+//! kept in the data directory and can be deleted at any time. Neither is
+//! kept while the days are encrypted, as they would tell about the days.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::model::DayInfo;
+use crate::report::{deduction, read_days, BreakRule};
+use crate::store::{data_dir, Store, ENCRYPTION_FILE};
+
+const CACHE_FILE: &str = "totals-cache.csv";
+
+/// A day's work as computed before. It stays valid as long as the day's
+/// modification time and the break rules are unchanged.
+#[derive(Deserialize, Serialize)]
+struct CacheEntry {
+    date: NaiveDate,
+    modified: u64,
+    break_rules: String,
+    net: isize,
+    deducted: isize,
+}
+
+/// Seconds since the epoch from which on changes are not cached, as a day
+/// changed again within the same second would keep its time.
+pub(crate) fn recent() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() - 1)
+}
+
+/// Whether the caches are kept, which they are not with the days encrypted.
+pub(crate) fn caching() -> bool {
+    data_dir().is_ok_and(|dir| !dir.join(ENCRYPTION_FILE).exists())
+}
+
+/// Removes the caches in `dir`, which are all named `*-cache.csv`.
+pub(crate) fn remove_caches(dir: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.to_string_lossy().ends_with("-cache.csv") {
+            match fs::remove_file(path) {
+                Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes `entries` to the cache `name` in the data dir through a temporary
+/// file of this process, so that it is never read half written. Does nothing
+/// unless [`caching`].
+pub(crate) fn write_cache<'a, T: Serialize + 'a>(
+    name: &str,
+    entries: impl IntoIterator<Item = &'a T>,
+) -> Result<(), Box<dyn Error>> {
+    if !caching() {
+        return Ok(());
+    }
+    let path = data_dir()?.join(name);
+    let tmp_path = path.with_extension(format!("csv.{}.tmp", std::process::id()));
+    let mut wtr = csv::Writer::from_path(&tmp_path)?;
+    for entry in entries {
+        wtr.serialize(entry)?;
+    }
+    wtr.flush()?;
+    drop(wtr);
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// A day's work after the deduction for short breaks, and the deduction.
+#[derive(Clone, Copy)]
+pub struct DayTotal {
+    pub net: isize,
+    pub deducted: isize,
+}
+
+/// Days with the total of those that are recorded.
+pub type Days = Vec<(NaiveDate, Option<DayTotal>)>;
+
+/// The [`DayTotal`] of recorded days, from the cache where it is still valid.
+pub struct Totals {
+    entries: BTreeMap<NaiveDate, CacheEntry>,
+    break_rules: String,
+    rules: Vec<BreakRule>,
+    /// See [`recent`].
+    recent: u64,
+    changed: bool,
+}
+
+impl Totals {
+    /// Reads the cache for deducting by `rules`. A cache that cannot be read
+    /// is rebuilt.
+    pub fn load(rules: &[BreakRule]) -> Totals {
+        let entries = if caching() {
+            data_dir()
+                .and_then(|dir| Ok(csv::Reader::from_path(dir.join(CACHE_FILE))?))
+                .map(|mut rdr| {
+                    rdr.deserialize::<CacheEntry>()
+                        .filter_map(Result::ok)
+                        .map(|entry| (entry.date, entry))
+                        .collect()
+                })
+                .unwrap_or_default()
+        } else {
+            BTreeMap::new()
+        };
+        Totals {
+            entries,
+            break_rules: rules
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+            rules: rules.to_vec(),
+            recent: recent(),
+            changed: false,
+        }
+    }
+
+    /// The totals of `dates`, which must be recorded, reading those not
+    /// cached in parallel. Only `today` counts an unfinished session up to
+    /// `now`, so it is never cached.
+    pub fn of(
+        &mut self,
+        store: &dyn Store,
+        dates: &[NaiveDate],
+        today: NaiveDate,
+        now: isize,
+    ) -> Result<Vec<DayTotal>, Box<dyn Error>> {
+        let mut totals = Vec::with_capacity(dates.len());
+        let mut missing = Vec::new();
+        for date in dates {
+            let modified = store.modified(*date)?;
+            let cached = self.entries.get(date).filter(|entry| {
+                *date != today
+                    && entry.modified == modified
+                    && entry.break_rules == self.break_rules
+            });
+            totals.push(cached.map(|entry| DayTotal {
+                net: entry.net,
+                deducted: entry.deducted,
+            }));
+            if cached.is_none() {
+                missing.push((*date, modified));
+            }
+        }
+
+        let dates: Vec<NaiveDate> = missing.iter().map(|(date, _)| *date).collect();
+        let mut read = missing
+            .into_iter()
+            .zip(read_days(store, &dates, today, now)?)
+            .map(|((date, modified), info)| self.add(date, modified, today, info));
+        Ok(totals
+            .into_iter()
+            .map(|total| total.unwrap_or_else(|| read.next().expect("a total per missing day")))
+            .collect())
+    }
+
+    /// The total of the day `info` read from `date`, cached unless it is
+    /// `today` or changed too recently.
+    fn add(
+        &mut self,
+        date: NaiveDate,
+        modified: u64,
+        today: NaiveDate,
+        info: Option<DayInfo>,
+    ) -> DayTotal {
+        let info = info.unwrap_or_default();
+        let deducted = deduction(&info, &self.rules);
+        let net = info.duration() - deducted;
+        if date != today && modified < self.recent {
+            self.entries.insert(
+                date,
+                CacheEntry {
+                    date,
+                    modified,
+                    break_rules: self.break_rules.clone(),
+                    net,
+                    deducted,
+                },
+            );
+            self.changed = true;
+        }
+        DayTotal { net, deducted }
+    }
+
+    /// Writes the cache back if anything was added to it, through a
+    /// temporary file.
+    pub fn save(self) -> Result<(), Box<dyn Error>> {
+        if !self.changed {
+            return Ok(());
+        }
+        write_cache(CACHE_FILE, self.entries.values())
+    }
+}
+
+/// The total of every day from `from` to `to`, `None` for days without
+/// records, through the cache.
+pub fn days(
+    store: &dyn Store,
+    from: NaiveDate,
+    to: NaiveDate,
+    today: NaiveDate,
+    now: isize,
+    rules: &[BreakRule],
+) -> Result<Days, Box<dyn Error>> {
+    let listed: Vec<NaiveDate> = store
+        .list_days()?
+        .into_iter()
+        .filter(|date| (from..=to).contains(date))
+        .collect();
+    let mut totals = Totals::load(rules);
+    let mut recorded = listed
+        .iter()
+        .copied()
+        .zip(totals.of(store, &listed, today, now)?)
+        .peekable();
+    totals.save()?;
+    Ok(from
+        .iter_days()
+        .take_while(|date| *date <= to)
+        .map(|date| {
+            (
+                date,
+                recorded
+                    .next_if(|(listed, _)| *listed == date)
+                    .map(|(_, total)| total),
+            )
+        })
+        .collect())
+}
@@ -0,0 +1,228 @@
+use std::error::Error;
+use std::time::Duration;
+
+use chrono::{Datelike, NaiveDate};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use azk::schedule::Schedule;
+use azk::store::{read_work_time, since_last_stamp, update_time, Store};
+use azk::time::{hhmmss_to_s, s_to_hhmm, s_to_signed_hhmm};
+use azk::{balance, config, hooks, off, report};
+
+use crate::edit;
+
+struct App<'a> {
+    store: &'a dyn Store,
+    date: NaiveDate,
+    schedule: Schedule,
+    rules: Vec<report::BreakRule>,
+    /// The region of the public holidays, see `config::Config::holidays`.
+    holidays: String,
+    balance: isize,
+    message: String,
+    /// Whether the last key was a stamp that was held back as too soon after
+    /// the previous one, so that pressing it again goes through.
+    stamp_held: bool,
+}
+
+fn now() -> (NaiveDate, String) {
+    let now = chrono::Local::now();
+    (now.date_naive(), format!("{}", now.format("%H:%M:%S")))
+}
+
+impl App<'_> {
+    fn refresh_balance(&mut self) -> Result<(), Box<dyn Error>> {
+        let (today, time) = now();
+        let first = self.store.list_days()?.first().copied().unwrap_or(today);
+        let off = off::read_with_holidays(&self.holidays, first, today)?;
+        self.balance = balance::monthly(
+            self.store,
+            today,
+            hhmmss_to_s(&time),
+            &self.schedule,
+            &self.rules,
+            &off,
+        )?
+        .iter()
+        .map(|month| month.balance)
+        .sum();
+        Ok(())
+    }
+
+    fn draw(&self, frame: &mut Frame) -> Result<(), Box<dyn Error>> {
+        let (today, time) = now();
+        let now_s = hhmmss_to_s(&time);
+        let day = read_work_time(self.store, self.date, (self.date == today).then_some(now_s))?
+            .unwrap_or_default();
+
+        let [header, body, footer, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(3),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [intervals, week] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(body);
+
+        let state = if day.ongoing() {
+            format!("Working for {}", s_to_hhmm(now_s - day.last))
+        } else if day.intervals.is_empty() {
+            "Not started".to_owned()
+        } else {
+            format!("On a break for {}", s_to_hhmm(now_s - day.last))
+        };
+        let title = format!("azk  {}  {state}", self.date.format("%a %Y-%m-%d"));
+        frame.render_widget(Paragraph::new(title), header);
+
+        let mut lines = Vec::new();
+        let mut previous_end = None;
+        for interval in &day.intervals {
+            if let Some(previous_end) = previous_end {
+                lines.push(format!(
+                    "break          {}",
+                    s_to_hhmm(interval.start - previous_end)
+                ));
+            }
+            let to_hhmm = if interval.ongoing {
+                "now  ".to_owned()
+            } else {
+                s_to_hhmm(interval.end)
+            };
+            lines.push(format!(
+                "{} - {to_hhmm}  {}  {}",
+                s_to_hhmm(interval.start),
+                s_to_hhmm(interval.duration()),
+                interval.project.as_deref().unwrap_or("")
+            ));
+            previous_end = Some(interval.end);
+        }
+        frame.render_widget(
+            List::new(lines).block(Block::bordered().title("Intervals")),
+            intervals,
+        );
+
+        let monday = self.date.week(chrono::Weekday::Mon).first_day();
+        let sunday = monday + chrono::Duration::days(6);
+        let mut week_total = 0;
+        let week_lines: Vec<Line> = report::days(self.store, monday, sunday, today, now_s)?
+            .into_iter()
+            .map(|report::Day { date, info }| {
+                let duration = info.map_or(0, |info| info.duration());
+                week_total += duration;
+                let marker = if date == self.date { ">" } else { " " };
+                Line::from(format!(
+                    "{marker} {}  {}",
+                    date.format("%a %m-%d"),
+                    s_to_hhmm(duration)
+                ))
+            })
+            .collect();
+        let iso_week = monday.iso_week();
+        let week_title = format!(
+            "Week {}-W{:02}  {}",
+            iso_week.year(),
+            iso_week.week(),
+            s_to_hhmm(week_total)
+        );
+        frame.render_widget(
+            List::new(week_lines).block(Block::bordered().title(week_title)),
+            week,
+        );
+
+        let summary = format!(
+            "Total {}  Target {}  Balance {}  {}",
+            s_to_hhmm(day.duration()),
+            s_to_hhmm(self.schedule.target(self.date)),
+            s_to_signed_hhmm(self.balance),
+            self.message
+        );
+        frame.render_widget(Paragraph::new(summary), footer);
+        frame.render_widget(
+            Paragraph::new("s stamp  e edit  ←/→ switch day  t today  q quit"),
+            help,
+        );
+        Ok(())
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), Box<dyn Error>> {
+        loop {
+            let mut result = Ok(());
+            terminal.draw(|frame| result = self.draw(frame))?;
+            result?;
+
+            if !event::poll(Duration::from_secs(1))? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let stamp_held = std::mem::take(&mut self.stamp_held);
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Left | KeyCode::Char('h') => self.date = self.date.pred_opt().unwrap(),
+                KeyCode::Right | KeyCode::Char('l') => self.date = self.date.succ_opt().unwrap(),
+                KeyCode::Char('t') => self.date = now().0,
+                KeyCode::Char('s') => {
+                    let (today, time) = now();
+                    let min_gap = config::load()?.min_gap as isize;
+                    let too_soon = since_last_stamp(self.store, today, &time)?
+                        .filter(|gap| (0..min_gap).contains(gap));
+                    self.message = if self.date != today {
+                        "Only today can be stamped.".to_owned()
+                    } else if let (Some(gap), false) = (too_soon, stamp_held) {
+                        self.stamp_held = true;
+                        format!("The last stamp was only {gap}s before, press s again to stamp.")
+                    } else {
+                        let record = update_time(self.store, today, &time, None, None, None)?;
+                        self.refresh_balance()?;
+                        let failures = record
+                            .map(|record| hooks::stamped(self.store, today, &record))
+                            .unwrap_or_default();
+                        match failures.first() {
+                            Some(failure) => format!("Stamped {time}, but {failure}"),
+                            None => format!("Stamped {time}."),
+                        }
+                    };
+                }
+                KeyCode::Char('e') => {
+                    ratatui::restore();
+                    let result = edit::edit(self.store, self.date);
+                    *terminal = ratatui::init();
+                    result?;
+                    self.refresh_balance()?;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Runs the interactive dashboard until quit.
+pub fn tui(store: &dyn Store) -> Result<(), Box<dyn Error>> {
+    let config = config::load()?;
+    let mut app = App {
+        store,
+        date: now().0,
+        schedule: config::schedule(&config)?,
+        rules: report::parse_break_rules(&config.break_rules)?,
+        holidays: config.holidays.clone(),
+        balance: 0,
+        message: String::new(),
+        stamp_held: false,
+    };
+    app.refresh_balance()?;
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
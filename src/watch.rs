@@ -0,0 +1,80 @@
+use std::error::Error;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use chrono::Offset;
+
+use azk::config::{self, Config};
+use azk::notify::BreakReminder;
+use azk::store::{read_work_time, Store};
+use azk::time::{hhmmss_to_s, s_to_hhmm};
+
+const HIDE_CURSOR: &str = "\x1b[?25l";
+const SHOW_CURSOR: &str = "\x1b[?25h";
+const CLEAR_LINE: &str = "\r\x1b[K";
+
+fn render(store: &dyn Store) -> Result<String, Box<dyn Error>> {
+    let now = chrono::Local::now();
+    let now_s = hhmmss_to_s(&format!("{}", now.format("%H:%M:%S")));
+    let Some(day) = read_work_time(store, now.date_naive(), Some(now_s))? else {
+        return Ok("Work hasn't started yet.".to_owned());
+    };
+    let state = if day.ongoing() {
+        "Working"
+    } else {
+        "On a break"
+    };
+    Ok(format!(
+        "{state} for {}  |  {} today",
+        s_to_hhmm(now_s - day.last),
+        s_to_hhmm(day.duration())
+    ))
+}
+
+/// Reminds of a break as configured, see [`BreakReminder::check`].
+fn remind(
+    store: &dyn Store,
+    config: &Config,
+    reminder: &mut BreakReminder,
+) -> Result<(), Box<dyn Error>> {
+    let now = chrono::Local::now();
+    let now_s = hhmmss_to_s(&format!("{}", now.format("%H:%M:%S")));
+    let Some(day) = read_work_time(store, now.date_naive(), Some(now_s))? else {
+        return Ok(());
+    };
+    reminder.check(config, &day, now.with_timezone(&now.offset().fix()))
+}
+
+/// Repaints the current session and today's total every second until Ctrl-C,
+/// reminding of breaks as configured.
+pub fn watch(store: &dyn Store) -> Result<(), Box<dyn Error>> {
+    let config = config::load()?;
+    let mut reminder = BreakReminder::default();
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_running = running.clone();
+    ctrlc::set_handler(move || handler_running.store(false, Ordering::SeqCst))?;
+
+    let mut stdout = io::stdout();
+    write!(stdout, "{HIDE_CURSOR}")?;
+    let result = (|| {
+        while running.load(Ordering::SeqCst) {
+            if let Err(err) = remind(store, &config, &mut reminder) {
+                writeln!(stdout, "{CLEAR_LINE}Cannot remind of breaks, {err}")?;
+            }
+            write!(stdout, "{CLEAR_LINE}{}", render(store)?)?;
+            stdout.flush()?;
+            for _ in 0..10 {
+                if !running.load(Ordering::SeqCst) {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+        Ok(())
+    })();
+    writeln!(stdout, "{SHOW_CURSOR}")?;
+    result
+}